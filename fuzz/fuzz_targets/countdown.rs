@@ -0,0 +1,24 @@
+//! Feeds arbitrary text through the whole query pipeline. Run with
+//! `cargo +nightly fuzz run countdown fuzz/corpus/countdown` from the repo
+//! root.
+#![no_main]
+
+use chrono::NaiveDate;
+use libfuzzer_sys::fuzz_target;
+use tuesdaybot::{pipeline::evaluate, units::CustomRegexCache};
+
+thread_local! {
+    static REGEX_CACHE: CustomRegexCache = CustomRegexCache::new(Vec::new());
+}
+
+fuzz_target!(|data: &[u8]| {
+    let s = match std::str::from_utf8(data) {
+        Ok(s) => s,
+        Err(_) => return,
+    };
+    // A Wednesday, so the countdown is as long as it gets
+    let now = NaiveDate::from_ymd(2026, 10, 14).and_hms(12, 34, 56);
+    let (value, unit) = REGEX_CACHE.with(|cache| evaluate(s, now, cache));
+    assert!(value.is_finite(), "{:?} gave {} {}", s, value, unit);
+    assert!(!unit.is_empty(), "{:?} gave no unit", s);
+});
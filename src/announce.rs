@@ -0,0 +1,174 @@
+use serenity::{
+    model::{
+        channel::ChannelType,
+        guild::Guild,
+        id::{ChannelId, UserId},
+    },
+    prelude::*,
+};
+
+/// Where something the bot says on its own, not in answer to anyone, ends
+/// up in a guild
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnnouncementTarget {
+    Channel(ChannelId),
+    /// Nowhere in the guild works, so tell the owner directly
+    OwnerDm(UserId),
+}
+
+/// Picks where to announce in `guild`, trying in order:
+/// 1. The `configured` channel, if we can post there
+/// 2. The system channel, if we can post there
+/// 3. The text channel nearest the top of the list that we can post in
+/// 4. A DM to the guild's owner
+pub fn announcement_target(
+    guild: &Guild,
+    configured: Option<ChannelId>,
+    own_id: UserId,
+) -> AnnouncementTarget {
+    let writable = |id: ChannelId| guild.user_permissions_in(id, own_id).send_messages();
+    if let Some(channel) = configured
+        .into_iter()
+        .chain(guild.system_channel_id)
+        .find(|&id| writable(id))
+    {
+        return AnnouncementTarget::Channel(channel);
+    }
+    let mut channels: Vec<(i64, ChannelId)> = guild
+        .channels
+        .values()
+        .filter_map(|channel| {
+            let channel = channel.read();
+            if channel.kind == ChannelType::Text && writable(channel.id) {
+                Some((channel.position, channel.id))
+            } else {
+                None
+            }
+        })
+        .collect();
+    channels.sort();
+    match channels.first() {
+        Some(&(_, channel)) => AnnouncementTarget::Channel(channel),
+        None => AnnouncementTarget::OwnerDm(guild.owner_id),
+    }
+}
+
+/// The channel to announce in for `guild`, following `announcement_target`,
+/// opening a DM with the owner if it comes to that. `None`, after logging
+/// why, if even that fails.
+pub fn pick_announcement_channel(
+    ctx: &Context,
+    guild: &Guild,
+    configured: Option<ChannelId>,
+) -> Option<ChannelId> {
+    let own_id = ctx.cache.read().user.id;
+    match announcement_target(guild, configured, own_id) {
+        AnnouncementTarget::Channel(channel) => Some(channel),
+        AnnouncementTarget::OwnerDm(owner) => match owner.create_dm_channel(&ctx.http) {
+            Ok(dm) => Some(dm.id),
+            Err(why) => {
+                println!(
+                    "Nowhere to announce in guild {}, and couldn't DM its owner: {:?}",
+                    guild.id, why
+                );
+                None
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Discord's send messages permission bit
+    const SEND_MESSAGES: u64 = 1 << 11;
+
+    const OWNER: UserId = UserId(99);
+    const BOT: UserId = UserId(1);
+
+    fn channel(id: u64, position: i64) -> serde_json::Value {
+        json!({
+            "id": id.to_string(),
+            "guild_id": "10",
+            "type": 0,
+            "name": format!("channel-{}", id),
+            "position": position,
+            "permission_overwrites": [],
+        })
+    }
+
+    /// Guild 10, where everyone, the bot included, has `permissions`
+    fn guild(permissions: u64, system_channel: Option<u64>) -> Guild {
+        serde_json::from_value(json!({
+            "id": "10",
+            "name": "Tuesday Club",
+            "owner_id": OWNER.0.to_string(),
+            "afk_timeout": 300,
+            "channels": [channel(21, 2), channel(22, 1), channel(23, 3)],
+            "default_message_notifications": 0,
+            "emojis": [],
+            "explicit_content_filter": 0,
+            "features": [],
+            "joined_at": "2024-01-02T00:00:00+00:00",
+            "large": false,
+            "member_count": 1,
+            "members": [],
+            "mfa_level": 0,
+            "preferred_locale": "en-US",
+            "presences": [],
+            "region": "us-east",
+            "roles": [{
+                "id": "10",
+                "name": "@everyone",
+                "color": 0,
+                "hoist": false,
+                "managed": false,
+                "mentionable": false,
+                "permissions": permissions,
+                "position": 0,
+            }],
+            "system_channel_id": system_channel.map(|id| id.to_string()),
+            "verification_level": 0,
+            "voice_states": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn prefers_the_configured_channel() {
+        let guild = guild(SEND_MESSAGES, Some(23));
+        assert_eq!(
+            announcement_target(&guild, Some(ChannelId(21)), BOT),
+            AnnouncementTarget::Channel(ChannelId(21))
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_system_channel() {
+        let guild = guild(SEND_MESSAGES, Some(23));
+        assert_eq!(
+            announcement_target(&guild, None, BOT),
+            AnnouncementTarget::Channel(ChannelId(23))
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_topmost_channel() {
+        let guild = guild(SEND_MESSAGES, None);
+        assert_eq!(
+            announcement_target(&guild, None, BOT),
+            AnnouncementTarget::Channel(ChannelId(22))
+        );
+    }
+
+    #[test]
+    fn dms_the_owner_when_nowhere_is_writable() {
+        let guild = guild(0, Some(23));
+        assert_eq!(
+            announcement_target(&guild, Some(ChannelId(21)), BOT),
+            AnnouncementTarget::OwnerDm(OWNER)
+        );
+    }
+}
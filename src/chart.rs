@@ -0,0 +1,119 @@
+/// Widest a bar in the chart can be, keeping replies well under Discord's
+/// length limit
+pub const MAX_BAR_WIDTH: usize = 40;
+
+const BREAKDOWN_UNITS: [(&str, i64); 5] = [
+    ("weeks", 604_800_000),
+    ("days", 86_400_000),
+    ("hours", 3_600_000),
+    ("minutes", 60_000),
+    ("seconds", 1000),
+];
+
+/// Splits `ms` into whole weeks, days, hours, minutes and seconds, returning
+/// each unit's count and how many milliseconds it accounts for
+pub fn breakdown(ms: i64) -> Vec<(&'static str, i64, i64)> {
+    let mut rest = ms.max(0);
+    BREAKDOWN_UNITS
+        .iter()
+        .map(|&(name, size)| {
+            let count = rest / size;
+            rest -= count * size;
+            (name, count, count * size)
+        })
+        .collect()
+}
+
+/// Each part's fraction of `total`, in the same order
+pub fn shares(parts: &[i64], total: i64) -> Vec<f64> {
+    parts
+        .iter()
+        .map(|&part| {
+            if total <= 0 {
+                0.0
+            } else {
+                part as f64 / total as f64
+            }
+        })
+        .collect()
+}
+
+/// A code block with one bar per unit of the breakdown of `ms`, each as
+/// long as its share of the total, out of `width` characters
+pub fn bar_chart(ms: i64, width: usize) -> String {
+    let width = width.clamp(1, MAX_BAR_WIDTH);
+    let parts = breakdown(ms);
+    let amounts: Vec<i64> = parts.iter().map(|&(_, _, amount)| amount).collect();
+    let shares = shares(&amounts, ms);
+    let mut lines = Vec::new();
+    for (&(name, count, _), share) in parts.iter().zip(shares) {
+        // Leave out units a countdown this short doesn't reach yet
+        if count == 0 && lines.is_empty() {
+            continue;
+        }
+        let filled = (share * width as f64).round() as usize;
+        lines.push(format!(
+            "{:<7} |{}{}| {}",
+            name,
+            "█".repeat(filled),
+            " ".repeat(width - filled.min(width)),
+            count
+        ));
+    }
+    // Under a second, all of what's left is a fraction of one
+    if lines.is_empty() {
+        lines.push(format!("{:<7} |{}| <1", "seconds", "█".repeat(width)));
+    }
+    format!("```\n{}\n```", lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY_MS: i64 = 86_400_000;
+    const HOUR_MS: i64 = 3_600_000;
+
+    #[test]
+    fn breaks_the_wait_into_whole_units() {
+        let parts = breakdown(8 * DAY_MS + 3 * HOUR_MS + 5500);
+        let counts: Vec<i64> = parts.iter().map(|&(_, count, _)| count).collect();
+        assert_eq!(counts, vec![1, 1, 3, 0, 5]);
+        assert_eq!(parts[1], ("days", 1, DAY_MS));
+    }
+
+    #[test]
+    fn gives_each_part_its_share() {
+        assert_eq!(shares(&[3, 1], 4), vec![0.75, 0.25]);
+        assert_eq!(shares(&[3, 1], 0), vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn draws_bars_in_proportion() {
+        let chart = bar_chart(3 * DAY_MS + DAY_MS, 8);
+        assert_eq!(
+            chart,
+            "```\n\
+             days    |████████| 4\n\
+             hours   |        | 0\n\
+             minutes |        | 0\n\
+             seconds |        | 0\n\
+             ```"
+        );
+        let chart = bar_chart(DAY_MS + 12 * HOUR_MS, 3);
+        assert!(chart.contains("days    |██ | 1"), "{}", chart);
+        assert!(chart.contains("hours   |█  | 12"), "{}", chart);
+    }
+
+    #[test]
+    fn fills_the_seconds_bar_under_a_second() {
+        assert_eq!(bar_chart(400, 5), "```\nseconds |█████| <1\n```");
+    }
+
+    #[test]
+    fn keeps_the_width_in_bounds() {
+        let chart = bar_chart(400, 1000);
+        assert!(chart.contains(&"█".repeat(MAX_BAR_WIDTH)));
+        assert!(!chart.contains(&"█".repeat(MAX_BAR_WIDTH + 1)));
+    }
+}
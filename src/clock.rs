@@ -0,0 +1,85 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use chrono::{DateTime, Duration, Utc};
+
+/// Milliseconds added to the real time by `tue!timeshift`, so the bot can be
+/// made to think it's nearly Tuesday without touching the system clock
+static OFFSET_MS: AtomicI64 = AtomicI64::new(0);
+
+/// The current time as far as the bot is concerned. Everything that reads
+/// the wall clock should go through this so a timeshift applies everywhere.
+pub fn now() -> DateTime<Utc> {
+    shifted(Utc::now(), offset())
+}
+
+/// `real` moved by `offset`, leaving it alone if that would overflow
+pub fn shifted(real: DateTime<Utc>, offset: Duration) -> DateTime<Utc> {
+    real.checked_add_signed(offset).unwrap_or(real)
+}
+
+pub fn offset() -> Duration {
+    Duration::milliseconds(OFFSET_MS.load(Ordering::Relaxed))
+}
+
+pub fn set_offset(offset: Duration) {
+    OFFSET_MS.store(offset.num_milliseconds(), Ordering::Relaxed);
+}
+
+/// Reads a shift like "+3d", "-90m" or "2h", in seconds, minutes, hours,
+/// days, or weeks. No sign means forwards.
+pub fn parse_shift(s: &str) -> Option<Duration> {
+    let (sign, rest) = match s.chars().next()? {
+        '+' => (1, &s[1..]),
+        '-' => (-1, &s[1..]),
+        _ => (1, s),
+    };
+    let split = rest.len().checked_sub(1)?;
+    if !rest.is_char_boundary(split) {
+        return None;
+    }
+    let amount: i64 = rest[..split].parse().ok()?;
+    let unit = match &rest[split..] {
+        "s" => Duration::seconds(1),
+        "m" => Duration::minutes(1),
+        "h" => Duration::hours(1),
+        "d" => Duration::days(1),
+        "w" => Duration::weeks(1),
+        _ => return None,
+    };
+    // Anything past a few thousand years is surely a typo
+    if amount.abs() > 1_000_000 {
+        return None;
+    }
+    Some(unit * (sign * amount) as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_shifts_in_each_unit() {
+        assert_eq!(parse_shift("+3d"), Some(Duration::days(3)));
+        assert_eq!(parse_shift("-90m"), Some(Duration::minutes(-90)));
+        assert_eq!(parse_shift("2h"), Some(Duration::hours(2)));
+        assert_eq!(parse_shift("1w"), Some(Duration::weeks(1)));
+        assert_eq!(parse_shift("45s"), Some(Duration::seconds(45)));
+    }
+
+    #[test]
+    fn rejects_unreadable_shifts() {
+        assert_eq!(parse_shift(""), None);
+        assert_eq!(parse_shift("+"), None);
+        assert_eq!(parse_shift("3"), None);
+        assert_eq!(parse_shift("3y"), None);
+        assert_eq!(parse_shift("3✨"), None);
+        assert_eq!(parse_shift("9999999d"), None);
+    }
+
+    #[test]
+    fn ignores_shifts_that_would_overflow() {
+        let real = Utc::now();
+        assert_eq!(shifted(real, Duration::hours(1)), real + Duration::hours(1));
+        assert_eq!(shifted(real, Duration::max_value()), real);
+    }
+}
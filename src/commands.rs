@@ -0,0 +1,1978 @@
+use std::{
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    Datelike, NaiveDate, NaiveDateTime, NaiveTime, Weekday,
+};
+use serenity::{
+    model::{channel::Message, id::GuildId},
+    prelude::*,
+    utils::MessageBuilder,
+};
+
+use crate::{
+    chart::MAX_BAR_WIDTH,
+    clock,
+    cooldown::{cooldown_message, CooldownKey, TriggerCooldownKey},
+    dates::{next_weekday_at, HOLIDAY_CALENDARS},
+    format::{format_clock, format_percent, MAX_PERCENT_DECIMALS},
+    offset::parse_offset,
+    pages::send_list,
+    response::{describe_grid, exact_response, tuesdays_left_response},
+    send::report_send_error,
+    state::{
+        Granularity, GuildConfig, LeapDayPolicy, MentionMode, MultiDayPolicy, NumberStyle,
+        PercentAnchor, Proximity, ReplyMode, ResponseStyle, SmallValueStyle, SpoilerPolicy,
+        TimeFormat, Vacation,
+    },
+    status::{status_lines, GuildStatus},
+    target::target_time,
+    timezone::{parse_utc_offset, zone_source_for, Zone},
+    units::{time_unit_names, TIME_UNITS},
+    ConfigKey, RegexKey, StateKey,
+};
+
+/// A command's handler, given the words after its name. `None` means it
+/// already answered, or has nothing to say.
+pub type Command = fn(&Context, &Message, &[&str]) -> Option<String>;
+
+/// Every command `dispatch` understands, in the order `tue!help` lists them
+pub const COMMANDS: &[(&str, Command)] = &[
+    ("role", |ctx, msg, _| role(ctx, msg)),
+    ("numbers", |ctx, msg, args| Some(numbers(ctx, msg, args))),
+    ("elapsed", |ctx, msg, args| Some(elapsed(ctx, msg, args))),
+    ("occasion", |ctx, msg, args| Some(occasion(ctx, msg, args))),
+    ("smallvalues", |ctx, msg, args| {
+        Some(small_values(ctx, msg, args))
+    }),
+    ("tuesdays", |ctx, msg, args| Some(tuesdays(ctx, msg, args))),
+    ("strict", |ctx, msg, args| Some(strict(ctx, msg, args))),
+    ("finalcountdown", |ctx, msg, args| {
+        Some(final_countdown(ctx, msg, args))
+    }),
+    ("eventchannel", |ctx, msg, args| {
+        Some(event_channel(ctx, msg, args))
+    }),
+    ("topicchannel", |ctx, msg, args| {
+        Some(topic_channel(ctx, msg, args))
+    }),
+    ("timezone", |ctx, msg, args| Some(timezone(ctx, msg, args))),
+    ("today", |ctx, msg, _| Some(today(ctx, msg))),
+    ("alias", |ctx, msg, args| {
+        if is_list(args) {
+            list_aliases(ctx, msg)
+        } else {
+            Some(alias(ctx, msg, args))
+        }
+    }),
+    ("spoilers", |ctx, msg, args| Some(spoilers(ctx, msg, args))),
+    ("maintenance", |ctx, msg, args| {
+        Some(maintenance(ctx, msg, args))
+    }),
+    ("vacation", |ctx, msg, args| Some(vacation(ctx, msg, args))),
+    ("grid", |ctx, msg, args| Some(grid(ctx, msg, args))),
+    ("when", |ctx, msg, args| Some(when(ctx, msg, args))),
+    ("timeformat", |ctx, msg, args| {
+        Some(time_format(ctx, msg, args))
+    }),
+    ("dadmode", |ctx, msg, args| Some(dad_mode(ctx, msg, args))),
+    ("style", |ctx, msg, args| Some(style(ctx, msg, args))),
+    ("occasiontime", |ctx, msg, args| {
+        Some(occasion_time(ctx, msg, args))
+    }),
+    ("mytimezone", |ctx, msg, args| {
+        Some(my_timezone(ctx, msg, args))
+    }),
+    ("whoami", |ctx, msg, _| Some(whoami(ctx, msg))),
+    ("tuesdayrole", |ctx, msg, args| {
+        Some(tuesday_role(ctx, msg, args))
+    }),
+    ("optin", |ctx, msg, _| Some(opt_in(ctx, msg, true))),
+    ("optout", |ctx, msg, _| Some(opt_in(ctx, msg, false))),
+    ("multiday", |ctx, msg, args| Some(multi_day(ctx, msg, args))),
+    ("granularity", |ctx, msg, args| {
+        Some(granularity(ctx, msg, args))
+    }),
+    ("mentions", |ctx, msg, args| Some(mentions(ctx, msg, args))),
+    ("allstatus", |ctx, msg, _| all_status(ctx, msg)),
+    ("proximity", |ctx, msg, args| {
+        Some(proximity(ctx, msg, args))
+    }),
+    ("until", |ctx, msg, args| Some(until(ctx, msg, args))),
+    ("replies", |ctx, msg, args| Some(replies(ctx, msg, args))),
+    ("timeshift", |ctx, msg, args| {
+        Some(timeshift(ctx, msg, args))
+    }),
+    ("quotes", |ctx, msg, args| Some(quotes(ctx, msg, args))),
+    ("unitnames", |ctx, msg, args| {
+        Some(unit_names(ctx, msg, args))
+    }),
+    ("exact", |ctx, msg, _| Some(exact(ctx, msg))),
+    ("help", |_, _, _| Some(help())),
+    ("showdate", |ctx, msg, args| Some(show_date(ctx, msg, args))),
+    ("freeze", |ctx, msg, args| {
+        if is_list(args) {
+            list_frozen_dates(ctx, msg)
+        } else {
+            Some(freeze(ctx, msg, args))
+        }
+    }),
+    ("percent", |ctx, msg, args| Some(percent(ctx, msg, args))),
+    ("units", |ctx, msg, args| Some(units(ctx, msg, args))),
+    ("wholeweeks", |ctx, msg, args| {
+        Some(whole_weeks(ctx, msg, args))
+    }),
+    ("triggers", |ctx, msg, args| Some(triggers(ctx, msg, args))),
+    ("explain", |ctx, msg, args| Some(explain(ctx, msg, args))),
+    ("holidays", |ctx, msg, args| {
+        if is_list(args) {
+            list_holidays(ctx, msg)
+        } else {
+            Some(holidays(ctx, msg, args))
+        }
+    }),
+    ("impatience", |ctx, msg, args| {
+        Some(impatience(ctx, msg, args))
+    }),
+];
+
+/// The built-in command called `name`, if there is one
+fn find_command(name: &str) -> Option<Command> {
+    COMMANDS
+        .iter()
+        .find(|&&(command, _)| command == name)
+        .map(|&(_, run)| run)
+}
+
+/// Longest name `tue!occasion` will accept, in characters
+const MAX_OCCASION_LEN: usize = 32;
+
+/// Longest display name `tue!unitnames` will accept for a unit, in characters
+const MAX_UNIT_NAME_LEN: usize = 20;
+
+/// Longest alias name and expansion `tue!alias` will accept, in characters
+const MAX_ALIAS_LEN: usize = 20;
+const MAX_ALIAS_QUERY_LEN: usize = 200;
+
+/// Longest `tue!proximity emoji` will accept for each emoji, in characters.
+/// Long enough for joined sequences like families and flags.
+const MAX_EMOJI_LEN: usize = 12;
+
+/// Longest date format `tue!showdate` will accept, in characters, so the
+/// date can't crowd the countdown out of its message
+const MAX_DATE_FORMAT_LEN: usize = 100;
+
+/// Handles everything after the `tue!` prefix. `rest` keeps the original
+/// casing so commands can take free-form arguments.
+pub fn dispatch(ctx: &Context, msg: &Message, rest: &str) {
+    let mut words = rest.split_whitespace();
+    let command = words.next().unwrap_or("").to_ascii_lowercase();
+    let args: Vec<&str> = words.collect();
+
+    if in_maintenance(ctx) && command != "maintenance" {
+        println!("Ignoring tue!{} during maintenance", command);
+        notify_maintenance(ctx, msg);
+        return;
+    }
+
+    let reply = if let Some(run) = find_command(&command) {
+        match check_cooldown(ctx, msg, &command) {
+            Some(remaining) => Some(cooldown_message(&command, remaining)),
+            None => run(ctx, msg, &args),
+        }
+    } else {
+        let query = match guild_config(ctx, msg).aliases.get(&command) {
+            Some(query) => query.clone(),
+            None => return,
+        };
+        match check_cooldown(ctx, msg, &command) {
+            Some(remaining) => Some(cooldown_message(&command, remaining)),
+            None => expand_alias(ctx, msg, &query),
+        }
+    };
+
+    if let Some(reply) = reply {
+        if let Err(why) = msg.channel_id.say(&ctx.http, &reply) {
+            println!("Error sending message: {:?}", why);
+        }
+    }
+}
+
+/// Whether the bot is in maintenance mode, and so shouldn't answer anything
+pub fn in_maintenance(ctx: &Context) -> bool {
+    let data = ctx.data.read();
+    data.get::<StateKey>()
+        .expect("Expected to find bot state in context")
+        .maintenance
+}
+
+/// Lets an admin know the bot is ignoring them on purpose, if the operator
+/// wants that.
+pub fn notify_maintenance(ctx: &Context, msg: &Message) {
+    let notice = {
+        let data = ctx.data.read();
+        data.get::<ConfigKey>()
+            .expect("Expected to find config in context")
+            .maintenance_notice
+    };
+    if notice && is_admin(ctx, msg) {
+        let reply = "I'm under maintenance right now, so I'm not answering anything.";
+        if let Err(why) = msg.channel_id.say(&ctx.http, reply) {
+            println!("Error sending message: {:?}", why);
+        }
+    }
+}
+
+/// Runs the query an alias stands for. That's either a built-in command,
+/// which can't be another alias so they can't loop, or anything that would
+/// otherwise trigger a countdown. Countdowns are sent right away, the same
+/// way as the implicit trigger's, so the guild's mention and reply modes
+/// apply to them.
+fn expand_alias(ctx: &Context, msg: &Message, query: &str) -> Option<String> {
+    if query
+        .get(..4)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("tue!"))
+    {
+        let mut words = query[4..].split_whitespace();
+        let command = words.next().unwrap_or("").to_ascii_lowercase();
+        let args: Vec<&str> = words.collect();
+        return find_command(&command).and_then(|run| run(ctx, msg, &args));
+    }
+    let output = crate::Handler::countdown_message(
+        query.to_ascii_lowercase(),
+        ctx,
+        msg.guild_id,
+        Some(msg.author.id),
+    );
+    if let Err(why) = crate::Handler::send(ctx, msg.channel_id, msg.guild_id, &output, Some(msg.id))
+    {
+        report_send_error(&why);
+    }
+    None
+}
+
+/// Returns how long the author still has to wait before running `command`,
+/// if it has a cooldown configured and they used it too recently.
+fn check_cooldown(ctx: &Context, msg: &Message, command: &str) -> Option<Duration> {
+    let mut data = ctx.data.write();
+    let cooldown = data
+        .get::<ConfigKey>()
+        .expect("Expected to find config in context")
+        .command_cooldowns
+        .get(command)
+        .copied()?;
+    data.get_mut::<CooldownKey>()
+        .expect("Expected to find cooldowns in context")
+        .check(
+            (command.to_string(), msg.author.id.0),
+            Duration::from_secs(cooldown),
+            Instant::now(),
+        )
+}
+
+/// Whether the author of `msg` runs the bot
+fn is_owner(ctx: &Context, msg: &Message) -> bool {
+    let data = ctx.data.read();
+    data.get::<ConfigKey>()
+        .expect("Expected to find config in context")
+        .owner_id
+        == Some(msg.author.id.0)
+}
+
+/// Whether the author of `msg` may change the settings of the guild it was
+/// sent in.
+pub fn is_admin(ctx: &Context, msg: &Message) -> bool {
+    match msg.guild(&ctx.cache) {
+        Some(guild) => {
+            let permissions = guild.read().member_permissions(msg.author.id);
+            permissions.administrator() || permissions.manage_guild()
+        }
+        None => false,
+    }
+}
+
+fn role(ctx: &Context, msg: &Message) -> Option<String> {
+    let roles = msg
+        .mention_roles
+        .iter()
+        .map(|roleid| roleid.to_string())
+        .collect();
+    send_list(ctx, msg.channel_id, "Roles mentioned", roles);
+    None
+}
+
+/// Settings for the guild `msg` was sent in
+pub fn guild_config(ctx: &Context, msg: &Message) -> GuildConfig {
+    let data = ctx.data.read();
+    data.get::<StateKey>()
+        .expect("Expected to find bot state in context")
+        .guild(msg.guild_id.map(|id| id.0))
+}
+
+/// Applies `change` to the settings of the guild `msg` was sent in and saves
+/// them, as long as the author is allowed to. Returns `reply` on success, or
+/// an explanation of why nothing changed.
+fn update_guild<F>(ctx: &Context, msg: &Message, reply: String, change: F) -> String
+where
+    F: FnOnce(&mut GuildConfig),
+{
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return "That setting only works in a server.".to_string(),
+    };
+    if !is_admin(ctx, msg) {
+        return "You need the Manage Server permission to do that.".to_string();
+    }
+
+    let mut data = ctx.data.write();
+    let state = data
+        .get_mut::<StateKey>()
+        .expect("Expected to find bot state in context");
+    change(state.guild_mut(guild_id.0));
+    state.save();
+    reply
+}
+
+/// Reads a channel mention like `<#1234>`, or a bare channel ID
+fn parse_channel(arg: &str) -> Option<u64> {
+    arg.trim_start_matches("<#")
+        .trim_end_matches('>')
+        .parse()
+        .ok()
+}
+
+/// Reads an `on`/`off` argument
+fn parse_toggle(args: &[&str]) -> Option<bool> {
+    match args.first().map(|s| s.to_ascii_lowercase()).as_deref() {
+        Some("on") => Some(true),
+        Some("off") => Some(false),
+        _ => None,
+    }
+}
+
+/// `tue!numbers digits|words|binary|octal|hex|engineering`
+fn numbers(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let style = match args.first().map(|s| s.to_ascii_lowercase()).as_deref() {
+        Some("digits") => NumberStyle::Digits,
+        Some("words") => NumberStyle::Words,
+        Some("binary") => NumberStyle::Binary,
+        Some("octal") => NumberStyle::Octal,
+        Some("hex") => NumberStyle::Hex,
+        Some("engineering") => NumberStyle::Engineering,
+        _ => return "Usage: tue!numbers digits|words|binary|octal|hex|engineering".to_string(),
+    };
+    let reply = format!(
+        "Numbers will now be shown as {}.",
+        args[0].to_ascii_lowercase()
+    );
+    update_guild(ctx, msg, reply, |guild| guild.number_style = style)
+}
+
+/// `tue!elapsed on|off`
+fn elapsed(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let show = match parse_toggle(args) {
+        Some(show) => show,
+        None => return "Usage: tue!elapsed on|off".to_string(),
+    };
+    let reply = if show {
+        "I'll also say how long it's been since Tuesday."
+    } else {
+        "I'll only count down to the next Tuesday."
+    };
+    update_guild(ctx, msg, reply.to_string(), |guild| {
+        guild.show_elapsed = show
+    })
+}
+
+/// Strips anything from user-supplied text that could ping someone or break
+/// the formatting of whatever it's echoed into.
+fn plain_text(raw: &str) -> String {
+    let cleaned: String = raw
+        .chars()
+        .filter(|c| !c.is_control() && !"@#<>*_~`|\\\"".contains(*c))
+        .collect();
+    cleaned.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Cleans up a user-supplied occasion name so it can't ping anyone or break
+/// the formatting of the sentence it's dropped into.
+fn sanitize_occasion(raw: &str) -> Result<String, String> {
+    let cleaned = plain_text(raw);
+    if cleaned.is_empty() {
+        return Err("That name is empty once formatting is removed.".to_string());
+    }
+    if cleaned.chars().count() > MAX_OCCASION_LEN {
+        return Err(format!(
+            "Occasion names can be at most {} characters.",
+            MAX_OCCASION_LEN
+        ));
+    }
+    Ok(cleaned)
+}
+
+/// `tue!occasion "Taco Tuesday"`, or `tue!occasion reset`
+fn occasion(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let raw = args.join(" ");
+    if raw.is_empty() {
+        return "Usage: tue!occasion \"Taco Tuesday\" or tue!occasion reset".to_string();
+    }
+    if raw.eq_ignore_ascii_case("reset") {
+        return update_guild(
+            ctx,
+            msg,
+            "Back to plain old Tuesday.".to_string(),
+            |guild| guild.occasion = None,
+        );
+    }
+    match sanitize_occasion(&raw) {
+        Ok(name) => {
+            let reply = format!("Counting down to {} from now on.", name);
+            update_guild(ctx, msg, reply, |guild| guild.occasion = Some(name))
+        }
+        Err(why) => why,
+    }
+}
+
+/// `tue!smallvalues plain|scientific|note [threshold]`
+fn small_values(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    const USAGE: &str = "Usage: tue!smallvalues plain|scientific|note [threshold]";
+    let style = match args.first().map(|s| s.to_ascii_lowercase()).as_deref() {
+        Some("plain") => SmallValueStyle::Plain,
+        Some("scientific") => SmallValueStyle::Scientific,
+        Some("note") => SmallValueStyle::Note,
+        _ => return USAGE.to_string(),
+    };
+    let threshold = match args.get(1).map(|s| s.parse::<f64>()) {
+        None => None,
+        Some(Ok(threshold)) if threshold.is_finite() && threshold > 0.0 => Some(threshold),
+        Some(_) => return USAGE.to_string(),
+    };
+    let reply = format!(
+        "Small values will now be shown as {}.",
+        args[0].to_ascii_lowercase()
+    );
+    update_guild(ctx, msg, reply, |guild| {
+        guild.small_values = style;
+        if let Some(threshold) = threshold {
+            guild.small_value_threshold = threshold;
+        }
+    })
+}
+
+/// `tue!tuesdays [list]`
+fn tuesdays(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let list = args
+        .first()
+        .is_some_and(|arg| arg.eq_ignore_ascii_case("list"));
+    let today = zone_source_for(&ctx.data.read(), msg.guild_id, Some(msg.author.id))
+        .0
+        .now()
+        .date();
+    tuesdays_left_response(today, list, &guild_config(ctx, msg))
+}
+
+/// `tue!strict on|off`
+fn strict(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let strict = match parse_toggle(args) {
+        Some(strict) => strict,
+        None => return "Usage: tue!strict on|off".to_string(),
+    };
+    let reply = if strict {
+        "I'll only answer tue! commands from now on."
+    } else {
+        "I'll answer whenever someone mentions Tuesday again."
+    };
+    update_guild(ctx, msg, reply.to_string(), |guild| {
+        guild.strict_mode = strict
+    })
+}
+
+/// `tue!quotes ignore|answer`, for "tues" in `> quoted` lines
+fn quotes(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let (ignore, reply) = match args.first().map(|s| s.to_ascii_lowercase()).as_deref() {
+        Some("ignore") => (true, "I'll ignore Tuesday in quoted lines."),
+        Some("answer") => (false, "I'll answer Tuesday in quoted lines too."),
+        _ => return "Usage: tue!quotes ignore|answer".to_string(),
+    };
+    update_guild(ctx, msg, reply.to_string(), |guild| {
+        guild.ignore_quotes = ignore
+    })
+}
+
+/// `tue!finalcountdown <seconds>`, where 0 turns it off
+fn final_countdown(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let secs = match args.first().map(|s| s.parse::<f64>()) {
+        Some(Ok(secs)) if secs.is_finite() && secs >= 0.0 => secs,
+        _ => return "Usage: tue!finalcountdown <seconds>".to_string(),
+    };
+    let reply = format!(
+        "I'll count down in tenths of a second for the last {} seconds.",
+        secs
+    );
+    update_guild(ctx, msg, reply, |guild| guild.final_countdown_secs = secs)
+}
+
+/// Reads a `#channel|off` argument, where `off` is `Some(None)`
+fn parse_channel_or_off(args: &[&str]) -> Option<Option<u64>> {
+    match args.first() {
+        Some(arg) if arg.eq_ignore_ascii_case("off") => Some(None),
+        Some(arg) => parse_channel(arg).map(Some),
+        None => None,
+    }
+}
+
+/// `tue!eventchannel #channel|off`
+fn event_channel(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let channel = match parse_channel_or_off(args) {
+        Some(channel) => channel,
+        None => return "Usage: tue!eventchannel #channel|off".to_string(),
+    };
+    let reply = match channel {
+        Some(channel) => format!("I'll point out Tuesday events in <#{}>.", channel),
+        None => "I'll stop commenting on Tuesday events.".to_string(),
+    };
+    update_guild(ctx, msg, reply, |guild| guild.event_channel = channel)
+}
+
+/// `tue!topicchannel #channel|off`
+fn topic_channel(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let channel = match parse_channel_or_off(args) {
+        Some(channel) => channel,
+        None => return "Usage: tue!topicchannel #channel|off".to_string(),
+    };
+    let reply = match channel {
+        Some(channel) => format!("I'll keep the topic of <#{}> counting down.", channel),
+        None => "I'll stop updating the countdown topic.".to_string(),
+    };
+    update_guild(ctx, msg, reply, |guild| guild.topic_channel = channel)
+}
+
+/// `tue!timezone UTC+2`, or `tue!timezone reset` to go back to the default
+fn timezone(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let raw = args.join(" ");
+    if raw.eq_ignore_ascii_case("reset") {
+        return update_guild(
+            ctx,
+            msg,
+            "Back to the default timezone.".to_string(),
+            |guild| guild.utc_offset_minutes = None,
+        );
+    }
+    match parse_utc_offset(&raw) {
+        Some(offset) => {
+            let reply = format!(
+                "Counting down in {} from now on.",
+                Zone::Fixed(offset).name()
+            );
+            let minutes = offset.local_minus_utc() / 60;
+            update_guild(ctx, msg, reply, |guild| {
+                guild.utc_offset_minutes = Some(minutes)
+            })
+        }
+        None => "Usage: tue!timezone UTC+2, or tue!timezone reset".to_string(),
+    }
+}
+
+/// Says what day it is at `now` in `zone`
+pub fn today_response(now: NaiveDateTime, zone: Zone, format: TimeFormat) -> String {
+    format!(
+        "It's {}, {} ({}).",
+        now.format("%A, %B %-d, %Y"),
+        format_clock(now.time(), format),
+        zone.name()
+    )
+}
+
+/// `tue!today`
+fn today(ctx: &Context, msg: &Message) -> String {
+    let zone = zone_source_for(&ctx.data.read(), msg.guild_id, Some(msg.author.id)).0;
+    today_response(zone.now(), zone, clock_format(ctx, msg))
+}
+
+/// `tue!when`, or `tue!when at 6pm`, saying when the next Tuesday starts on
+/// the clock
+fn when(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let zone = zone_source_for(&ctx.data.read(), msg.guild_id, Some(msg.author.id)).0;
+    let guild = guild_config(ctx, msg);
+    let at = {
+        let data = ctx.data.read();
+        let regex = data
+            .get::<RegexKey>()
+            .expect("Expected to find cached regexes in context");
+        target_time(&args.join(" ").to_lowercase(), &regex.time_of_day, &guild)
+    };
+    let now = zone.now();
+    let start = next_weekday_at(now, Weekday::Tue, at);
+    let clock = format_clock(start.time(), clock_format(ctx, msg));
+    if start <= now {
+        return format!(
+            "It's {} already! It started today at {} ({}).",
+            guild.occasion_name(),
+            clock,
+            zone.name()
+        );
+    }
+    format!(
+        "The next {} starts {} at {} ({}).",
+        guild.occasion_name(),
+        start.format("%A, %B %-d"),
+        clock,
+        zone.name()
+    )
+}
+
+/// The clock this guild reads times in, by its setting or else its locale
+fn clock_format(ctx: &Context, msg: &Message) -> TimeFormat {
+    if let Some(format) = guild_config(ctx, msg).time_format {
+        return format;
+    }
+    match msg.guild(&ctx.cache) {
+        Some(guild) => TimeFormat::for_locale(&guild.read().preferred_locale),
+        None => TimeFormat::TwentyFourHour,
+    }
+}
+
+/// `tue!timeformat 12h|24h|auto`
+fn time_format(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let (format, reply) = match args.first().map(|s| s.to_ascii_lowercase()).as_deref() {
+        Some("12h") => (
+            Some(TimeFormat::TwelveHour),
+            "Times will use a 12 hour clock.",
+        ),
+        Some("24h") => (
+            Some(TimeFormat::TwentyFourHour),
+            "Times will use a 24 hour clock.",
+        ),
+        Some("auto") => (None, "Times will follow the server's language."),
+        _ => return "Usage: tue!timeformat 12h|24h|auto".to_string(),
+    };
+    update_guild(ctx, msg, reply.to_string(), |guild| {
+        guild.time_format = format
+    })
+}
+
+/// Checks that `name` can be used as an alias: a short, single word that
+/// isn't already a built-in command.
+fn validate_alias_name(name: &str) -> Result<(), String> {
+    if find_command(name).is_some() {
+        return Err(format!("tue!{} is already a built-in command.", name));
+    }
+    if name.is_empty()
+        || name.chars().count() > MAX_ALIAS_LEN
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "Alias names have to be up to {} letters, numbers, dashes, or underscores.",
+            MAX_ALIAS_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// Whether `args` are just "list", for the commands whose lists get paged
+fn is_list(args: &[&str]) -> bool {
+    matches!(args, [arg] if arg.eq_ignore_ascii_case("list"))
+}
+
+/// `tue!alias list`, paged like any other long list
+fn list_aliases(ctx: &Context, msg: &Message) -> Option<String> {
+    let guild = guild_config(ctx, msg);
+    if guild.aliases.is_empty() {
+        return Some("No aliases yet.".to_string());
+    }
+    let mut aliases: Vec<_> = guild.aliases.iter().collect();
+    aliases.sort();
+    let lines = aliases
+        .into_iter()
+        .map(|(name, query)| format!("tue!{} → {}", name, plain_text(query)))
+        .collect();
+    send_list(ctx, msg.channel_id, "Aliases", lines);
+    None
+}
+
+/// `tue!alias add <name> <query>` or `tue!alias remove <name>`. `tue!alias
+/// list` is `list_aliases`.
+fn alias(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    const USAGE: &str =
+        "Usage: tue!alias add <name> <query>, tue!alias remove <name>, or tue!alias list";
+    let action = args.first().map(|s| s.to_ascii_lowercase());
+    let name = args.get(1).map(|s| s.to_ascii_lowercase());
+    match (action.as_deref(), name) {
+        (Some("add"), Some(name)) => {
+            let query = args[2..].join(" ");
+            if query.is_empty() || query.chars().count() > MAX_ALIAS_QUERY_LEN {
+                return format!(
+                    "Aliases have to stand for 1 to {} characters.",
+                    MAX_ALIAS_QUERY_LEN
+                );
+            }
+            if let Err(why) = validate_alias_name(&name) {
+                return why;
+            }
+            let reply = format!("tue!{} now means \"{}\".", name, plain_text(&query));
+            update_guild(ctx, msg, reply, |guild| {
+                guild.aliases.insert(name, query);
+            })
+        }
+        (Some("remove"), Some(name)) => {
+            if !guild_config(ctx, msg).aliases.contains_key(&name) {
+                return format!("There's no alias called tue!{}.", name);
+            }
+            let reply = format!("Removed tue!{}.", name);
+            update_guild(ctx, msg, reply, |guild| {
+                guild.aliases.remove(&name);
+            })
+        }
+        _ => USAGE.to_string(),
+    }
+}
+
+/// `tue!spoilers ignore|skip|hide`
+fn spoilers(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let (policy, reply) = match args.first().map(|s| s.to_ascii_lowercase()).as_deref() {
+        Some("ignore") => (SpoilerPolicy::Ignore, "Spoilers count like any other text."),
+        Some("skip") => (
+            SpoilerPolicy::Skip,
+            "I won't answer to anything inside spoilers.",
+        ),
+        Some("hide") => (SpoilerPolicy::Hide, "I'll answer spoilers with spoilers."),
+        _ => return "Usage: tue!spoilers ignore|skip|hide".to_string(),
+    };
+    update_guild(ctx, msg, reply.to_string(), |guild| {
+        guild.spoiler_policy = policy
+    })
+}
+
+/// `tue!maintenance on|off`, owner only
+fn maintenance(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    if !is_owner(ctx, msg) {
+        return "Only the bot's owner can do that.".to_string();
+    }
+    let on = match parse_toggle(args) {
+        Some(on) => on,
+        None => return "Usage: tue!maintenance on|off".to_string(),
+    };
+    let mut data = ctx.data.write();
+    let state = data
+        .get_mut::<StateKey>()
+        .expect("Expected to find bot state in context");
+    state.maintenance = on;
+    state.save();
+    if on {
+        "Maintenance mode on. I'll stay quiet until tue!maintenance off.".to_string()
+    } else {
+        "Maintenance mode off. Back to counting down!".to_string()
+    }
+}
+
+/// `tue!vacation <start> <end>` with dates like 2026-12-24, or
+/// `tue!vacation off`
+fn vacation(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    const USAGE: &str = "Usage: tue!vacation 2026-12-24 2027-01-02, or tue!vacation off";
+    if args
+        .first()
+        .is_some_and(|arg| arg.eq_ignore_ascii_case("off"))
+    {
+        return update_guild(ctx, msg, "Vacation's over.".to_string(), |guild| {
+            guild.vacation = None
+        });
+    }
+    let dates: Vec<NaiveDate> = args
+        .iter()
+        .filter_map(|arg| NaiveDate::parse_from_str(arg, "%Y-%m-%d").ok())
+        .collect();
+    let (start, end) = match dates.as_slice() {
+        [start, end] if args.len() == 2 && start <= end => (*start, *end),
+        _ => return USAGE.to_string(),
+    };
+    let reply = format!(
+        "Time from {} through {} won't count toward the countdown.",
+        start.format("%b %-d, %Y"),
+        end.format("%b %-d, %Y")
+    );
+    update_guild(ctx, msg, reply, |guild| {
+        guild.vacation = Some(Vacation { start, end })
+    })
+}
+
+/// Reads grid sizes like "15m", "12h", "1d", or a bare number of minutes
+fn parse_grid(arg: &str) -> Option<u32> {
+    let arg = arg.to_ascii_lowercase();
+    let (number, scale) = match arg.chars().last()? {
+        'm' => (&arg[..arg.len() - 1], 1),
+        'h' => (&arg[..arg.len() - 1], 60),
+        'd' => (&arg[..arg.len() - 1], 1440),
+        _ => (&arg[..], 1),
+    };
+    number
+        .parse::<u32>()
+        .ok()
+        .and_then(|n| n.checked_mul(scale))
+        .filter(|&minutes| minutes > 0)
+}
+
+/// `tue!grid 15m|12h|1d|off`
+fn grid(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    match args.first() {
+        Some(arg) if arg.eq_ignore_ascii_case("off") => update_guild(
+            ctx,
+            msg,
+            "Countdowns won't be rounded.".to_string(),
+            |guild| guild.grid_minutes = None,
+        ),
+        Some(arg) => match parse_grid(arg) {
+            Some(minutes) => {
+                let reply = format!(
+                    "Countdowns will be rounded to the nearest {}.",
+                    describe_grid(minutes)
+                );
+                update_guild(ctx, msg, reply, |guild| guild.grid_minutes = Some(minutes))
+            }
+            None => "Usage: tue!grid 15m|12h|1d|off".to_string(),
+        },
+        None => "Usage: tue!grid 15m|12h|1d|off".to_string(),
+    }
+}
+
+/// `tue!dadmode on|off`
+fn dad_mode(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let on = match parse_toggle(args) {
+        Some(on) => on,
+        None => return "Usage: tue!dadmode on|off".to_string(),
+    };
+    let reply = if on {
+        "Dad mode on. Every countdown will be in the worst unit I can find."
+    } else {
+        "Dad mode off. Units are back to what you asked for."
+    };
+    update_guild(ctx, msg, reply.to_string(), |guild| guild.dad_mode = on)
+}
+
+/// `tue!style sentence|eta|chart [width]`, or `tue!style bare <style>|off` for
+/// messages that are nothing but the trigger word
+fn style(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    const USAGE: &str = "Usage: tue!style sentence|eta|chart [width], or tue!style bare \
+                         sentence|eta|chart|off for messages that are just \"tuesday\"";
+    let parse = |arg: &str| match arg.to_ascii_lowercase().as_str() {
+        "sentence" => Some(ResponseStyle::Sentence),
+        "eta" => Some(ResponseStyle::Eta),
+        "chart" => Some(ResponseStyle::Chart),
+        _ => None,
+    };
+    if let [bare, arg] = args {
+        if bare.eq_ignore_ascii_case("bare") {
+            let (style, reply) = match parse(arg) {
+                Some(style) => (
+                    Some(style),
+                    format!(
+                        "A plain \"tuesday\" gets the {} style.",
+                        arg.to_ascii_lowercase()
+                    ),
+                ),
+                None if arg.eq_ignore_ascii_case("off") => (
+                    None,
+                    "A plain \"tuesday\" gets the usual style.".to_string(),
+                ),
+                None => return USAGE.to_string(),
+            };
+            return update_guild(ctx, msg, reply, |guild| guild.trigger_only_style = style);
+        }
+    }
+    let style = match args.first().and_then(|arg| parse(arg)) {
+        Some(style) => style,
+        None => return USAGE.to_string(),
+    };
+    let width = match args.get(1).map(|s| s.parse::<usize>()) {
+        Some(Ok(width)) if (1..=MAX_BAR_WIDTH).contains(&width) => Some(width),
+        Some(_) => return format!("Chart width has to be between 1 and {}.", MAX_BAR_WIDTH),
+        None => None,
+    };
+    let reply = format!(
+        "Countdowns will now use the {} style.",
+        args[0].to_ascii_lowercase()
+    );
+    update_guild(ctx, msg, reply, |guild| {
+        guild.style = style;
+        if let Some(width) = width {
+            guild.chart_width = width;
+        }
+    })
+}
+
+/// `tue!occasiontime 5pm|17:00|reset`
+fn occasion_time(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let raw = args.join(" ");
+    if raw.eq_ignore_ascii_case("reset") {
+        return update_guild(
+            ctx,
+            msg,
+            "Back to counting down to midnight.".to_string(),
+            |guild| guild.occasion_time = None,
+        );
+    }
+    let time = ctx
+        .data
+        .read()
+        .get::<RegexKey>()
+        .expect("Expected to find cached regexes in context")
+        .time_of_day
+        .parse_clock(&raw);
+    match time {
+        Some(time) => {
+            let reply = format!(
+                "Counting down to {} on {} from now on.",
+                format_clock(time, clock_format(ctx, msg)),
+                guild_config(ctx, msg).occasion_name()
+            );
+            update_guild(ctx, msg, reply, |guild| guild.occasion_time = Some(time))
+        }
+        None => "Usage: tue!occasiontime 5pm, or tue!occasiontime reset".to_string(),
+    }
+}
+
+/// `tue!mytimezone UTC+2|reset`, a timezone for just the author, wherever
+/// they ask
+fn my_timezone(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let raw = args.join(" ");
+    let minutes = if raw.eq_ignore_ascii_case("reset") {
+        None
+    } else {
+        match parse_utc_offset(&raw) {
+            Some(offset) => Some(offset.local_minus_utc() / 60),
+            None => return "Usage: tue!mytimezone UTC+2, or tue!mytimezone reset".to_string(),
+        }
+    };
+    let mut data = ctx.data.write();
+    let state = data
+        .get_mut::<StateKey>()
+        .expect("Expected to find bot state in context");
+    state.user_mut(msg.author.id.0).utc_offset_minutes = minutes;
+    state.save();
+    match minutes.and_then(Zone::from_minutes) {
+        Some(zone) => format!("Your countdowns will be in {} from now on.", zone.name()),
+        None => "Your countdowns will use the server's timezone again.".to_string(),
+    }
+}
+
+/// `tue!whoami`, explaining what the author's answers are based on
+fn whoami(ctx: &Context, msg: &Message) -> String {
+    let premium_tier = msg
+        .guild(&ctx.cache)
+        .map(|guild| guild.read().premium_tier.num());
+    let data = ctx.data.read();
+    let (zone, source) = zone_source_for(&data, msg.guild_id, Some(msg.author.id));
+    let config = data
+        .get::<ConfigKey>()
+        .expect("Expected to find config in context");
+    let user = data
+        .get::<StateKey>()
+        .expect("Expected to find bot state in context")
+        .user(msg.author.id.0);
+
+    let now = Instant::now();
+    let cooldowns = data
+        .get::<CooldownKey>()
+        .expect("Expected to find cooldowns in context");
+    let mut waiting: Vec<String> = config
+        .command_cooldowns
+        .iter()
+        .filter_map(|(command, &secs)| {
+            let key = (command.clone(), msg.author.id.0);
+            let remaining = cooldowns.remaining(&key, Duration::from_secs(secs), now)?;
+            Some(format!("tue!{} in {}s", command, remaining.as_secs() + 1))
+        })
+        .collect();
+    waiting.sort();
+    let trigger = data
+        .get::<TriggerCooldownKey>()
+        .expect("Expected to find cooldowns in context")
+        .remaining(
+            &msg.channel_id.0,
+            config.trigger_cooldown(premium_tier),
+            now,
+        );
+    if let Some(remaining) = trigger {
+        waiting.push(format!("\"tues\" here in {}s", remaining.as_secs() + 1));
+    }
+
+    let mut builder = MessageBuilder::new();
+    builder
+        .push("Here's what I know about ")
+        .push_safe(&msg.author.name)
+        .push(":\n")
+        .push(format!(
+            "Timezone: {}, from {}\n",
+            zone.name(),
+            source.describe()
+        ))
+        .push(format!("Countdowns you've set off: {}\n", user.triggers))
+        .push("Cooldowns: ");
+    if waiting.is_empty() {
+        builder.push("none, ask away");
+    } else {
+        builder.push(format!("you can use {} again", waiting.join(", ")));
+    }
+    builder.build()
+}
+
+/// `tue!tuesdayrole @role|off`
+fn tuesday_role(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    const USAGE: &str = "Usage: tue!tuesdayrole @role, or tue!tuesdayrole off";
+    let role = match args.first() {
+        Some(arg) if arg.eq_ignore_ascii_case("off") => None,
+        Some(arg) => match arg.trim_start_matches("<@&").trim_end_matches('>').parse() {
+            Ok(role) => Some(role),
+            Err(_) => return USAGE.to_string(),
+        },
+        None => return USAGE.to_string(),
+    };
+    let reply = match role {
+        Some(role) => format!(
+            "Members who tue!optin will get <@&{}> every Tuesday. I need Manage Roles, \
+             and my role has to be above it.",
+            role
+        ),
+        None => "No more Tuesday role.".to_string(),
+    };
+    update_guild(ctx, msg, reply, |guild| guild.tuesday_role = role)
+}
+
+/// `tue!optin` and `tue!optout`, for the Tuesday role. Anyone can run these
+/// for themselves.
+fn opt_in(ctx: &Context, msg: &Message, join: bool) -> String {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return "That only works in a server.".to_string(),
+    };
+    let mut data = ctx.data.write();
+    let state = data
+        .get_mut::<StateKey>()
+        .expect("Expected to find bot state in context");
+    let guild = state.guild_mut(guild_id.0);
+    if guild.tuesday_role.is_none() {
+        return "This server doesn't have a Tuesday role.".to_string();
+    }
+    let user = msg.author.id.0;
+    guild.role_optins.retain(|&optin| optin != user);
+    if join {
+        guild.role_optins.push(user);
+    }
+    state.save();
+    if join {
+        "You'll get the Tuesday role every Tuesday.".to_string()
+    } else {
+        "You won't get the Tuesday role anymore.".to_string()
+    }
+}
+
+/// `tue!multiday earliest|all`, for messages naming several weekdays
+fn multi_day(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let (policy, reply) = match args.first().map(|s| s.to_ascii_lowercase()).as_deref() {
+        Some("earliest") => (
+            MultiDayPolicy::Earliest,
+            "When a message names several days, I'll count down to the first one.",
+        ),
+        Some("all") => (
+            MultiDayPolicy::All,
+            "When a message names several days, I'll count down to each of them.",
+        ),
+        _ => return "Usage: tue!multiday earliest|all".to_string(),
+    };
+    update_guild(ctx, msg, reply.to_string(), |guild| {
+        guild.multi_day_policy = policy
+    })
+}
+
+/// `tue!granularity minute|hour|off`
+fn granularity(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let (granularity, reply) = match args.first().map(|s| s.to_ascii_lowercase()).as_deref() {
+        Some("minute") => (
+            Some(Granularity::Minute),
+            "Times will be rounded to the minute, so answers only change once a minute.",
+        ),
+        Some("hour") => (
+            Some(Granularity::Hour),
+            "Times will be rounded to the hour, so answers only change once an hour.",
+        ),
+        Some("off") => (None, "Times won't be rounded."),
+        _ => return "Usage: tue!granularity minute|hour|off".to_string(),
+    };
+    update_guild(ctx, msg, reply.to_string(), |guild| {
+        guild.granularity = granularity
+    })
+}
+
+/// `tue!mentions ping|silent|off`, for the Tuesday role, or `tue!mentions
+/// quiet <percent>|off` to only ping while enough of the role is online
+fn mentions(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    const USAGE: &str = "Usage: tue!mentions ping|silent|off, or tue!mentions quiet 50|off";
+    if let [action, arg] = args {
+        if !action.eq_ignore_ascii_case("quiet") {
+            return USAGE.to_string();
+        }
+        if arg.eq_ignore_ascii_case("off") {
+            let reply = "Pings go out no matter who's online.".to_string();
+            return update_guild(ctx, msg, reply, |guild| guild.quiet_ping_threshold = None);
+        }
+        return match arg.trim_end_matches('%').parse::<f64>() {
+            Ok(percent) if (0.0..=100.0).contains(&percent) => {
+                let reply = format!(
+                    "Pings turn silent while fewer than {}% of the Tuesday role is online.",
+                    percent
+                );
+                update_guild(ctx, msg, reply, |guild| {
+                    guild.quiet_ping_threshold = Some(percent / 100.0)
+                })
+            }
+            _ => USAGE.to_string(),
+        };
+    }
+    let (mode, reply) = match args.first().map(|s| s.to_ascii_lowercase()).as_deref() {
+        Some("ping") => (MentionMode::Ping, "Countdowns will ping the Tuesday role."),
+        Some("silent") => (
+            MentionMode::Silent,
+            "Countdowns will mention the Tuesday role without pinging it.",
+        ),
+        Some("off") => (
+            MentionMode::Off,
+            "Countdowns won't mention the Tuesday role.",
+        ),
+        _ => return USAGE.to_string(),
+    };
+    update_guild(ctx, msg, reply.to_string(), |guild| {
+        guild.mention_mode = mode
+    })
+}
+
+/// `tue!replies plain|reply|ping`
+fn replies(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let (mode, reply) = match args.first().map(|s| s.to_ascii_lowercase()).as_deref() {
+        Some("plain") => (ReplyMode::Plain, "Countdowns will be plain messages."),
+        Some("reply") => (
+            ReplyMode::Reply,
+            "Countdowns will reply to whoever asked, without pinging them.",
+        ),
+        Some("ping") => (
+            ReplyMode::Ping,
+            "Countdowns will reply to whoever asked and ping them.",
+        ),
+        _ => return "Usage: tue!replies plain|reply|ping".to_string(),
+    };
+    update_guild(ctx, msg, reply.to_string(), |guild| guild.reply_mode = mode)
+}
+
+/// `tue!allstatus`, the owner's overview of every guild, in DMs only. The
+/// report can be long, so it's sent here in pieces instead of as one reply.
+fn all_status(ctx: &Context, msg: &Message) -> Option<String> {
+    if !is_owner(ctx, msg) {
+        return Some("Only the bot's owner can do that.".to_string());
+    }
+    if msg.guild_id.is_some() {
+        return Some("Ask me that in DMs.".to_string());
+    }
+
+    let guilds: Vec<(GuildId, String)> = ctx
+        .cache
+        .read()
+        .guilds
+        .iter()
+        .map(|(id, guild)| (*id, guild.read().name.clone()))
+        .collect();
+    let statuses = {
+        let data = ctx.data.read();
+        let state = data
+            .get::<StateKey>()
+            .expect("Expected to find bot state in context");
+        guilds
+            .into_iter()
+            .map(|(id, name)| GuildStatus {
+                id: id.0,
+                name,
+                role: state.guild(Some(id.0)).tuesday_role,
+                zone: zone_source_for(&data, Some(id), None).0,
+            })
+            .collect()
+    };
+
+    let lines = status_lines(statuses);
+    if lines.is_empty() {
+        return Some("I'm not in any servers.".to_string());
+    }
+    send_list(ctx, msg.channel_id, "Server status", lines);
+    None
+}
+
+/// Whether `s` looks like a single emoji: short, and nothing like text,
+/// mentions, or formatting in it
+fn is_single_emoji(s: &str) -> bool {
+    let count = s.chars().count();
+    count > 0
+        && count <= MAX_EMOJI_LEN
+        && s.chars()
+            .all(|c| !c.is_ascii() && !c.is_control() && !c.is_whitespace())
+}
+
+/// `tue!proximity on|off`, `tue!proximity hours <close> <imminent>`, or
+/// `tue!proximity emoji <far> <close> <imminent> <today>`
+fn proximity(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    const USAGE: &str = "Usage: tue!proximity on|off, tue!proximity hours 48 6, or \
+                         tue!proximity emoji 😴 🙂 🤩 🎉";
+    let current = guild_config(ctx, msg).proximity.unwrap_or_default();
+    let subcommand = args.first().map(|s| s.to_ascii_lowercase());
+    let rest = args.get(1..).unwrap_or(&[]);
+    match (subcommand.as_deref(), rest) {
+        (Some("on"), []) => update_guild(
+            ctx,
+            msg,
+            "Countdowns will show how close Tuesday is.".to_string(),
+            |guild| guild.proximity = Some(current),
+        ),
+        (Some("off"), []) => {
+            update_guild(ctx, msg, "No more proximity emoji.".to_string(), |guild| {
+                guild.proximity = None
+            })
+        }
+        (Some("hours"), [close, imminent]) => {
+            match (close.parse::<f64>(), imminent.parse::<f64>()) {
+                (Ok(close), Ok(imminent)) if imminent > 0.0 && close > imminent => {
+                    let reply = format!(
+                        "Tuesday is close under {} hours away, and imminent under {}.",
+                        close, imminent
+                    );
+                    update_guild(ctx, msg, reply, |guild| {
+                        guild.proximity = Some(Proximity {
+                            close_hours: close,
+                            imminent_hours: imminent,
+                            ..current
+                        })
+                    })
+                }
+                _ => "The close threshold has to be above the imminent one, and both above 0."
+                    .to_string(),
+            }
+        }
+        (Some("emoji"), [far, close, imminent, today]) => {
+            if ![far, close, imminent, today]
+                .iter()
+                .all(|emoji| is_single_emoji(emoji))
+            {
+                return "Each of those has to be a single emoji.".to_string();
+            }
+            let reply = format!(
+                "Proximity emoji set: {} {} {} {}",
+                far, close, imminent, today
+            );
+            let emoji = Proximity {
+                far: far.to_string(),
+                close: close.to_string(),
+                imminent: imminent.to_string(),
+                today: today.to_string(),
+                ..current
+            };
+            update_guild(ctx, msg, reply, |guild| guild.proximity = Some(emoji))
+        }
+        _ => USAGE.to_string(),
+    }
+}
+
+/// `tue!until <offset>`, like `tue!until 3 weeks and 2 days from now`,
+/// saying what day that lands on
+fn until(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let offset = match parse_offset(&args.join(" ")) {
+        Some(offset) => offset,
+        None => {
+            return "Usage: tue!until 3 weeks and 2 days from now, or tue!until two hours"
+                .to_string()
+        }
+    };
+    let zone = zone_source_for(&ctx.data.read(), msg.guild_id, Some(msg.author.id)).0;
+    let target = match zone.now().checked_add_signed(offset) {
+        Some(target) => target,
+        None => return "That's further out than I can count.".to_string(),
+    };
+    let mut reply = format!(
+        "That's {} at {} ({}).",
+        target.format("%A, %B %-d, %Y"),
+        format_clock(target.time(), clock_format(ctx, msg)),
+        zone.name()
+    );
+    if target.weekday() == Weekday::Tue {
+        reply.push_str(&format!(
+            " It's a {}!",
+            guild_config(ctx, msg).occasion_name()
+        ));
+    }
+    reply
+}
+
+/// `tue!timeshift +3d` or `tue!timeshift reset`, owner only. Moves the
+/// bot's clock to try out time-dependent features, until reset or restart.
+fn timeshift(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    if !is_owner(ctx, msg) {
+        return "Only the bot's owner can do that.".to_string();
+    }
+    let shift = match args {
+        [arg] if arg.eq_ignore_ascii_case("reset") => chrono::Duration::zero(),
+        [arg] => match clock::parse_shift(arg) {
+            Some(shift) => clock::offset() + shift,
+            None => {
+                return "Usage: tue!timeshift +3d, tue!timeshift -6h, or tue!timeshift reset"
+                    .to_string()
+            }
+        },
+        _ => {
+            return format!(
+            "The clock is shifted by {} seconds. Usage: tue!timeshift +3d, or tue!timeshift reset",
+            clock::offset().num_seconds()
+        )
+        }
+    };
+    clock::set_offset(shift);
+    println!("Clock shifted by {} seconds", shift.num_seconds());
+    if shift.is_zero() {
+        "Back to the real time.".to_string()
+    } else {
+        format!(
+            "Time travel! It's now {} as far as I'm concerned.",
+            clock::now().format("%A, %B %-d %H:%M UTC")
+        )
+    }
+}
+
+/// `tue!unitnames <seconds> <minutes> <hours> <days> <weeks> <years>`, like
+/// `tue!unitnames secs mins hrs days wks yrs`, or `tue!unitnames reset`
+fn unit_names(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    if let [arg] = args {
+        if arg.eq_ignore_ascii_case("reset") {
+            return update_guild(
+                ctx,
+                msg,
+                "Back to the usual unit names.".to_string(),
+                |guild| guild.unit_names.clear(),
+            );
+        }
+    }
+    if args.len() != TIME_UNITS.len() {
+        return format!(
+            "Usage: tue!unitnames {}, with one name for each, or tue!unitnames reset",
+            time_unit_names().join(" ")
+        );
+    }
+    if !args.iter().all(|name| is_valid_unit_name(name)) {
+        return format!(
+            "Unit names have to be up to {} letters, numbers, spaces, or dashes.",
+            MAX_UNIT_NAME_LEN
+        );
+    }
+    let names: Vec<String> = args.iter().map(|name| name.to_string()).collect();
+    let reply = format!("Countdowns will use {}.", names.join(", "));
+    update_guild(ctx, msg, reply, |guild| guild.unit_names = names)
+}
+
+/// Whether `name` can stand in for a unit in every countdown: short, and
+/// nothing that could mention, link, or format anything
+fn is_valid_unit_name(name: &str) -> bool {
+    !name.trim().is_empty()
+        && name.chars().count() <= MAX_UNIT_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == ' ' || c == '-')
+}
+
+/// `tue!exact`, the time until Tuesday to the millisecond
+fn exact(ctx: &Context, msg: &Message) -> String {
+    let zone = zone_source_for(&ctx.data.read(), msg.guild_id, Some(msg.author.id)).0;
+    let guild = guild_config(ctx, msg);
+    let now = zone.now();
+    let at = guild
+        .occasion_time
+        .unwrap_or_else(|| NaiveTime::from_hms(0, 0, 0));
+    let target = next_weekday_at(now, Weekday::Tue, at);
+    exact_response(now, target, guild.occasion_name())
+}
+
+/// `tue!help`, listing every command
+fn help() -> String {
+    let commands: Vec<String> = COMMANDS
+        .iter()
+        .map(|(name, _)| format!("tue!{}", name))
+        .collect();
+    format!(
+        "Mention Tuesday in a message and I'll tell you how long is left, in \
+         whatever unit you ask for. Commands: {}",
+        commands.join(", ")
+    )
+}
+
+/// An example date in `format`, or why it can't be used. chrono panics while
+/// formatting if the format has a bad specifier, or one a date can't fill in
+/// like %H or %Z, so this has to be checked before it's saved.
+fn date_format_example(format: &str) -> Result<String, String> {
+    if format.chars().count() > MAX_DATE_FORMAT_LEN {
+        return Err(format!(
+            "Date formats can be at most {} characters.",
+            MAX_DATE_FORMAT_LEN
+        ));
+    }
+    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+        return Err(format!("I can't read the date format \"{}\".", format));
+    }
+    let mut example = String::new();
+    if write!(
+        example,
+        "{}",
+        NaiveDate::from_ymd(2026, 12, 1).format(format)
+    )
+    .is_err()
+    {
+        return Err(format!(
+            "The date format \"{}\" can only have parts of the date, not times or \
+             timezones.",
+            format
+        ));
+    }
+    Ok(example)
+}
+
+/// `tue!showdate on [format]` or `tue!showdate off`, where the format is
+/// strftime style like `%b %-d`
+fn show_date(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    const USAGE: &str = "Usage: tue!showdate on, tue!showdate on %A %B %-d, or tue!showdate off";
+    let action = args.first().map(|s| s.to_ascii_lowercase());
+    match action.as_deref() {
+        Some("off") if args.len() == 1 => update_guild(
+            ctx,
+            msg,
+            "Countdowns won't show the date.".to_string(),
+            |guild| guild.date_format = None,
+        ),
+        Some("on") => {
+            // No pinging anyone from inside a date
+            let format: String = match &args[1..] {
+                [] => "%b %-d".to_string(),
+                rest => rest.join(" "),
+            }
+            .replace('@', "");
+            let example = match date_format_example(&format) {
+                Ok(example) => example,
+                Err(why) => return why,
+            };
+            let reply = format!("Countdowns will show the date, like \"{}\".", example);
+            update_guild(ctx, msg, reply, |guild| guild.date_format = Some(format))
+        }
+        _ => USAGE.to_string(),
+    }
+}
+
+/// `tue!freeze list`, paged like any other long list
+fn list_frozen_dates(ctx: &Context, msg: &Message) -> Option<String> {
+    let mut dates = guild_config(ctx, msg).frozen_dates;
+    if dates.is_empty() {
+        return Some("No frozen days.".to_string());
+    }
+    dates.sort();
+    let lines = dates
+        .iter()
+        .map(|date| date.format("%b %-d, %Y").to_string())
+        .collect();
+    send_list(ctx, msg.channel_id, "Frozen days", lines);
+    None
+}
+
+/// `tue!freeze add <date>` or `tue!freeze remove <date>`, with dates like
+/// 2026-12-25. `tue!freeze list` is `list_frozen_dates`.
+fn freeze(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    const USAGE: &str =
+        "Usage: tue!freeze add 2026-12-25, tue!freeze remove 2026-12-25, or tue!freeze list";
+    let action = args.first().map(|s| s.to_ascii_lowercase());
+    let date = args
+        .get(1)
+        .and_then(|arg| NaiveDate::parse_from_str(arg, "%Y-%m-%d").ok());
+    match (action.as_deref(), date) {
+        (Some("add"), Some(date)) if args.len() == 2 => {
+            if guild_config(ctx, msg).frozen_dates.contains(&date) {
+                return format!("{} is already frozen.", date.format("%b %-d, %Y"));
+            }
+            let reply = format!(
+                "The countdown will stand still on {}.",
+                date.format("%b %-d, %Y")
+            );
+            update_guild(ctx, msg, reply, |guild| guild.frozen_dates.push(date))
+        }
+        (Some("remove"), Some(date)) if args.len() == 2 => {
+            if !guild_config(ctx, msg).frozen_dates.contains(&date) {
+                return format!("{} isn't frozen.", date.format("%b %-d, %Y"));
+            }
+            let reply = format!("{} is back to normal.", date.format("%b %-d, %Y"));
+            update_guild(ctx, msg, reply, |guild| {
+                guild.frozen_dates.retain(|&frozen| frozen != date)
+            })
+        }
+        _ => USAGE.to_string(),
+    }
+}
+
+/// `tue!percent day|tuesday|month`, or `tue!percent since <date>` with a
+/// date like 2026-09-01, for where percent questions count from. `tue!percent
+/// decimals <n>` sets how precise they are.
+fn percent(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    if let [action, decimals] = args {
+        if action.eq_ignore_ascii_case("decimals") {
+            return match decimals.parse::<u8>() {
+                Ok(decimals) if decimals <= MAX_PERCENT_DECIMALS => {
+                    let reply = format!(
+                        "Percentages will be shown like {}%.",
+                        format_percent(0.4375, decimals)
+                    );
+                    update_guild(ctx, msg, reply, |guild| guild.percent_decimals = decimals)
+                }
+                _ => format!("Pick from 0 to {} decimal places.", MAX_PERCENT_DECIMALS),
+            };
+        }
+    }
+    let anchor = match args.first().map(|s| s.to_ascii_lowercase()).as_deref() {
+        Some("day") if args.len() == 1 => None,
+        Some("tuesday") if args.len() == 1 => Some(PercentAnchor::Tuesday),
+        Some("month") if args.len() == 1 => Some(PercentAnchor::Month),
+        Some("since") if args.len() == 2 => match NaiveDate::parse_from_str(args[1], "%Y-%m-%d") {
+            Ok(date) => Some(PercentAnchor::Date(date)),
+            Err(_) => return "Dates look like 2026-09-01.".to_string(),
+        },
+        _ => {
+            return "Usage: tue!percent day, tue!percent tuesday, tue!percent month, \
+                    tue!percent since 2026-09-01, or tue!percent decimals 1"
+                .to_string()
+        }
+    };
+    let reply = match anchor {
+        Some(anchor) => format!(
+            "Percentages will count from {} to Tuesday.",
+            anchor.describe()
+        ),
+        None => "Percentages will be of the day again, near Tuesday.".to_string(),
+    };
+    update_guild(ctx, msg, reply, |guild| guild.percent_anchor = anchor)
+}
+
+/// `tue!units all`, `tue!units only <unit>...`, `tue!units prefixes on|off`,
+/// or `tue!units refuse on|off`, for which units countdowns may use
+fn units(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    const USAGE: &str = "Usage: tue!units all, tue!units only hours days, \
+                         tue!units prefixes on|off, or tue!units refuse on|off";
+    let action = args.first().map(|s| s.to_ascii_lowercase());
+    match action.as_deref() {
+        Some("all") if args.len() == 1 => update_guild(
+            ctx,
+            msg,
+            "Every unit is allowed again.".to_string(),
+            |guild| guild.allowed_units.clear(),
+        ),
+        Some("only") if args.len() > 1 => {
+            let mut names = Vec::new();
+            for arg in &args[1..] {
+                let arg = arg.to_ascii_lowercase();
+                match TIME_UNITS
+                    .iter()
+                    .find(|unit| arg == unit.plural || arg == unit.singular)
+                    .map(|unit| unit.plural)
+                {
+                    Some(plural) if !names.contains(&plural.to_string()) => {
+                        names.push(plural.to_string())
+                    }
+                    Some(_) => {}
+                    None => {
+                        return format!(
+                            "I don't know the unit \"{}\". Pick from {}.",
+                            arg,
+                            time_unit_names().join(", ")
+                        )
+                    }
+                }
+            }
+            let reply = format!("Countdowns will only use {}.", names.join(", "));
+            update_guild(ctx, msg, reply, |guild| guild.allowed_units = names)
+        }
+        Some("prefixes") => match parse_toggle(&args[1..]) {
+            Some(on) => {
+                let reply = if on {
+                    "SI prefixes are allowed."
+                } else {
+                    "No more SI prefixes."
+                };
+                update_guild(ctx, msg, reply.to_string(), |guild| {
+                    guild.si_prefixes_allowed = on
+                })
+            }
+            None => USAGE.to_string(),
+        },
+        Some("refuse") => match parse_toggle(&args[1..]) {
+            Some(on) => {
+                let reply = if on {
+                    "I'll refuse to answer in units that aren't allowed."
+                } else {
+                    "I'll switch to the nearest allowed unit instead."
+                };
+                update_guild(ctx, msg, reply.to_string(), |guild| {
+                    guild.refuse_disallowed_units = on
+                })
+            }
+            None => USAGE.to_string(),
+        },
+        _ => USAGE.to_string(),
+    }
+}
+
+/// `tue!wholeweeks on|off`
+fn whole_weeks(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let on = match parse_toggle(args) {
+        Some(on) => on,
+        None => return "Usage: tue!wholeweeks on|off".to_string(),
+    };
+    let reply = if on {
+        "When it's exactly some number of weeks until Tuesday, I'll say so."
+    } else {
+        "Whole weeks get the usual countdown."
+    };
+    update_guild(ctx, msg, reply.to_string(), |guild| guild.whole_weeks = on)
+}
+
+/// `tue!triggers`, or `tue!triggers phrase|mention|reply|reaction on|off`
+fn triggers(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    const USAGE: &str = "Usage: tue!triggers phrase|mention|reply|reaction on|off";
+    if args.is_empty() {
+        let sources = guild_config(ctx, msg).trigger_sources;
+        let toggles = [
+            ("phrase", sources.phrase),
+            ("mention", sources.mention),
+            ("reply", sources.reply),
+            ("reaction", sources.reaction),
+        ];
+        let listed: Vec<String> = toggles
+            .iter()
+            .map(|(name, on)| format!("{} {}", name, if *on { "on" } else { "off" }))
+            .collect();
+        return format!("Triggers: {}. {}", listed.join(", "), USAGE);
+    }
+    let on = match parse_toggle(&args[1..]) {
+        Some(on) => on,
+        None => return USAGE.to_string(),
+    };
+    let source = args[0].to_ascii_lowercase();
+    let set: fn(&mut GuildConfig, bool) = match source.as_str() {
+        "phrase" => |guild, on| guild.trigger_sources.phrase = on,
+        "mention" => |guild, on| guild.trigger_sources.mention = on,
+        "reply" => |guild, on| guild.trigger_sources.reply = on,
+        "reaction" => |guild, on| guild.trigger_sources.reaction = on,
+        _ => return USAGE.to_string(),
+    };
+    let reply = format!(
+        "The {} trigger is {}.",
+        source,
+        if on { "on" } else { "off" }
+    );
+    update_guild(ctx, msg, reply, |guild| set(guild, on))
+}
+
+/// `tue!explain on|off`, for saying which unit an ambiguous query was read
+/// as. Discord buttons would be nicer, but serenity 0.8 can't send them, so
+/// the note says what to ask instead.
+fn explain(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let on = match parse_toggle(args) {
+        Some(on) => on,
+        None => return "Usage: tue!explain on|off".to_string(),
+    };
+    let reply = if on {
+        "When a question names more than one unit, I'll say which one I went with."
+    } else {
+        "No more notes about which unit I picked."
+    };
+    update_guild(ctx, msg, reply.to_string(), |guild| {
+        guild.explain_units = on
+    })
+}
+
+/// How a yearly holiday is shown, like "Dec 25 every year"
+fn describe_yearly((month, day): (u32, u32)) -> String {
+    format!(
+        "{} every year",
+        NaiveDate::from_ymd(2000, month, day).format("%b %-d")
+    )
+}
+
+/// `tue!holidays list`, paged like any other long list
+fn list_holidays(ctx: &Context, msg: &Message) -> Option<String> {
+    let guild = guild_config(ctx, msg);
+    let mut dates = guild.holidays;
+    dates.sort();
+    let mut yearly = guild.yearly_holidays;
+    yearly.sort();
+    let mut lines: Vec<String> = dates
+        .iter()
+        .map(|date| date.format("%b %-d, %Y").to_string())
+        .chain(yearly.into_iter().map(describe_yearly))
+        .collect();
+    if let Some(calendar) = guild.holiday_calendar {
+        lines.push(format!("Everything in the {} calendar", calendar));
+    }
+    if lines.is_empty() {
+        return Some("No holidays.".to_string());
+    }
+    send_list(ctx, msg.channel_id, "Holidays", lines);
+    None
+}
+
+/// `tue!holidays add <date>`, `tue!holidays remove <date>`, `tue!holidays
+/// leapday clamp|roll`, or `tue!holidays calendar <name>|off`, with dates
+/// like 2026-12-25, or 12-25 for every year. `tue!holidays list` is
+/// `list_holidays`.
+fn holidays(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let calendars: Vec<&str> = HOLIDAY_CALENDARS.iter().map(|(name, _)| *name).collect();
+    let usage = format!(
+        "Usage: tue!holidays add 2026-12-25, tue!holidays add 12-25 (every year), tue!holidays remove 2026-12-25, tue!holidays list, tue!holidays leapday clamp|roll, or tue!holidays calendar {}|off",
+        calendars.join("|")
+    );
+    let action = args.first().map(|s| s.to_ascii_lowercase());
+    let date = args
+        .get(1)
+        .and_then(|arg| NaiveDate::parse_from_str(arg, "%Y-%m-%d").ok());
+    // Parsed in a leap year, so Feb 29 is allowed
+    let yearly = args
+        .get(1)
+        .and_then(|arg| NaiveDate::parse_from_str(&format!("2000-{}", arg), "%Y-%m-%d").ok())
+        .map(|date| (date.month(), date.day()));
+    match (action.as_deref(), date, yearly) {
+        (Some("add"), Some(date), _) if args.len() == 2 => {
+            if guild_config(ctx, msg).holidays.contains(&date) {
+                return format!("{} is already a holiday.", date.format("%b %-d, %Y"));
+            }
+            let reply = format!("{} is a holiday.", date.format("%b %-d, %Y"));
+            update_guild(ctx, msg, reply, |guild| guild.holidays.push(date))
+        }
+        (Some("remove"), Some(date), _) if args.len() == 2 => {
+            if !guild_config(ctx, msg).holidays.contains(&date) {
+                return format!("{} isn't a holiday.", date.format("%b %-d, %Y"));
+            }
+            let reply = format!("{} isn't a holiday anymore.", date.format("%b %-d, %Y"));
+            update_guild(ctx, msg, reply, |guild| {
+                guild.holidays.retain(|&holiday| holiday != date)
+            })
+        }
+        (Some("add"), None, Some(yearly)) if args.len() == 2 => {
+            if guild_config(ctx, msg).yearly_holidays.contains(&yearly) {
+                return format!("{} is already a holiday.", describe_yearly(yearly));
+            }
+            let reply = format!("{} is a holiday.", describe_yearly(yearly));
+            update_guild(ctx, msg, reply, |guild| guild.yearly_holidays.push(yearly))
+        }
+        (Some("remove"), None, Some(yearly)) if args.len() == 2 => {
+            if !guild_config(ctx, msg).yearly_holidays.contains(&yearly) {
+                return format!("{} isn't a holiday.", describe_yearly(yearly));
+            }
+            let reply = format!("{} isn't a holiday anymore.", describe_yearly(yearly));
+            update_guild(ctx, msg, reply, |guild| {
+                guild.yearly_holidays.retain(|&holiday| holiday != yearly)
+            })
+        }
+        (Some("leapday"), None, None) if args.len() == 2 => {
+            let (policy, reply) = match args[1].to_ascii_lowercase().as_str() {
+                "clamp" => (
+                    LeapDayPolicy::Clamp,
+                    "Outside leap years, Feb 29 holidays are on Feb 28.",
+                ),
+                "roll" => (
+                    LeapDayPolicy::Roll,
+                    "Outside leap years, Feb 29 holidays are on Mar 1.",
+                ),
+                _ => return usage,
+            };
+            update_guild(ctx, msg, reply.to_string(), |guild| guild.leap_day = policy)
+        }
+        (Some("calendar"), None, None) if args.len() == 2 => {
+            let name = args[1].to_ascii_lowercase();
+            if name == "off" {
+                let reply = "Only the holidays added by hand count now.".to_string();
+                return update_guild(ctx, msg, reply, |guild| guild.holiday_calendar = None);
+            }
+            if !calendars.contains(&name.as_str()) {
+                return usage;
+            }
+            let reply = format!("Holidays in the {} calendar count now.", name);
+            update_guild(ctx, msg, reply, |guild| guild.holiday_calendar = Some(name))
+        }
+        _ => usage,
+    }
+}
+
+/// `tue!impatience on|off`, for how "is it tuesday yet" gets answered
+fn impatience(ctx: &Context, msg: &Message, args: &[&str]) -> String {
+    let on = match parse_toggle(args) {
+        Some(on) => on,
+        None => return "Usage: tue!impatience on|off".to_string(),
+    };
+    let reply = if on {
+        "Ask me if it's Tuesday yet. Go on."
+    } else {
+        "\"Is it tuesday yet\" gets a normal countdown again."
+    };
+    update_guild(ctx, msg, reply.to_string(), |guild| guild.impatience = on)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_pings_and_formatting_from_occasions() {
+        assert_eq!(
+            sanitize_occasion("  **Taco**   Tuesday  "),
+            Ok("Taco Tuesday".to_string())
+        );
+        assert_eq!(
+            sanitize_occasion("@everyone <@&123> party"),
+            Ok("everyone &123 party".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_empty_and_long_occasions() {
+        assert!(sanitize_occasion("***").is_err());
+        assert!(sanitize_occasion(&"a".repeat(MAX_OCCASION_LEN + 1)).is_err());
+        assert!(sanitize_occasion(&"a".repeat(MAX_OCCASION_LEN)).is_ok());
+    }
+
+    #[test]
+    fn accepts_single_emoji_only() {
+        assert!(is_single_emoji("🎉"));
+        assert!(is_single_emoji("👨‍👩‍👧"));
+        assert!(!is_single_emoji(""));
+        assert!(!is_single_emoji("yes"));
+        assert!(!is_single_emoji("🎉 🎉"));
+        assert!(!is_single_emoji("<:tue:42>"));
+        assert!(!is_single_emoji(&"🎉".repeat(MAX_EMOJI_LEN + 1)));
+    }
+
+    #[test]
+    fn help_lists_every_command() {
+        let help = help();
+        for (name, _) in COMMANDS {
+            assert!(help.contains(&format!("tue!{}", name)), "missing {}", name);
+        }
+        assert!(help.len() < 2000);
+    }
+
+    #[test]
+    fn pages_only_a_bare_list() {
+        assert!(is_list(&["list"]));
+        assert!(is_list(&["LIST"]));
+        assert!(!is_list(&[]));
+        assert!(!is_list(&["list", "extra"]));
+        assert!(!is_list(&["add", "list"]));
+    }
+
+    #[test]
+    fn finds_every_command_by_name() {
+        for (i, (name, _)) in COMMANDS.iter().enumerate() {
+            assert!(find_command(name).is_some());
+            assert!(
+                COMMANDS[i + 1..].iter().all(|(other, _)| other != name),
+                "{} is listed twice",
+                name
+            );
+        }
+        assert!(find_command("nope").is_none());
+    }
+
+    #[test]
+    fn keeps_unit_names_to_plain_words() {
+        assert!(is_valid_unit_name("hrs"));
+        assert!(is_valid_unit_name("half-days"));
+        assert!(is_valid_unit_name("Stunden"));
+        assert!(!is_valid_unit_name(""));
+        assert!(!is_valid_unit_name(&"a".repeat(MAX_UNIT_NAME_LEN + 1)));
+        for name in [
+            "@everyone",
+            "**hrs**",
+            "`hrs`",
+            "<#42>",
+            "<:tue:42>",
+            "hrs\nhi",
+        ] {
+            assert!(!is_valid_unit_name(name), "{:?} was allowed", name);
+        }
+    }
+
+    #[test]
+    fn refuses_overly_long_date_formats() {
+        let longest = "%A".repeat(MAX_DATE_FORMAT_LEN / 2);
+        assert!(date_format_example(&longest).is_ok());
+        assert_eq!(
+            date_format_example(&format!("{} ", longest)),
+            Err("Date formats can be at most 100 characters.".to_string())
+        );
+    }
+
+    #[test]
+    fn shows_an_example_of_the_date_format() {
+        assert_eq!(date_format_example("%b %-d"), Ok("Dec 1".to_string()));
+        assert_eq!(
+            date_format_example("%A, %B %-d"),
+            Ok("Tuesday, December 1".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_date_formats_that_would_panic() {
+        assert!(date_format_example("%Q").is_err());
+        assert!(date_format_example("%H:%M").is_err());
+        assert!(date_format_example("%Z").is_err());
+    }
+
+    #[test]
+    fn reads_on_and_off() {
+        assert_eq!(parse_toggle(&["ON"]), Some(true));
+        assert_eq!(parse_toggle(&["off", "please"]), Some(false));
+        assert_eq!(parse_toggle(&["maybe"]), None);
+        assert_eq!(parse_toggle(&[]), None);
+    }
+
+    #[test]
+    fn accepts_short_single_word_aliases() {
+        assert!(validate_alias_name("work").is_ok());
+        assert!(validate_alias_name("week-in_ms").is_ok());
+        assert!(validate_alias_name(&"a".repeat(MAX_ALIAS_LEN)).is_ok());
+    }
+
+    #[test]
+    fn rejects_aliases_over_commands_or_with_odd_names() {
+        assert!(validate_alias_name("help").is_err());
+        assert!(validate_alias_name("").is_err());
+        assert!(validate_alias_name("two words").is_err());
+        assert!(validate_alias_name("<@123>").is_err());
+        assert!(validate_alias_name(&"a".repeat(MAX_ALIAS_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn strips_pings_from_echoed_text() {
+        assert_eq!(plain_text("`tue!help`  @here\n"), "tue!help here");
+        assert_eq!(plain_text("  how   many weeks "), "how many weeks");
+    }
+
+    #[test]
+    fn says_what_day_it_is_in_the_zone() {
+        let now = NaiveDate::from_ymd(2024, 1, 3).and_hms(15, 4, 0);
+        let zone = Zone::from_minutes(120).unwrap();
+        assert_eq!(
+            today_response(now, zone, TimeFormat::TwentyFourHour),
+            "It's Wednesday, January 3, 2024, 15:04 (UTC+02:00)."
+        );
+        assert_eq!(
+            today_response(now, Zone::Host, TimeFormat::TwelveHour),
+            "It's Wednesday, January 3, 2024, 3:04 PM (server time)."
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use std::{collections::HashMap, fs::File, io::prelude::*, time::Duration};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Deserialize;
+
+const CONFIG_FILE: &str = "config.json";
+
+/// A voice (or stage) channel that gets a countdown posted when a call starts
+/// in it
+#[derive(Debug, Deserialize)]
+pub struct VoiceCountdown {
+    pub voice_channel: u64,
+    /// Text channel the countdown is posted in
+    pub text_channel: u64,
+}
+
+/// Operator settings, read once from config.json at startup. Every field is
+/// optional; a missing file means the defaults.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// User allowed to run owner-only commands. Defaults to the owner of the
+    /// bot's application.
+    pub owner_id: Option<u64>,
+    /// When someone asked how long until Tuesday, reply to them again once
+    /// it arrives
+    pub followup_on_arrival: bool,
+    /// Made-up units people can ask for, in seconds per unit, like
+    /// `"coffee break": 900`
+    pub custom_units: HashMap<String, f64>,
+    /// Give opted-in members their guild's Tuesday role for the day
+    pub tuesday_roles: bool,
+    /// Reacting to any message with this emoji asks for the countdown
+    pub reaction_trigger: Option<String>,
+    /// During maintenance, tell admins who trigger the bot why it's quiet
+    pub maintenance_notice: bool,
+    /// Default timezone for guilds that haven't set one, as minutes east of
+    /// UTC. Uses the host's timezone if unset.
+    pub utc_offset_minutes: Option<i32>,
+    /// Seconds each user has to wait between uses of a command, keyed by
+    /// the command name without the `tue!` prefix
+    pub command_cooldowns: HashMap<String, u64>,
+    /// Seconds a channel has to wait between answers to the implicit "tues"
+    /// trigger. Zero means no cooldown.
+    pub trigger_cooldown_secs: u64,
+    /// Scales `trigger_cooldown_secs` by a guild's boost level, indexed by
+    /// premium tier (0 through 3)
+    pub premium_cooldown_multipliers: Vec<f64>,
+    pub voice_countdowns: Vec<VoiceCountdown>,
+    /// Minimum seconds between two voice countdowns for the same channel
+    pub voice_debounce_secs: u64,
+    /// Delete countdown replies once the Tuesday they counted down to is
+    /// over
+    pub cleanup_stale_replies: bool,
+    /// Seconds after the start of its Tuesday that a reply counts as stale
+    pub stale_reply_age_secs: u64,
+    /// Minimum seconds between two scheduled event comments in one guild
+    pub event_debounce_secs: u64,
+    /// Minimum seconds between two topic edits of a countdown channel
+    pub topic_update_secs: u64,
+    /// Let multiple SI prefixes in one query stack, as in "kilomegaseconds"
+    pub si_stacking: bool,
+    /// With stacking on, the most prefixes one query can apply. No limit if
+    /// unset.
+    pub max_si_prefixes: Option<usize>,
+    /// Fold stacked SI prefixes into the one prefix they add up to, where
+    /// there is one, so "megakiloseconds" reads "gigaseconds"
+    pub simplify_si_prefixes: bool,
+    /// Enables the joke units, like heartbeats
+    pub easter_eggs: bool,
+    /// Average heart rate used to count heartbeats, in beats per minute
+    pub heartbeat_bpm: f64,
+    /// Seconds during which the exact same countdown won't be posted twice
+    /// in a row in a channel. 0 turns this off.
+    pub duplicate_window_secs: u64,
+    /// Seconds during which a user asking the exact same thing again is
+    /// told "I already told you" instead of getting a fresh countdown. 0
+    /// turns this off.
+    pub already_answered_secs: u64,
+    /// Seconds a countdown can hang on Discord before we decide the bot is
+    /// globally rate limited and drop new ones until it goes through. 0
+    /// turns this off, queueing everything.
+    pub global_rate_limit_secs: u64,
+    /// Show long lists, like `tue!allstatus`, one page at a time in a single
+    /// message instead of spread over several. Pages are turned with ◀️ and
+    /// ▶️ reactions, since Serenity 0.8 can't send buttons.
+    pub compact_lists: bool,
+    /// Lines on each page of a compact list
+    pub list_page_size: usize,
+    /// Seconds after the last page turn that a compact list stops turning
+    pub list_page_ttl_secs: u64,
+    /// Seconds a computed countdown is reused for the same question in the
+    /// same channel. 0 turns this off.
+    pub answer_cache_secs: u64,
+    /// Recent messages kept per channel, so telling whether a reply is to
+    /// the bot usually doesn't cost a request. 0 keeps none.
+    pub cached_messages: usize,
+    /// Most countdowns that can be on their way to Discord at once. Any more
+    /// wait for one of those to finish. 0 turns this off.
+    pub max_concurrent_sends: usize,
+    /// Check a few sample countdowns before connecting, and refuse to start
+    /// if any come out broken. `--selftest` runs the same check and exits.
+    pub self_test_on_start: bool,
+    /// Say hello when added to a new guild
+    pub welcome_on_join: bool,
+    /// What to say when added to a new guild, instead of the built-in intro
+    pub welcome_message: Option<String>,
+    /// Chance from 0 to 1 that the bot answers an implicit trigger at all.
+    /// Commands always get an answer.
+    pub response_chance: f64,
+    /// Seeds every random choice the bot makes, so they repeat exactly
+    /// from one run to the next. Meant for testing.
+    pub rng_seed: Option<u64>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            owner_id: None,
+            maintenance_notice: true,
+            reaction_trigger: None,
+            tuesday_roles: false,
+            custom_units: HashMap::new(),
+            followup_on_arrival: false,
+            utc_offset_minutes: None,
+            command_cooldowns: HashMap::new(),
+            trigger_cooldown_secs: 0,
+            premium_cooldown_multipliers: vec![1.0, 0.75, 0.5, 0.25],
+            voice_countdowns: Vec::new(),
+            voice_debounce_secs: 600,
+            cleanup_stale_replies: false,
+            stale_reply_age_secs: 86400,
+            event_debounce_secs: 300,
+            topic_update_secs: 600,
+            si_stacking: true,
+            max_si_prefixes: None,
+            simplify_si_prefixes: false,
+            easter_eggs: false,
+            heartbeat_bpm: 70.0,
+            response_chance: 1.0,
+            duplicate_window_secs: 0,
+            already_answered_secs: 0,
+            global_rate_limit_secs: 0,
+            max_concurrent_sends: 0,
+            answer_cache_secs: 0,
+            cached_messages: 50,
+            compact_lists: false,
+            self_test_on_start: false,
+            list_page_size: 10,
+            list_page_ttl_secs: 600,
+            welcome_on_join: true,
+            welcome_message: None,
+            rng_seed: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Config {
+        let mut file = match File::open(CONFIG_FILE) {
+            Ok(file) => file,
+            Err(_) => return Config::default(),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)
+            .expect("Error reading config.json");
+        serde_json::from_str(&contents).expect("Error parsing config.json")
+    }
+}
+
+impl Config {
+    /// The implicit trigger cooldown for a guild at `premium_tier`. Tiers
+    /// without a configured multiplier, including guilds we don't know the
+    /// tier of, get the unscaled cooldown, and one too long to represent is
+    /// as long as it can be.
+    pub fn trigger_cooldown(&self, premium_tier: Option<u64>) -> Duration {
+        let multiplier = premium_tier
+            .and_then(|tier| self.premium_cooldown_multipliers.get(tier as usize))
+            .copied()
+            .filter(|multiplier| multiplier.is_finite() && *multiplier >= 0.0)
+            .unwrap_or(1.0);
+        Duration::try_from_secs_f64(self.trigger_cooldown_secs as f64 * multiplier)
+            .unwrap_or(Duration::MAX)
+    }
+
+    /// The most SI prefixes a query can apply, if there is a limit
+    pub fn prefix_cap(&self) -> Option<usize> {
+        if self.si_stacking {
+            self.max_si_prefixes
+        } else {
+            Some(1)
+        }
+    }
+
+    pub fn rng(&self) -> StdRng {
+        match self.rng_seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
+}
+
+/// Rolls whether to answer an implicit trigger this time, given the
+/// configured `response_chance`
+pub fn should_respond<R: Rng>(chance: f64, rng: &mut R) -> bool {
+    if chance >= 1.0 {
+        true
+    } else if chance > 0.0 {
+        rng.gen_bool(chance)
+    } else {
+        // Also covers NaN, which gen_bool would panic on
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_cooldown(secs: u64, multipliers: Vec<f64>) -> Config {
+        Config {
+            trigger_cooldown_secs: secs,
+            premium_cooldown_multipliers: multipliers,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn scales_the_cooldown_by_boost_tier() {
+        let config = with_cooldown(60, vec![1.0, 0.75, 0.5, 0.25]);
+        assert_eq!(config.trigger_cooldown(Some(0)), Duration::from_secs(60));
+        assert_eq!(config.trigger_cooldown(Some(2)), Duration::from_secs(30));
+        assert_eq!(config.trigger_cooldown(Some(3)), Duration::from_secs(15));
+    }
+
+    #[test]
+    fn unknown_tiers_get_the_unscaled_cooldown() {
+        let config = with_cooldown(60, vec![1.0, 0.5]);
+        assert_eq!(config.trigger_cooldown(None), Duration::from_secs(60));
+        assert_eq!(config.trigger_cooldown(Some(3)), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn ignores_multipliers_that_make_no_sense() {
+        let config = with_cooldown(60, vec![-1.0, f64::NAN, f64::INFINITY]);
+        for tier in 0..3 {
+            assert_eq!(config.trigger_cooldown(Some(tier)), Duration::from_secs(60));
+        }
+    }
+
+    #[test]
+    fn caps_cooldowns_too_long_to_represent() {
+        let config = with_cooldown(u64::MAX, vec![1e300]);
+        assert_eq!(config.trigger_cooldown(Some(0)), Duration::MAX);
+    }
+
+    #[test]
+    fn always_or_never_responds_at_the_extremes() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            assert!(should_respond(1.0, &mut rng));
+            assert!(should_respond(2.0, &mut rng));
+            assert!(!should_respond(0.0, &mut rng));
+            assert!(!should_respond(-1.0, &mut rng));
+            assert!(!should_respond(f64::NAN, &mut rng));
+        }
+    }
+
+    #[test]
+    fn responds_some_of_the_time_in_between() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let responses = (0..1000).filter(|_| should_respond(0.25, &mut rng)).count();
+        assert!((150..350).contains(&responses), "{}", responses);
+    }
+}
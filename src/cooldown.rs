@@ -0,0 +1,407 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+use serenity::prelude::*;
+
+/// When each key (a user's command, a channel, ...) was last allowed through
+pub struct Cooldowns<K> {
+    last_used: HashMap<K, Instant>,
+}
+
+impl<K> Default for Cooldowns<K> {
+    fn default() -> Self {
+        Cooldowns {
+            last_used: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash> Cooldowns<K> {
+    /// Records a use of `key` at `now`, unless it's still on cooldown from a
+    /// previous use, in which case the time left is returned and nothing is
+    /// recorded.
+    pub fn check(&mut self, key: K, cooldown: Duration, now: Instant) -> Option<Duration> {
+        if let Some(last) = self.last_used.get(&key) {
+            let elapsed = now.duration_since(*last);
+            if elapsed < cooldown {
+                return Some(cooldown - elapsed);
+            }
+        }
+        self.last_used.insert(key, now);
+        None
+    }
+
+    /// How long until `key` is off cooldown, without recording a use
+    pub fn remaining(&self, key: &K, cooldown: Duration, now: Instant) -> Option<Duration> {
+        let elapsed = now.duration_since(*self.last_used.get(key)?);
+        if elapsed < cooldown {
+            Some(cooldown - elapsed)
+        } else {
+            None
+        }
+    }
+}
+
+/// The last reply in each channel, so an identical one can be held back
+#[derive(Default)]
+pub struct RecentReplies {
+    last: HashMap<u64, (String, Instant)>,
+}
+
+impl RecentReplies {
+    /// Whether `text` is the same as the last reply in `channel`, sent
+    /// within `window` of `now`. Otherwise, remembers `text` as the last
+    /// reply.
+    pub fn is_repeat(&mut self, channel: u64, text: &str, window: Duration, now: Instant) -> bool {
+        if let Some((last, sent)) = self.last.get(&channel) {
+            if last == text && now.duration_since(*sent) < window {
+                return true;
+            }
+        }
+        self.last.insert(channel, (text.to_string(), now));
+        false
+    }
+}
+
+/// Messages that already got an automatic answer, so no other trigger source
+/// answers them again. Only the most recent ones are kept.
+#[derive(Default)]
+pub struct AnsweredMessages {
+    recent: VecDeque<u64>,
+}
+
+/// How many answered messages `AnsweredMessages` remembers
+const MAX_ANSWERED_MESSAGES: usize = 1000;
+
+impl AnsweredMessages {
+    /// Marks `message` as answered. Returns false, changing nothing, if it
+    /// already was.
+    pub fn claim(&mut self, message: u64) -> bool {
+        if self.recent.contains(&message) {
+            return false;
+        }
+        if self.recent.len() == MAX_ANSWERED_MESSAGES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(message);
+        true
+    }
+}
+
+pub struct AnsweredMessagesKey;
+impl TypeMapKey for AnsweredMessagesKey {
+    type Value = AnsweredMessages;
+}
+
+/// The last question each user asked and what we answered, so asking the
+/// exact same thing again gets pointed back at the first answer
+#[derive(Default)]
+pub struct RecentAnswers {
+    last: HashMap<u64, (String, String, Instant)>,
+}
+
+impl RecentAnswers {
+    /// What we told `user` when they last asked `query`, if that was within
+    /// `window` of `now`. Queries are compared ignoring case and spacing.
+    pub fn already_answered(
+        &self,
+        user: u64,
+        query: &str,
+        window: Duration,
+        now: Instant,
+    ) -> Option<&str> {
+        let (last_query, answer, sent) = self.last.get(&user)?;
+        if *last_query == normalize_query(query) && now.duration_since(*sent) < window {
+            Some(answer)
+        } else {
+            None
+        }
+    }
+
+    /// Remembers `answer` as what `user` was told for `query`.
+    pub fn remember(&mut self, user: u64, query: &str, answer: &str, now: Instant) {
+        self.last
+            .insert(user, (normalize_query(query), answer.to_string(), now));
+    }
+}
+
+/// Countdowns worked out a moment ago, so a burst of the same trigger in one
+/// channel only gets computed once. Answers are kept per channel, timezone,
+/// and query, the query standing in for the unit since that's where the
+/// unit is read from.
+#[derive(Default)]
+pub struct AnswerCache {
+    answers: HashMap<(u64, String, String), (String, Instant)>,
+}
+
+impl AnswerCache {
+    /// The answer to `query` in `channel` and `zone`, if one was cached less
+    /// than `ttl` before `now`
+    pub fn get(
+        &self,
+        channel: u64,
+        zone: &str,
+        query: &str,
+        ttl: Duration,
+        now: Instant,
+    ) -> Option<&str> {
+        let key = (channel, zone.to_string(), normalize_query(query));
+        let (answer, computed) = self.answers.get(&key)?;
+        if now.duration_since(*computed) < ttl {
+            Some(answer)
+        } else {
+            None
+        }
+    }
+
+    /// Caches `answer`, and drops anything older than `ttl` while at it
+    pub fn insert(
+        &mut self,
+        channel: u64,
+        zone: &str,
+        query: &str,
+        answer: &str,
+        ttl: Duration,
+        now: Instant,
+    ) {
+        self.answers
+            .retain(|_, (_, computed)| now.duration_since(*computed) < ttl);
+        let key = (channel, zone.to_string(), normalize_query(query));
+        self.answers.insert(key, (answer.to_string(), now));
+    }
+}
+
+pub struct AnswerCacheKey;
+impl TypeMapKey for AnswerCacheKey {
+    type Value = AnswerCache;
+}
+
+fn normalize_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Tells a user how long until they can run `command` again, rounding up so
+/// we never say "0 seconds".
+pub fn cooldown_message(command: &str, remaining: Duration) -> String {
+    let mut secs = remaining.as_secs();
+    if remaining.subsec_nanos() > 0 {
+        secs += 1;
+    }
+    let unit = if secs == 1 { "second" } else { "seconds" };
+    format!(
+        "Slow down! You can use tue!{} again in {} {}.",
+        command, secs, unit
+    )
+}
+
+/// Per-user cooldowns for `Config::command_cooldowns`, keyed by command name
+/// and user ID
+pub struct CooldownKey;
+impl TypeMapKey for CooldownKey {
+    type Value = Cooldowns<(String, u64)>;
+}
+
+/// Per-channel cooldowns for the implicit "tues" trigger, keyed by channel ID
+pub struct TriggerCooldownKey;
+impl TypeMapKey for TriggerCooldownKey {
+    type Value = Cooldowns<u64>;
+}
+
+pub struct RecentRepliesKey;
+impl TypeMapKey for RecentRepliesKey {
+    type Value = RecentReplies;
+}
+
+pub struct RecentAnswersKey;
+impl TypeMapKey for RecentAnswersKey {
+    type Value = RecentAnswers;
+}
+
+/// Per-guild debounce for scheduled event comments, keyed by guild ID
+pub struct EventCooldownKey;
+impl TypeMapKey for EventCooldownKey {
+    type Value = Cooldowns<u64>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holds_back_a_command_until_its_cooldown_is_up() {
+        let mut cooldowns = Cooldowns::default();
+        let start = Instant::now();
+        let cooldown = Duration::from_secs(10);
+        let key = || ("next".to_string(), 1);
+        assert_eq!(cooldowns.check(key(), cooldown, start), None);
+        assert_eq!(
+            cooldowns.check(key(), cooldown, start + Duration::from_secs(4)),
+            Some(Duration::from_secs(6))
+        );
+        assert_eq!(
+            cooldowns.check(key(), cooldown, start + Duration::from_secs(10)),
+            None
+        );
+    }
+
+    #[test]
+    fn keeps_cooldowns_apart_by_command_and_user() {
+        let mut cooldowns = Cooldowns::default();
+        let now = Instant::now();
+        let cooldown = Duration::from_secs(10);
+        assert_eq!(
+            cooldowns.check(("next".to_string(), 1), cooldown, now),
+            None
+        );
+        assert_eq!(
+            cooldowns.check(("next".to_string(), 2), cooldown, now),
+            None
+        );
+        assert_eq!(
+            cooldowns.check(("help".to_string(), 1), cooldown, now),
+            None
+        );
+    }
+
+    #[test]
+    fn a_held_back_use_does_not_restart_the_cooldown() {
+        let mut cooldowns = Cooldowns::default();
+        let start = Instant::now();
+        let cooldown = Duration::from_secs(10);
+        cooldowns.check(1, cooldown, start);
+        cooldowns.check(1, cooldown, start + Duration::from_secs(8));
+        assert_eq!(
+            cooldowns.remaining(&1, cooldown, start + Duration::from_secs(9)),
+            Some(Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn rounds_the_remaining_time_up() {
+        assert_eq!(
+            cooldown_message("next", Duration::from_millis(200)),
+            "Slow down! You can use tue!next again in 1 second."
+        );
+        assert_eq!(
+            cooldown_message("next", Duration::from_millis(4100)),
+            "Slow down! You can use tue!next again in 5 seconds."
+        );
+        assert_eq!(
+            cooldown_message("next", Duration::from_secs(3)),
+            "Slow down! You can use tue!next again in 3 seconds."
+        );
+    }
+
+    #[test]
+    fn holds_back_identical_replies_within_the_window() {
+        let mut recent = RecentReplies::default();
+        let start = Instant::now();
+        let window = Duration::from_secs(30);
+        assert!(!recent.is_repeat(1, "It is 5 hours until Tuesday.", window, start));
+        let later = start + Duration::from_secs(10);
+        assert!(recent.is_repeat(1, "It is 5 hours until Tuesday.", window, later));
+        // Other channels and other replies aren't repeats
+        assert!(!recent.is_repeat(2, "It is 5 hours until Tuesday.", window, later));
+        assert!(!recent.is_repeat(1, "It is 4 hours until Tuesday.", window, later));
+    }
+
+    #[test]
+    fn answers_each_message_once() {
+        let mut answered = AnsweredMessages::default();
+        assert!(answered.claim(1));
+        assert!(!answered.claim(1));
+        assert!(answered.claim(2));
+    }
+
+    #[test]
+    fn forgets_the_oldest_answered_messages() {
+        let mut answered = AnsweredMessages::default();
+        for message in 0..=MAX_ANSWERED_MESSAGES as u64 {
+            assert!(answered.claim(message));
+        }
+        assert!(answered.claim(0));
+        assert!(!answered.claim(MAX_ANSWERED_MESSAGES as u64));
+    }
+
+    #[test]
+    fn points_back_at_the_same_question() {
+        let mut answers = RecentAnswers::default();
+        let start = Instant::now();
+        let window = Duration::from_secs(60);
+        answers.remember(1, "hours until Tuesday", "It is 5 hours.", start);
+        let later = start + Duration::from_secs(30);
+        assert_eq!(
+            answers.already_answered(1, "  HOURS until   tuesday", window, later),
+            Some("It is 5 hours.")
+        );
+        assert_eq!(
+            answers.already_answered(1, "days until tuesday", window, later),
+            None
+        );
+        assert_eq!(
+            answers.already_answered(2, "hours until tuesday", window, later),
+            None
+        );
+        assert_eq!(
+            answers.already_answered(1, "hours until tuesday", window, start + window),
+            None
+        );
+    }
+
+    #[test]
+    fn lets_identical_replies_through_after_the_window() {
+        let mut recent = RecentReplies::default();
+        let start = Instant::now();
+        let window = Duration::from_secs(30);
+        recent.is_repeat(1, "same", window, start);
+        assert!(!recent.is_repeat(1, "same", window, start + window));
+    }
+
+    #[test]
+    fn reuses_an_answer_within_its_window() {
+        let mut cache = AnswerCache::default();
+        let start = Instant::now();
+        let ttl = Duration::from_secs(5);
+        cache.insert(
+            1,
+            "UTC",
+            "Tuesday?",
+            "It is 3 hours until Tuesday.",
+            ttl,
+            start,
+        );
+        assert_eq!(
+            cache.get(1, "UTC", "tuesday?", ttl, start + Duration::from_secs(4)),
+            Some("It is 3 hours until Tuesday.")
+        );
+        assert_eq!(cache.get(1, "UTC", "tuesday?", ttl, start + ttl), None);
+    }
+
+    #[test]
+    fn keeps_answers_apart_by_channel_zone_and_query() {
+        let mut cache = AnswerCache::default();
+        let now = Instant::now();
+        let ttl = Duration::from_secs(5);
+        cache.insert(1, "UTC", "tuesday", "answer", ttl, now);
+        assert_eq!(cache.get(2, "UTC", "tuesday", ttl, now), None);
+        assert_eq!(cache.get(1, "+05:00", "tuesday", ttl, now), None);
+        assert_eq!(cache.get(1, "UTC", "minutes until tuesday", ttl, now), None);
+    }
+
+    #[test]
+    fn drops_stale_answers_when_caching_new_ones() {
+        let mut cache = AnswerCache::default();
+        let start = Instant::now();
+        let ttl = Duration::from_secs(5);
+        cache.insert(1, "UTC", "tuesday", "old", ttl, start);
+        cache.insert(2, "UTC", "tuesday", "new", ttl, start + ttl);
+        assert_eq!(cache.answers.len(), 1);
+    }
+}
@@ -0,0 +1,331 @@
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Weekday};
+
+/// Days from a date falling on `from` until the next `to`, counting a day as
+/// zero days away from itself.
+pub fn days_until_weekday(from: Weekday, to: Weekday) -> i64 {
+    (7 + to.num_days_from_monday() as i64 - from.num_days_from_monday() as i64) % 7
+}
+
+/// The start of the upcoming `day`. On that day this is the start of today,
+/// so it's already in the past.
+pub fn next_weekday(now: NaiveDateTime, day: Weekday) -> NaiveDateTime {
+    (now.date() + Duration::days(days_until_weekday(now.weekday(), day))).and_hms(0, 0, 0)
+}
+
+/// `at` on the upcoming `day`, which may already have passed if it's today.
+pub fn next_weekday_at(now: NaiveDateTime, day: Weekday, at: NaiveTime) -> NaiveDateTime {
+    next_weekday(now, day).date().and_time(at)
+}
+
+/// The start of the most recent `day`. On that day this is the start of
+/// today, same as `next_weekday`.
+pub fn previous_weekday(now: NaiveDateTime, day: Weekday) -> NaiveDateTime {
+    let days_since = (7 - days_until_weekday(now.weekday(), day)) % 7;
+    (now.date() - Duration::days(days_since)).and_hms(0, 0, 0)
+}
+
+/// The most recent `at` on `day` that isn't after `now`. Early on `day`,
+/// before `at`, that's a week ago.
+pub fn previous_weekday_at(now: NaiveDateTime, day: Weekday, at: NaiveTime) -> NaiveDateTime {
+    let latest = previous_weekday(now, day).date().and_time(at);
+    if latest > now {
+        latest - Duration::weeks(1)
+    } else {
+        latest
+    }
+}
+
+/// The start of the upcoming Tuesday. On a Tuesday this is the start of
+/// today, so it's already in the past.
+pub fn next_tuesday(now: NaiveDateTime) -> NaiveDateTime {
+    next_weekday(now, Weekday::Tue)
+}
+
+/// How many days from `start` to `end`, both inclusive, fall on `weekday`.
+/// Zero if `end` is before `start`.
+pub fn count_weekdays_between(start: NaiveDate, end: NaiveDate, weekday: Weekday) -> u32 {
+    if end < start {
+        return 0;
+    }
+    let offset = (7 + weekday.num_days_from_monday() as i64
+        - start.weekday().num_days_from_monday() as i64)
+        % 7;
+    let first = start + Duration::days(offset);
+    if first > end {
+        return 0;
+    }
+    (end.signed_duration_since(first).num_days() / 7 + 1) as u32
+}
+
+/// Every Tuesday from `today` through the end of its year. If `today` is a
+/// Tuesday it counts, since it isn't over yet.
+pub fn tuesdays_left_in_year(today: NaiveDate) -> Vec<NaiveDate> {
+    let first = today + Duration::days(days_until_weekday(today.weekday(), Weekday::Tue));
+    let end = NaiveDate::from_ymd(today.year(), 12, 31);
+    let count = count_weekdays_between(today, end, Weekday::Tue);
+    (0..count as i64)
+        .map(|week| first + Duration::weeks(week))
+        .collect()
+}
+
+/// How much of the span from `start` to `end` falls between `gap_start` and
+/// `gap_end`. Zero if they don't overlap or either span is backwards.
+pub fn overlap(
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    gap_start: NaiveDateTime,
+    gap_end: NaiveDateTime,
+) -> Duration {
+    let from = start.max(gap_start);
+    let to = end.min(gap_end);
+    if to > from {
+        to - from
+    } else {
+        Duration::zero()
+    }
+}
+
+/// `d` rounded to the nearest multiple of `grid`, with halfway rounding away
+/// from zero. A zero or negative `grid` leaves `d` as it is.
+pub fn round_to_grid(d: Duration, grid: Duration) -> Duration {
+    let grid = grid.num_milliseconds();
+    if grid <= 0 {
+        return d;
+    }
+    let ms = d.num_milliseconds();
+    let steps = (ms.abs() + grid / 2) / grid;
+    Duration::milliseconds(ms.signum() * steps * grid)
+}
+
+/// How far through its day `now` is, from 0 at midnight up to just under 1
+pub fn fraction_of_day(now: NaiveDateTime) -> f64 {
+    now.time().num_seconds_from_midnight() as f64 / 86_400.0
+}
+
+/// `time` rounded to the nearest multiple of `granularity` since midnight,
+/// with halfway rounding up. That can land on the next day.
+pub fn round_datetime(time: NaiveDateTime, granularity: Duration) -> NaiveDateTime {
+    let midnight = time.date().and_hms(0, 0, 0);
+    midnight + round_to_grid(time - midnight, granularity)
+}
+
+/// `at` on the next `n`th `day` of a month, like the 2nd Tuesday, starting
+/// with this month. Months without an `n`th one (most have no 5th Tuesday)
+/// are skipped. Like `next_weekday_at`, this may already have passed if
+/// it's today. `None` if `n` isn't 1 to 5.
+pub fn next_nth_weekday_at(
+    now: NaiveDateTime,
+    n: u8,
+    day: Weekday,
+    at: NaiveTime,
+) -> Option<NaiveDateTime> {
+    if !(1..=5).contains(&n) {
+        return None;
+    }
+    let (mut year, mut month) = (now.year(), now.month());
+    // Every month has a 4th of each weekday, and five months in a row can't
+    // all miss a 5th, so this always finds one
+    for _ in 0..6 {
+        if let Some(date) = NaiveDate::from_weekday_of_month_opt(year, month, day, n) {
+            if date >= now.date() {
+                return Some(date.and_time(at));
+            }
+        }
+        if month == 12 {
+            year += 1;
+            month = 1;
+        } else {
+            month += 1;
+        }
+    }
+    None
+}
+
+/// Holidays that fall on the same date every year, as (month, day), for each
+/// calendar a guild can pick. Holidays that move around, like Easter, aren't
+/// covered yet.
+pub const HOLIDAY_CALENDARS: &[(&str, &[(u32, u32)])] = &[
+    ("au", &[(1, 1), (1, 26), (4, 25), (12, 25), (12, 26)]),
+    ("ca", &[(1, 1), (7, 1), (11, 11), (12, 25), (12, 26)]),
+    ("uk", &[(1, 1), (12, 25), (12, 26)]),
+    ("us", &[(1, 1), (6, 19), (7, 4), (11, 11), (12, 25)]),
+];
+
+/// Whether `date` is a holiday in the named calendar. Unknown calendars have
+/// no holidays.
+pub fn is_calendar_holiday(calendar: &str, date: NaiveDate) -> bool {
+    HOLIDAY_CALENDARS
+        .iter()
+        .find(|(name, _)| *name == calendar)
+        .is_some_and(|(_, days)| days.contains(&(date.month(), date.day())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        // January 2024 starts on a Monday, so the 2nd is a Tuesday
+        NaiveDate::from_ymd(2024, 1, day).and_hms(hour, 0, 0)
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(year, month, day)
+    }
+
+    #[test]
+    fn rounds_durations_to_the_nearest_grid_line() {
+        let grid = Duration::minutes(15);
+        assert_eq!(
+            round_to_grid(Duration::minutes(22), grid),
+            Duration::minutes(15)
+        );
+        assert_eq!(
+            round_to_grid(Duration::seconds(22 * 60 + 30), grid),
+            Duration::minutes(30)
+        );
+        assert_eq!(
+            round_to_grid(Duration::minutes(-22), grid),
+            Duration::minutes(-15)
+        );
+        assert_eq!(
+            round_to_grid(Duration::minutes(22), Duration::zero()),
+            Duration::minutes(22)
+        );
+    }
+
+    #[test]
+    fn measures_how_far_through_the_day_it_is() {
+        assert_eq!(fraction_of_day(at(1, 0)), 0.0);
+        assert_eq!(fraction_of_day(at(1, 18)), 0.75);
+        assert!(fraction_of_day(date(2024, 1, 1).and_hms(23, 59, 59)) < 1.0);
+    }
+
+    #[test]
+    fn rounds_times_to_the_granularity() {
+        let time = date(2024, 1, 1).and_hms(10, 29, 30);
+        assert_eq!(
+            round_datetime(time, Duration::minutes(1)),
+            date(2024, 1, 1).and_hms(10, 30, 0)
+        );
+        assert_eq!(round_datetime(time, Duration::hours(1)), at(1, 10));
+    }
+
+    #[test]
+    fn rounding_can_land_on_the_next_day() {
+        let time = date(2024, 1, 1).and_hms(23, 59, 30);
+        assert_eq!(round_datetime(time, Duration::minutes(1)), at(2, 0));
+    }
+
+    #[test]
+    fn finds_the_nth_tuesday_from_this_month_on() {
+        let midnight = NaiveTime::from_hms(0, 0, 0);
+        let find = |n| next_nth_weekday_at(at(3, 12), n, Weekday::Tue, midnight);
+        assert_eq!(find(2), Some(at(9, 0)));
+        assert_eq!(find(5), Some(at(30, 0)));
+        // The 1st Tuesday of January is already over
+        assert_eq!(find(1), Some(date(2024, 2, 6).and_hms(0, 0, 0)));
+    }
+
+    #[test]
+    fn skips_months_without_a_fifth_tuesday() {
+        let now = date(2024, 1, 31).and_hms(0, 0, 0);
+        assert_eq!(
+            next_nth_weekday_at(now, 5, Weekday::Tue, NaiveTime::from_hms(0, 0, 0)),
+            Some(date(2024, 4, 30).and_hms(0, 0, 0))
+        );
+    }
+
+    #[test]
+    fn has_no_sixth_tuesday() {
+        assert_eq!(
+            next_nth_weekday_at(at(3, 12), 6, Weekday::Tue, NaiveTime::from_hms(0, 0, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn measures_overlapping_spans() {
+        assert_eq!(
+            overlap(at(1, 0), at(3, 0), at(2, 0), at(5, 0)),
+            Duration::days(1)
+        );
+        assert_eq!(
+            overlap(at(2, 6), at(2, 18), at(1, 0), at(5, 0)),
+            Duration::hours(12)
+        );
+    }
+
+    #[test]
+    fn measures_nothing_for_apart_or_backwards_spans() {
+        assert_eq!(
+            overlap(at(1, 0), at(2, 0), at(3, 0), at(5, 0)),
+            Duration::zero()
+        );
+        assert_eq!(
+            overlap(at(1, 0), at(2, 0), at(2, 0), at(5, 0)),
+            Duration::zero()
+        );
+        assert_eq!(
+            overlap(at(3, 0), at(1, 0), at(1, 0), at(5, 0)),
+            Duration::zero()
+        );
+    }
+
+    #[test]
+    fn counts_tuesdays_left_mid_year() {
+        let left = tuesdays_left_in_year(date(2024, 7, 1));
+        assert_eq!(left.len(), 27);
+        assert_eq!(left.first(), Some(&date(2024, 7, 2)));
+        assert_eq!(left.last(), Some(&date(2024, 12, 31)));
+    }
+
+    #[test]
+    fn counts_tuesdays_left_near_year_end() {
+        assert_eq!(
+            tuesdays_left_in_year(date(2024, 12, 25)),
+            vec![date(2024, 12, 31)]
+        );
+        assert!(tuesdays_left_in_year(date(2023, 12, 27)).is_empty());
+    }
+
+    #[test]
+    fn counts_today_when_it_is_tuesday() {
+        assert_eq!(
+            tuesdays_left_in_year(date(2024, 12, 31)),
+            vec![date(2024, 12, 31)]
+        );
+        assert_eq!(
+            count_weekdays_between(date(2024, 1, 2), date(2024, 1, 2), Weekday::Tue),
+            1
+        );
+    }
+
+    #[test]
+    fn finds_the_previous_tuesday() {
+        assert_eq!(previous_weekday(at(3, 12), Weekday::Tue), at(2, 0));
+        assert_eq!(
+            previous_weekday(at(1, 12), Weekday::Tue),
+            NaiveDate::from_ymd(2023, 12, 26).and_hms(0, 0, 0)
+        );
+        assert_eq!(previous_weekday(at(2, 12), Weekday::Tue), at(2, 0));
+    }
+
+    #[test]
+    fn finds_the_previous_tuesday_at_a_time() {
+        let six = NaiveTime::from_hms(18, 0, 0);
+        assert_eq!(previous_weekday_at(at(2, 20), Weekday::Tue, six), at(2, 18));
+        assert_eq!(
+            previous_weekday_at(at(2, 10), Weekday::Tue, six),
+            NaiveDate::from_ymd(2023, 12, 26).and_hms(18, 0, 0)
+        );
+    }
+
+    #[test]
+    fn looks_up_calendar_holidays() {
+        assert!(is_calendar_holiday("us", date(2024, 7, 4)));
+        assert!(!is_calendar_holiday("uk", date(2024, 7, 4)));
+        assert!(is_calendar_holiday("uk", date(2029, 12, 25)));
+        assert!(!is_calendar_holiday("nowhere", date(2024, 12, 25)));
+    }
+}
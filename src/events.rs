@@ -0,0 +1,123 @@
+use chrono::{DateTime, Datelike, NaiveDateTime, Utc, Weekday};
+use serde_json::Value;
+
+use crate::{state::GuildConfig, timezone::Zone, units::Unit};
+
+/// Gateway event name for a newly created guild scheduled event. The serenity
+/// version we use predates scheduled events, so they arrive as unknown events.
+pub const SCHEDULED_EVENT_CREATE: &str = "GUILD_SCHEDULED_EVENT_CREATE";
+
+/// The parts of a scheduled event payload we care about
+pub struct ScheduledEvent {
+    pub id: u64,
+    pub guild_id: u64,
+    pub name: String,
+    pub start: DateTime<Utc>,
+}
+
+impl ScheduledEvent {
+    pub fn from_raw(raw: &Value) -> Option<ScheduledEvent> {
+        // Snowflakes come over the wire as strings
+        let id = raw.get("id")?.as_str()?.parse().ok()?;
+        let guild_id = raw.get("guild_id")?.as_str()?.parse().ok()?;
+        let name = raw.get("name")?.as_str()?.to_string();
+        let start = DateTime::parse_from_rfc3339(raw.get("scheduled_start_time")?.as_str()?)
+            .ok()?
+            .with_timezone(&Utc);
+        Some(ScheduledEvent {
+            id,
+            guild_id,
+            name,
+            start,
+        })
+    }
+
+    /// Whether the event starts on a Tuesday in `zone`
+    pub fn is_on_tuesday(&self, zone: Zone) -> bool {
+        zone.to_local(self.start).weekday() == Weekday::Tue
+    }
+
+    /// The comment to post about this event at `now` in `zone`, if it's
+    /// worth one: it has to be on a Tuesday and not already started.
+    pub fn countdown(
+        &self,
+        now: NaiveDateTime,
+        zone: Zone,
+        unit: &Unit,
+        guild: &GuildConfig,
+    ) -> Option<String> {
+        let start = zone.to_local(self.start);
+        if !self.is_on_tuesday(zone) || start <= now {
+            return None;
+        }
+        let until = start.signed_duration_since(now).num_milliseconds();
+        Some(format!(
+            "\"{}\" is on a {}! It starts in {}.",
+            self.name,
+            guild.occasion_name(),
+            unit.render(until, guild)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    use crate::units::default_unit;
+
+    fn utc() -> Zone {
+        Zone::from_minutes(0).unwrap()
+    }
+
+    /// An event starting Tuesday, Jan 2 2024 at 18:00 UTC
+    fn event() -> ScheduledEvent {
+        ScheduledEvent::from_raw(&serde_json::json!({
+            "id": "1",
+            "guild_id": "2",
+            "name": "Game night",
+            "scheduled_start_time": "2024-01-02T18:00:00+00:00",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn reads_the_raw_payload() {
+        let event = event();
+        assert_eq!(event.id, 1);
+        assert_eq!(event.guild_id, 2);
+        assert_eq!(event.name, "Game night");
+        assert!(ScheduledEvent::from_raw(&serde_json::json!({ "id": 1 })).is_none());
+    }
+
+    #[test]
+    fn checks_the_weekday_in_the_guild_zone() {
+        assert!(event().is_on_tuesday(utc()));
+        // 04:00 on Wednesday ten hours east
+        assert!(!event().is_on_tuesday(Zone::from_minutes(600).unwrap()));
+    }
+
+    #[test]
+    fn counts_down_to_upcoming_tuesday_events() {
+        let now = NaiveDate::from_ymd(2024, 1, 1).and_hms(18, 0, 0);
+        assert_eq!(
+            event().countdown(now, utc(), &default_unit(), &GuildConfig::default()),
+            Some("\"Game night\" is on a Tuesday! It starts in 24 hours.".to_string())
+        );
+    }
+
+    #[test]
+    fn ignores_other_days_and_started_events() {
+        let guild = GuildConfig::default();
+        let before = NaiveDate::from_ymd(2024, 1, 1).and_hms(18, 0, 0);
+        let after = NaiveDate::from_ymd(2024, 1, 2).and_hms(19, 0, 0);
+        let east = Zone::from_minutes(600).unwrap();
+        assert!(event()
+            .countdown(before, east, &default_unit(), &guild)
+            .is_none());
+        assert!(event()
+            .countdown(after, utc(), &default_unit(), &guild)
+            .is_none());
+    }
+}
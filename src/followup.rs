@@ -0,0 +1,67 @@
+use serde_json::json;
+use serenity::{model::id::ChannelId, prelude::*};
+
+use crate::{clock, scheduler::is_unknown_message, state::TrackedQuery, ConfigKey, StateKey};
+
+/// Replies to every query whose Tuesday has arrived
+pub fn send_followups(ctx: &Context) {
+    let due = {
+        let mut data = ctx.data.write();
+        let enabled = data
+            .get::<ConfigKey>()
+            .expect("Expected to find config in context")
+            .followup_on_arrival;
+        let state = data
+            .get_mut::<StateKey>()
+            .expect("Expected to find bot state in context");
+        let due = state.take_arrived_queries(clock::now());
+        if !due.is_empty() {
+            state.save();
+        }
+        // Still drain the queue when turned off, so turning it back on
+        // doesn't dump a backlog of old follow-ups
+        if !enabled {
+            return;
+        }
+        due
+    };
+
+    for query in due {
+        send_followup(ctx, &query);
+    }
+}
+
+fn send_followup(ctx: &Context, query: &TrackedQuery) {
+    let channel_id = ChannelId(query.channel_id);
+    // Replying to a deleted message fails, and there'd be nothing to reply
+    // to anyway
+    match ctx.http.get_message(query.channel_id, query.message_id) {
+        Ok(_) => {}
+        Err(why) => {
+            if !is_unknown_message(&why) {
+                println!("Error fetching query to follow up on: {:?}", why);
+            }
+            return;
+        }
+    }
+
+    let occasion = {
+        let data = ctx.data.read();
+        let guild_id = channel_id
+            .to_channel_cached(&ctx.cache)
+            .and_then(|channel| channel.guild())
+            .map(|channel| channel.read().guild_id);
+        data.get::<StateKey>()
+            .expect("Expected to find bot state in context")
+            .guild(guild_id.map(|id| id.0))
+            .occasion_name()
+            .to_string()
+    };
+    let map = json!({
+        "content": format!("It's {} now!", occasion),
+        "message_reference": { "message_id": query.message_id.to_string() },
+    });
+    if let Err(why) = ctx.http.send_message(query.channel_id, &map) {
+        println!("Error sending follow-up: {:?}", why);
+    }
+}
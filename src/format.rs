@@ -0,0 +1,298 @@
+use chrono::NaiveTime;
+
+use crate::state::{GuildConfig, NumberStyle, SmallValueStyle, TimeFormat};
+
+const ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+
+const TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+/// Largest number `number_to_words` will spell out
+pub const MAX_WORDS: u64 = 999_999;
+
+/// Spells out numbers below 1000, e.g. 115 -> "one hundred fifteen"
+fn hundreds_to_words(n: u64) -> String {
+    let mut words = Vec::new();
+    if n >= 100 {
+        words.push(format!("{} hundred", ONES[(n / 100) as usize]));
+    }
+    let rest = n % 100;
+    if rest >= 20 {
+        let tens = TENS[(rest / 10) as usize];
+        words.push(match rest % 10 {
+            0 => tens.to_string(),
+            ones => format!("{}-{}", tens, ONES[ones as usize]),
+        });
+    } else if rest > 0 || n == 0 {
+        words.push(ONES[rest as usize].to_string());
+    }
+    words.join(" ")
+}
+
+/// Spells out `n` in English, e.g. 21 -> "twenty-one". Anything above
+/// `MAX_WORDS` is returned as digits.
+pub fn number_to_words(n: u64) -> String {
+    if n > MAX_WORDS {
+        return n.to_string();
+    }
+    let thousands = n / 1000;
+    let rest = n % 1000;
+    if thousands == 0 {
+        return hundreds_to_words(rest);
+    }
+    let mut words = format!("{} thousand", hundreds_to_words(thousands));
+    if rest > 0 {
+        words.push(' ');
+        words.push_str(&hundreds_to_words(rest));
+    }
+    words
+}
+
+/// Reads a number spelled out in English, the reverse of `number_to_words`.
+/// Takes the words already split up, so "twenty-one" and "twenty one" both
+/// work, and "a" counts as one. Numbers too big for a u64 are `None`.
+pub fn words_to_number(words: &[&str]) -> Option<u64> {
+    if words.is_empty() {
+        return None;
+    }
+    let mut total: u64 = 0;
+    let mut current: u64 = 0;
+    for word in words.iter().flat_map(|word| word.split('-')) {
+        let word = word.to_ascii_lowercase();
+        if let Some(n) = ONES.iter().position(|&one| one == word) {
+            current = current.checked_add(n as u64)?;
+        } else if let Some(n) = TENS.iter().skip(2).position(|&ten| ten == word) {
+            current = current.checked_add((n as u64 + 2) * 10)?;
+        } else {
+            match word.as_str() {
+                "a" | "an" if current == 0 => current = 1,
+                "hundred" => current = current.max(1).checked_mul(100)?,
+                "thousand" => {
+                    total = total.checked_add(current.max(1).checked_mul(1000)?)?;
+                    current = 0;
+                }
+                _ => return None,
+            }
+        }
+    }
+    total.checked_add(current)
+}
+
+/// Writes `n` in binary, octal, or hex with the usual `0b`/`0o`/`0x` prefix.
+/// Any other style gets plain decimal.
+pub fn format_in_base(n: u64, style: NumberStyle) -> String {
+    match style {
+        NumberStyle::Binary => format!("{:#b}", n),
+        NumberStyle::Octal => format!("{:#o}", n),
+        NumberStyle::Hex => format!("{:#x}", n),
+        _ => n.to_string(),
+    }
+}
+
+/// Renders a countdown value in the guild's chosen style. Spelling out and
+/// other bases only show whole numbers; fractional values, and ones too
+/// large for the style, stay as digits.
+pub fn format_value(value: f64, guild: &GuildConfig) -> String {
+    if guild.small_values == SmallValueStyle::Scientific && guild.is_small(value) {
+        return format!("{:.3e}", value);
+    }
+    match guild.number_style {
+        NumberStyle::Words if value.fract() == 0.0 && value >= 0.0 && value <= MAX_WORDS as f64 => {
+            number_to_words(value as u64)
+        }
+        style @ NumberStyle::Binary | style @ NumberStyle::Octal | style @ NumberStyle::Hex
+            if value.fract() == 0.0 && value >= 0.0 && value <= u64::MAX as f64 =>
+        {
+            format_in_base(value as u64, style)
+        }
+        _ => format!("{}", value),
+    }
+}
+
+/// Rounds `value` to a whole number and groups its digits in threes, e.g.
+/// 1234567.8 -> "1,234,568"
+pub fn format_thousands(value: f64) -> String {
+    let digits = format!("{}", value.round().abs());
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    if value.round() < 0.0 {
+        grouped.insert(0, '-');
+    }
+    grouped
+}
+
+/// Writes a time of day like "3:04 PM" or "15:04". Every clock time the bot
+/// shows should go through here so a guild's choice is respected.
+pub fn format_clock(time: NaiveTime, format: TimeFormat) -> String {
+    match format {
+        TimeFormat::TwelveHour => time.format("%-I:%M %p").to_string(),
+        TimeFormat::TwentyFourHour => time.format("%H:%M").to_string(),
+    }
+}
+
+/// Writes a duration tersely, like a timer: "3d 4h", "12m 5s", "0s". Only
+/// the two largest parts are shown.
+pub fn format_timer(ms: i64) -> String {
+    let secs = ms.max(0) / 1000;
+    let parts = [
+        (secs / 86_400, "d"),
+        (secs / 3600 % 24, "h"),
+        (secs / 60 % 60, "m"),
+        (secs % 60, "s"),
+    ];
+    let shown: Vec<String> = parts
+        .iter()
+        .skip_while(|(amount, _)| *amount == 0)
+        .take(2)
+        .map(|(amount, suffix)| format!("{}{}", amount, suffix))
+        .collect();
+    if shown.is_empty() {
+        "0s".to_string()
+    } else {
+        shown.join(" ")
+    }
+}
+
+/// Most decimals `format_percent` will show
+pub const MAX_PERCENT_DECIMALS: u8 = 2;
+
+/// `fraction` of the way as a percentage with `decimals` places, like "43.7".
+/// Kept between 0 and 100, and rounded down so nothing reads 100 until it's
+/// actually all the way there.
+pub fn format_percent(fraction: f64, decimals: u8) -> String {
+    let decimals = decimals.min(MAX_PERCENT_DECIMALS);
+    let scale = 10f64.powi(decimals as i32);
+    let fraction = if fraction.is_nan() {
+        0.0
+    } else {
+        fraction.clamp(0.0, 1.0)
+    };
+    // The nudge keeps float error from flooring 29% down to 28.99%
+    let percent = (fraction * 100.0 * scale + 1e-9).floor() / scale;
+    format!("{:.*}", decimals as usize, percent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guild_with(number_style: NumberStyle) -> GuildConfig {
+        GuildConfig {
+            number_style,
+            ..GuildConfig::default()
+        }
+    }
+
+    #[test]
+    fn spells_out_tricky_numbers() {
+        assert_eq!(number_to_words(0), "zero");
+        assert_eq!(number_to_words(13), "thirteen");
+        assert_eq!(number_to_words(21), "twenty-one");
+        assert_eq!(number_to_words(100), "one hundred");
+        assert_eq!(number_to_words(115), "one hundred fifteen");
+        assert_eq!(number_to_words(1000), "one thousand");
+        assert_eq!(
+            number_to_words(999_999),
+            "nine hundred ninety-nine thousand nine hundred ninety-nine"
+        );
+    }
+
+    #[test]
+    fn leaves_numbers_past_the_limit_as_digits() {
+        assert_eq!(number_to_words(MAX_WORDS + 1), "1000000");
+    }
+
+    #[test]
+    fn only_spells_out_whole_values() {
+        let guild = guild_with(NumberStyle::Words);
+        assert_eq!(format_value(23.0, &guild), "twenty-three");
+        assert_eq!(format_value(23.5, &guild), "23.5");
+        assert_eq!(format_value(-2.0, &guild), "-2");
+        assert_eq!(format_value(2_000_000.0, &guild), "2000000");
+    }
+
+    #[test]
+    fn writes_clock_times_in_either_format() {
+        let time = NaiveTime::from_hms(15, 4, 0);
+        assert_eq!(format_clock(time, TimeFormat::TwelveHour), "3:04 PM");
+        assert_eq!(format_clock(time, TimeFormat::TwentyFourHour), "15:04");
+    }
+
+    #[test]
+    fn writes_midnight_and_noon_on_a_twelve_hour_clock() {
+        let midnight = NaiveTime::from_hms(0, 5, 0);
+        assert_eq!(format_clock(midnight, TimeFormat::TwelveHour), "12:05 AM");
+        assert_eq!(format_clock(midnight, TimeFormat::TwentyFourHour), "00:05");
+        let noon = NaiveTime::from_hms(12, 0, 0);
+        assert_eq!(format_clock(noon, TimeFormat::TwelveHour), "12:00 PM");
+    }
+
+    #[test]
+    fn writes_the_two_largest_timer_parts() {
+        assert_eq!(format_timer((3 * 86_400 + 4 * 3600 + 59) * 1000), "3d 4h");
+        assert_eq!(format_timer((12 * 60 + 5) * 1000 + 999), "12m 5s");
+        assert_eq!(format_timer(86_400_000), "1d 0h");
+        assert_eq!(format_timer(999), "0s");
+        assert_eq!(format_timer(-5000), "0s");
+    }
+
+    #[test]
+    fn writes_other_bases_with_their_prefix() {
+        assert_eq!(format_in_base(132, NumberStyle::Binary), "0b10000100");
+        assert_eq!(format_in_base(132, NumberStyle::Octal), "0o204");
+        assert_eq!(format_in_base(132, NumberStyle::Hex), "0x84");
+        assert_eq!(format_in_base(132, NumberStyle::Words), "132");
+    }
+
+    #[test]
+    fn leaves_fractional_values_in_decimal() {
+        let guild = guild_with(NumberStyle::Hex);
+        assert_eq!(format_value(255.0, &guild), "0xff");
+        assert_eq!(format_value(255.5, &guild), "255.5");
+        assert_eq!(format_value(-1.0, &guild), "-1");
+    }
+
+    #[test]
+    fn shows_percentages_to_the_chosen_decimals() {
+        assert_eq!(format_percent(0.29, 0), "29");
+        assert_eq!(format_percent(0.29, 2), "29.00");
+        assert_eq!(format_percent(0.123456, 1), "12.3");
+        // Rounds down, so it never claims 100% early
+        assert_eq!(format_percent(0.99999, 2), "99.99");
+        assert_eq!(format_percent(0.123456, 9), "12.34");
+    }
+
+    #[test]
+    fn keeps_percentages_between_0_and_100() {
+        assert_eq!(format_percent(1.5, 0), "100");
+        assert_eq!(format_percent(-0.5, 1), "0.0");
+        assert_eq!(format_percent(f64::NAN, 0), "0");
+    }
+}
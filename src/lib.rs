@@ -0,0 +1,15 @@
+//! The parts of the bot that don't need Discord: reading a query, and
+//! working out the countdown that answers it. The bot itself lives in
+//! main.rs and pulls these in from here.
+
+pub mod chart;
+pub mod config;
+pub mod dates;
+pub mod format;
+pub mod markdown;
+pub mod pipeline;
+pub mod response;
+pub mod selftest;
+pub mod state;
+pub mod target;
+pub mod units;
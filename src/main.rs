@@ -1,36 +1,99 @@
-use std::{fs::File, io::prelude::*};
+// The Discord-free core is shared with the library, and imported here so
+// the rest of the bot can keep reaching it through `crate::`
+use tuesdaybot::{
+    chart, config, dates, format, markdown, pipeline, response, selftest, state, target, units,
+};
+
+mod announce;
+mod clock;
+mod commands;
+mod cooldown;
+mod events;
+mod followup;
+mod mock;
+mod offset;
+mod pages;
+mod presence;
+mod reaction;
+mod roles;
+mod scheduler;
+mod send;
+mod status;
+mod timezone;
+mod token;
+mod topic;
+mod voice;
+mod welcome;
+
+use std::{
+    collections::HashMap,
+    env,
+    fs::File,
+    io::prelude::*,
+    process,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, Weekday};
-use regex::RegexSet;
+use rand::rngs::StdRng;
 use serenity::{
-    model::{channel::Message, gateway::Ready, id::RoleId},
+    model::{
+        channel::{Message, Reaction},
+        gateway::Ready,
+        guild::Guild,
+        id::{ChannelId, GuildId, MessageId, RoleId, UserId},
+        voice::VoiceState,
+    },
     prelude::*,
-    utils::MessageBuilder,
 };
 
-const TUESDAY_GROUP_ID: u64 = 709526709187248241;
-
-const TIME_UNITS: [&str; 6] = [r"sec", r"min", r"hour", r" day|days", r"week", r"year"];
-const TIME_UNITS_PLURAL: [&str; 6] = ["seconds", "minutes", "hours", "days", "weeks", "years"];
-const TIME_MULTIPLIERS: [i64; 6] = [1, 60, 3600, 86400, 604800, 31557600];
+use announce::pick_announcement_channel;
+use config::{should_respond, Config};
+use cooldown::{
+    AnswerCache, AnswerCacheKey, AnsweredMessages, AnsweredMessagesKey, CooldownKey, Cooldowns,
+    EventCooldownKey, RecentAnswers, RecentAnswersKey, RecentReplies, RecentRepliesKey,
+    TriggerCooldownKey,
+};
+use dates::next_tuesday;
+use events::{ScheduledEvent, SCHEDULED_EVENT_CREATE};
+use markdown::spoiler;
+use pages::{Paginator, PaginatorKey};
+use pipeline::{arbitrate, classify, countdown_text, Trigger, TriggerSource};
+use presence::{role_statuses, should_suppress_ping};
+use reaction::is_reaction_trigger;
+use response::impatient_response;
+use selftest::self_test;
+use send::{
+    report_send_error, send_countdown, GlobalRateLimit, GlobalRateLimitKey, SendLimiter,
+    SendLimiterKey,
+};
+use state::{BotState, MentionMode, ReplyMode, TrackedQuery, TrackedReply};
+use timezone::{infer_utc_offset, zone_for, zone_source_for, ZoneHintKey};
+use token::clean_token;
+use topic::{TopicTracker, TopicTrackerKey};
+use units::{default_unit, load_custom_units, CustomRegexCache};
+use voice::{VoiceDebounce, VoiceDebounceKey};
 
-const DEFAULT_TIME_INDEX: usize = 2;
+const TUESDAY_GROUP_ID: u64 = 709526709187248241;
 
-const SI_UNITS: [&str; 20] = [
-    r"yocto", r"zepto", r"atto", r"femto", r"pico", r"nano", r"micro", r"milli", r"centi", r"deci",
-    r"deca", r"hecto", r"kilo", r"mega", r"giga", r"tera", r"peta", r"exa", r"zetta", r"yotta",
-];
+// Keys for the library's types in serenity's shared data. They live here
+// rather than next to the types so the library doesn't need serenity.
+pub struct ConfigKey;
+impl TypeMapKey for ConfigKey {
+    type Value = Config;
+}
 
-const SI_POWERS: [i32; 20] = [
-    -24, -21, -18, -15, -12, -9, -6, -3, -2, -1, 1, 2, 3, 6, 9, 12, 15, 18, 21, 24,
-];
+pub struct RngKey;
+impl TypeMapKey for RngKey {
+    type Value = StdRng;
+}
 
-struct CustomRegexCache {
-    time_regex: RegexSet,
-    si_regex: RegexSet,
+pub struct StateKey;
+impl TypeMapKey for StateKey {
+    type Value = BotState;
 }
 
-struct RegexKey;
+pub struct RegexKey;
 impl TypeMapKey for RegexKey {
     type Value = CustomRegexCache;
 }
@@ -40,58 +103,465 @@ struct Handler;
 impl Handler {
     fn initialize_regex(client: &Client) {
         let mut data = client.data.write();
-        let time_regex = RegexSet::new(&TIME_UNITS).expect("Error building time regexes");
-        let si_regex = RegexSet::new(&SI_UNITS).expect("Error building si regexes");
+        let custom_units = load_custom_units(
+            &data
+                .get::<ConfigKey>()
+                .expect("Expected to find config in context")
+                .custom_units,
+        );
+        data.insert::<RegexKey>(CustomRegexCache::new(custom_units));
+    }
+
+    fn initialize_state(client: &Client) {
+        let mut data = client.data.write();
+        data.insert::<StateKey>(BotState::load());
+    }
+
+    fn initialize_config(client: &Client) {
+        let mut data = client.data.write();
+        let mut config = Config::load();
+        if config.owner_id.is_none() {
+            match client.cache_and_http.http.get_current_application_info() {
+                Ok(info) => config.owner_id = Some(info.owner.id.0),
+                Err(why) => println!("Error getting application owner: {:?}", why),
+            }
+        }
+        data.insert::<RngKey>(config.rng());
+        data.insert::<SendLimiterKey>(Arc::new(SendLimiter::new(config.max_concurrent_sends)));
+        data.insert::<ConfigKey>(config);
+        data.insert::<CooldownKey>(Cooldowns::default());
+        data.insert::<TriggerCooldownKey>(Cooldowns::default());
+        data.insert::<EventCooldownKey>(Cooldowns::default());
+        data.insert::<RecentRepliesKey>(RecentReplies::default());
+        data.insert::<RecentAnswersKey>(RecentAnswers::default());
+        data.insert::<AnsweredMessagesKey>(AnsweredMessages::default());
+        data.insert::<AnswerCacheKey>(AnswerCache::default());
+        data.insert::<GlobalRateLimitKey>(GlobalRateLimit::default());
+        data.insert::<VoiceDebounceKey>(VoiceDebounce::default());
+        data.insert::<TopicTrackerKey>(TopicTracker::default());
+        data.insert::<PaginatorKey>(Paginator::default());
+        data.insert::<ZoneHintKey>(HashMap::new());
+    }
+
+    /// Answers the implicit trigger, inside spoiler tags if `hide` is set.
+    fn handle_tuesday(s: String, ctx: &Context, msg: Message, hide: bool) {
+        if !Handler::should_respond(ctx) {
+            println!("Staying quiet in channel {} this time", msg.channel_id);
+            return;
+        }
+        if Handler::on_trigger_cooldown(ctx, msg.channel_id, msg.guild_id) {
+            return;
+        }
+        if let Some(answer) = Handler::already_answered(ctx, msg.author.id, &s) {
+            // Quoting the old answer shouldn't ping anyone a second time
+            let reply = format!("I already told you: {}", answer);
+            let sent = send_countdown(
+                ctx,
+                msg.channel_id,
+                &reply,
+                MentionMode::Silent,
+                Some(TUESDAY_GROUP_ID),
+                Some((msg.id, Handler::reply_mode(ctx, msg.guild_id))),
+            );
+            if let Err(why) = sent {
+                report_send_error(&why);
+            }
+            return;
+        }
+        let query = s.clone();
+        let mut output =
+            Handler::cached_countdown(s, ctx, msg.channel_id, msg.guild_id, msg.author.id);
+        if hide {
+            output = spoiler(&output);
+        }
+        if Handler::is_repeat(ctx, msg.channel_id, &output) {
+            return;
+        }
+
+        // Sending a message can fail, due to a network error, an
+        // authentication error, or lack of permissions to post in the
+        // channel, so log to stdout when some error happens, with a
+        // description of it.
+        match Handler::send(ctx, msg.channel_id, msg.guild_id, &output, Some(msg.id)) {
+            Ok(reply) => {
+                Handler::track_reply(ctx, &reply, msg.guild_id);
+                Handler::track_query(ctx, &msg);
+                Handler::count_trigger(ctx, msg.author.id);
+                Handler::remember_answer(ctx, msg.author.id, &query, &output);
+            }
+            Err(why) => report_send_error(&why),
+        }
+    }
+
+    /// Answers "is it tuesday yet", a little more tersely each time the same
+    /// person asks before it is.
+    fn handle_impatience(ctx: &Context, msg: &Message) {
+        let now = zone_source_for(&ctx.data.read(), msg.guild_id, Some(msg.author.id))
+            .0
+            .now();
+        let output = {
+            let mut data = ctx.data.write();
+            let state = data
+                .get_mut::<StateKey>()
+                .expect("Expected to find bot state in context");
+            let guild = state.guild(msg.guild_id.map(|id| id.0));
+            let asks = state
+                .user_mut(msg.author.id.0)
+                .ask_yet(next_tuesday(now).date());
+            state.save();
+            impatient_response(asks, now, &guild)
+        };
+        if let Err(why) = Handler::send(ctx, msg.channel_id, msg.guild_id, &output, Some(msg.id)) {
+            report_send_error(&why);
+        }
+    }
+
+    /// How the guild wants the Tuesday role mentioned.
+    fn mention_mode(ctx: &Context, guild_id: Option<GuildId>) -> MentionMode {
+        let data = ctx.data.read();
+        data.get::<StateKey>()
+            .expect("Expected to find bot state in context")
+            .guild(guild_id.map(|id| id.0))
+            .mention_mode
+    }
+
+    /// Whether the guild holds back pings while most of the Tuesday role is
+    /// offline or on do not disturb, and it is right now.
+    fn role_mostly_away(ctx: &Context, guild_id: Option<GuildId>) -> bool {
+        let guild_id = match guild_id {
+            Some(guild_id) => guild_id,
+            None => return false,
+        };
+        let threshold = {
+            let data = ctx.data.read();
+            data.get::<StateKey>()
+                .expect("Expected to find bot state in context")
+                .guild(Some(guild_id.0))
+                .quiet_ping_threshold
+        };
+        let (threshold, guild) = match (threshold, guild_id.to_guild_cached(&ctx.cache)) {
+            (Some(threshold), Some(guild)) => (threshold, guild),
+            _ => return false,
+        };
+        let statuses = role_statuses(&guild.read(), RoleId(TUESDAY_GROUP_ID));
+        should_suppress_ping(&statuses, threshold)
+    }
+
+    /// Whether the guild answers the reaction trigger.
+    fn reactions_enabled(ctx: &Context, guild_id: Option<GuildId>) -> bool {
+        let data = ctx.data.read();
+        TriggerSource::Reaction.enabled(
+            &data
+                .get::<StateKey>()
+                .expect("Expected to find bot state in context")
+                .guild(guild_id.map(|id| id.0)),
+        )
+    }
+
+    /// Whether the guild wants countdowns posted as replies.
+    fn reply_mode(ctx: &Context, guild_id: Option<GuildId>) -> ReplyMode {
+        let data = ctx.data.read();
+        data.get::<StateKey>()
+            .expect("Expected to find bot state in context")
+            .guild(guild_id.map(|id| id.0))
+            .reply_mode
+    }
 
-        data.insert::<RegexKey>(CustomRegexCache {
-            time_regex: time_regex,
-            si_regex: si_regex,
+    /// Sends a countdown built by `countdown_message`, with the guild's
+    /// mention mode applied. If there's a `reply_to` message that asked for
+    /// it, the guild's reply mode is applied too.
+    fn send(
+        ctx: &Context,
+        channel_id: ChannelId,
+        guild_id: Option<GuildId>,
+        output: &str,
+        reply_to: Option<MessageId>,
+    ) -> serenity::Result<Message> {
+        let mut mode = Handler::mention_mode(ctx, guild_id);
+        if mode == MentionMode::Ping && Handler::role_mostly_away(ctx, guild_id) {
+            println!(
+                "Not pinging in guild {:?}, most of the role is away",
+                guild_id
+            );
+            mode = MentionMode::Silent;
+        }
+        let reply_to = reply_to.map(|id| (id, Handler::reply_mode(ctx, guild_id)));
+        send_countdown(
+            ctx,
+            channel_id,
+            output,
+            mode,
+            Some(TUESDAY_GROUP_ID),
+            reply_to,
+        )
+    }
+
+    /// Adds one to the user's count of countdowns set off.
+    fn count_trigger(ctx: &Context, user_id: UserId) {
+        let mut data = ctx.data.write();
+        let state = data
+            .get_mut::<StateKey>()
+            .expect("Expected to find bot state in context");
+        state.user_mut(user_id.0).triggers += 1;
+        state.save();
+    }
+
+    /// Remembers who asked, to follow up once Tuesday arrives.
+    fn track_query(ctx: &Context, msg: &Message) {
+        let mut data = ctx.data.write();
+        let zone = zone_for(&data, msg.guild_id);
+        let followup = data
+            .get::<ConfigKey>()
+            .expect("Expected to find config in context")
+            .followup_on_arrival;
+        let now = zone.now();
+        let tuesday = next_tuesday(now);
+        // Nothing to wait for if it's already Tuesday
+        if !followup || tuesday <= now {
+            return;
+        }
+        let state = data
+            .get_mut::<StateKey>()
+            .expect("Expected to find bot state in context");
+        state.track_query(TrackedQuery {
+            channel_id: msg.channel_id.0,
+            message_id: msg.id.0,
+            user_id: msg.author.id.0,
+            tuesday: zone.to_utc(tuesday),
         });
+        state.save();
     }
 
-    fn handle_tuesday(s: String, ctx: &Context, msg: Message) {
-        // First, get the local time
-        let now = Local::now().naive_local();
-
-        // Then, calculate when the next tuesday will be
-        let num_days_increment = match now.weekday() {
-            Weekday::Mon => 1,
-            Weekday::Tue => 0,
-            Weekday::Wed => 6,
-            Weekday::Thu => 5,
-            Weekday::Fri => 4,
-            Weekday::Sat => 3,
-            Weekday::Sun => 2,
+    /// Claims `message_id` for `source`, so nothing else answers it. False
+    /// if it was already answered.
+    fn claim_message(ctx: &Context, message_id: MessageId, source: TriggerSource) -> bool {
+        let claimed = ctx
+            .data
+            .write()
+            .get_mut::<AnsweredMessagesKey>()
+            .expect("Expected to find answered messages in context")
+            .claim(message_id.0);
+        if !claimed {
+            println!(
+                "Message {} was already answered, skipping {:?}",
+                message_id, source
+            );
+        }
+        claimed
+    }
+
+    /// Whether `msg` is a reply to something the bot said. The message being
+    /// replied to is looked up in the cache first, and only fetched if it
+    /// isn't there.
+    fn replies_to_us(ctx: &Context, msg: &Message, own_id: UserId) -> bool {
+        let replied = match msg
+            .message_reference
+            .as_ref()
+            .and_then(|reference| reference.message_id)
+        {
+            Some(replied) => replied,
+            None => return false,
         };
+        if let Some(replied) = ctx.cache.read().message(msg.channel_id, replied) {
+            return replied.author.id == own_id;
+        }
+        match ctx.http.get_message(msg.channel_id.0, replied.0) {
+            Ok(replied) => replied.author.id == own_id,
+            Err(why) => {
+                println!("Error fetching replied-to message: {:?}", why);
+                false
+            }
+        }
+    }
+
+    /// Whether `output` would just repeat the last reply in the channel.
+    fn is_repeat(ctx: &Context, channel_id: ChannelId, output: &str) -> bool {
+        let mut data = ctx.data.write();
+        let window = data
+            .get::<ConfigKey>()
+            .expect("Expected to find config in context")
+            .duplicate_window_secs;
+        if window == 0 {
+            return false;
+        }
+        data.get_mut::<RecentRepliesKey>()
+            .expect("Expected to find recent replies in context")
+            .is_repeat(
+                channel_id.0,
+                output,
+                Duration::from_secs(window),
+                Instant::now(),
+            )
+    }
+
+    /// What we last told `user_id` when they asked exactly `query`, if it
+    /// was recent enough to point them back at it.
+    fn already_answered(ctx: &Context, user_id: UserId, query: &str) -> Option<String> {
+        let data = ctx.data.read();
+        let window = data
+            .get::<ConfigKey>()
+            .expect("Expected to find config in context")
+            .already_answered_secs;
+        if window == 0 {
+            return None;
+        }
+        data.get::<RecentAnswersKey>()
+            .expect("Expected to find recent answers in context")
+            .already_answered(
+                user_id.0,
+                query,
+                Duration::from_secs(window),
+                Instant::now(),
+            )
+            .map(str::to_string)
+    }
+
+    /// `countdown_message` for `s`, reusing the answer to the same question
+    /// in the same channel if it was worked out within the cache window.
+    fn cached_countdown(
+        s: String,
+        ctx: &Context,
+        channel_id: ChannelId,
+        guild_id: Option<GuildId>,
+        user_id: UserId,
+    ) -> String {
+        let (ttl, zone) = {
+            let data = ctx.data.read();
+            let ttl = data
+                .get::<ConfigKey>()
+                .expect("Expected to find config in context")
+                .answer_cache_secs;
+            (
+                Duration::from_secs(ttl),
+                zone_source_for(&data, guild_id, Some(user_id)).0.name(),
+            )
+        };
+        if ttl.as_secs() == 0 {
+            return Handler::countdown_message(s, ctx, guild_id, Some(user_id));
+        }
+        let cached = ctx
+            .data
+            .read()
+            .get::<AnswerCacheKey>()
+            .expect("Expected to find answer cache in context")
+            .get(channel_id.0, &zone, &s, ttl, Instant::now())
+            .map(str::to_string);
+        if let Some(answer) = cached {
+            return answer;
+        }
+        let query = s.clone();
+        let answer = Handler::countdown_message(s, ctx, guild_id, Some(user_id));
+        ctx.data
+            .write()
+            .get_mut::<AnswerCacheKey>()
+            .expect("Expected to find answer cache in context")
+            .insert(channel_id.0, &zone, &query, &answer, ttl, Instant::now());
+        answer
+    }
+
+    fn remember_answer(ctx: &Context, user_id: UserId, query: &str, answer: &str) {
+        let mut data = ctx.data.write();
+        data.get_mut::<RecentAnswersKey>()
+            .expect("Expected to find recent answers in context")
+            .remember(user_id.0, query, answer, Instant::now());
+    }
+
+    /// Rolls the configured response chance.
+    fn should_respond(ctx: &Context) -> bool {
+        let mut data = ctx.data.write();
+        let chance = data
+            .get::<ConfigKey>()
+            .expect("Expected to find config in context")
+            .response_chance;
+        let rng = data
+            .get_mut::<RngKey>()
+            .expect("Expected to find rng in context");
+        should_respond(chance, rng)
+    }
+
+    /// Whether the implicit trigger already fired in this channel too
+    /// recently. Otherwise, starts a new cooldown.
+    fn on_trigger_cooldown(
+        ctx: &Context,
+        channel_id: ChannelId,
+        guild_id: Option<GuildId>,
+    ) -> bool {
+        let premium_tier = guild_id
+            .and_then(|id| id.to_guild_cached(&ctx.cache))
+            .map(|guild| guild.read().premium_tier.num());
+        let mut data = ctx.data.write();
+        let cooldown = data
+            .get::<ConfigKey>()
+            .expect("Expected to find config in context")
+            .trigger_cooldown(premium_tier);
+        data.get_mut::<TriggerCooldownKey>()
+            .expect("Expected to find cooldowns in context")
+            .check(channel_id.0, cooldown, Instant::now())
+            .is_some()
+    }
 
-        let tuesday: NaiveDateTime = match now.with_ordinal(now.ordinal() + num_days_increment) {
-            // Account for the year boundary, get the first tuesday
-            // next year in that case
-            None => NaiveDate::from_weekday_of_month(now.year() + 1, 1, Weekday::Tue, 1),
-            Some(t) => NaiveDate::from_yo(t.year(), t.ordinal()),
+    /// Remembers a countdown we posted, if it will need cleaning up later.
+    fn track_reply(ctx: &Context, reply: &Message, guild_id: Option<GuildId>) {
+        let mut data = ctx.data.write();
+        let zone = zone_for(&data, guild_id);
+        let cleanup = data
+            .get::<ConfigKey>()
+            .expect("Expected to find config in context")
+            .cleanup_stale_replies;
+        if !cleanup {
+            return;
         }
-        .and_hms_micro(0, 0, 0, 0);
-        // Finally get how long it will be until the start of that tuesday
-        let diff = tuesday.signed_duration_since(now);
-        let diffms = diff.num_milliseconds() as f64;
+        let state = data
+            .get_mut::<StateKey>()
+            .expect("Expected to find bot state in context");
+        state.track_reply(TrackedReply {
+            channel_id: reply.channel_id.0,
+            message_id: reply.id.0,
+            tuesday: zone.to_utc(next_tuesday(zone.now())),
+        });
+        state.save();
+    }
+
+    /// Builds the full countdown reply to the query `s`, including the role
+    /// mention when `guild_id` is the role's guild. Times are in the asking
+    /// user's timezone, when there is one.
+    fn countdown_message(
+        s: String,
+        ctx: &Context,
+        guild_id: Option<GuildId>,
+        user_id: Option<UserId>,
+    ) -> String {
+        // First, get the local time
+        let now = zone_source_for(&ctx.data.read(), guild_id, user_id).0.now();
 
         // Start constructing the output message
-        let tuesday_role_id : RoleId = TUESDAY_GROUP_ID.into();
+        let tuesday_role_id: RoleId = TUESDAY_GROUP_ID.into();
         let mut output: String = "".to_string();
-        
-        // Check that we can mention and are in the same guild
+
+        let mention_mode = Handler::mention_mode(ctx, guild_id);
+
+        // Check that we can mention and are in the same guild. A silent
+        // mention doesn't notify anyone, so it doesn't need to be mentionable.
         if let Some(role) = tuesday_role_id.to_role_cached(ctx.cache.as_ref()) {
-            if role.mentionable {
+            let usable = match mention_mode {
+                MentionMode::Ping => role.mentionable,
+                MentionMode::Silent => true,
+                MentionMode::Off => false,
+            };
+            if usable {
                 match role.find_guild(ctx.cache.as_ref()) {
-                    Ok(guild_id) => {
-                        if let Some(msg_guild_id) = msg.guild_id {
-                            if guild_id == msg_guild_id {
+                    Ok(role_guild_id) => {
+                        if let Some(msg_guild_id) = guild_id {
+                            if role_guild_id == msg_guild_id {
                                 output.push_str(format!("{} ", tuesday_role_id.mention()).as_str());
                             } else {
-                                println!("Tuesdaybot activated in guild {}, but wants to be in {}", msg_guild_id, guild_id);
+                                println!(
+                                    "Tuesdaybot activated in guild {}, but wants to be in {}",
+                                    msg_guild_id, role_guild_id
+                                );
                             }
                         }
-                    },
+                    }
                     Err(why) => {
                         println!("Error getting guild_id of TUESDAY_GROUP_ID role: {:?}", why);
                     }
@@ -99,49 +569,32 @@ impl Handler {
             }
         }
 
-        output.push_str("It is ");
-
-        let (multiplier, unit_string) = Handler::find_multiplier_from(s, &ctx);
-
-        let adjusted_diff = diffms / multiplier;
-        output.push_str(format!("{} {} until Tuesday.", adjusted_diff, unit_string).as_str());
-
-        // Sending a message can fail, due to a network error, an
-        // authentication error, or lack of permissions to post in the
-        // channel, so log to stdout when some error happens, with a
-        // description of it.
-        if let Err(why) = msg.channel_id.say(&ctx.http, output) {
-            println!("Error sending message: {:?}", why);
-        }
-    }
-
-    fn find_multiplier_from(s: String, ctx: &Context) -> (f64, String) {
-        // Load regexes from cache, find all matches in the string.
-        let data = ctx.data.read();
-        let regex_cache: &CustomRegexCache = data
+        let mut data = ctx.data.write();
+        // The rng has to be borrowed mutably alongside everything else, so
+        // take it out of the map for the duration
+        let mut rng = data
+            .remove::<RngKey>()
+            .expect("Expected to find rng in context");
+        let guild = data
+            .get::<StateKey>()
+            .expect("Expected to find bot state in context")
+            .guild(guild_id.map(|id| id.0));
+        let config = data
+            .get::<ConfigKey>()
+            .expect("Expected to find config in context");
+        let regex_cache = data
             .get::<RegexKey>()
             .expect("Expected to find cached regexes in context");
-        let time_matches = regex_cache.time_regex.matches(&s);
-        let si_matches = regex_cache.si_regex.matches(&s);
-
-        // Prioritize the longest time amounts, defaulting to hours if nothing found
-        let time_index: usize = match time_matches.iter().next_back() {
-            None => DEFAULT_TIME_INDEX,
-            Some(i) => i,
-        };
-
-        // For all powers mentioned, add it to the multiplier and also to a prefix string
-        let mut si_power = 3;
-        let mut unit_string = String::new();
-        for i in si_matches.iter() {
-            si_power += SI_POWERS[i];
-            unit_string.push_str(SI_UNITS[i]);
-        }
-        unit_string.push_str(TIME_UNITS_PLURAL[time_index]);
-
-        let multiplier = (TIME_MULTIPLIERS[time_index] as f64) * (10 as f64).powf(si_power as f64);
-
-        return (multiplier, unit_string);
+        output.push_str(&countdown_text(
+            &s,
+            now,
+            &guild,
+            config,
+            regex_cache,
+            &mut rng,
+        ));
+        data.insert::<RngKey>(rng);
+        output
     }
 }
 
@@ -158,55 +611,331 @@ impl EventHandler for Handler {
         let mut s = String::from(&msg.content);
         s.make_ascii_lowercase();
 
+        let guild = commands::guild_config(&ctx, &msg);
+        let trigger = classify(&s, &guild);
+        let own_id = ctx.cache.read().user.id;
+        let mut sources = Vec::new();
         if s.starts_with("tue!") {
-            let rest = s.split_off(4);
-            if rest.starts_with("role") {
-                let mut builder = MessageBuilder::new();
-                builder.push("Roles mentioned:\n");
-                for roleid in &msg.mention_roles {
-                    builder.push(&format!("{}\n", roleid).to_string());
-                }
-                if let Err(why) = msg.channel_id.say(&ctx.http, &builder.build()) {
-                    println!("Error sending message: {:?}", why);
-                }
+            sources.push(TriggerSource::Command);
+        }
+        if msg.mentions.iter().any(|user| user.id == own_id) {
+            sources.push(TriggerSource::Mention);
+        }
+        // Finding out who wrote the message being replied to costs a
+        // request, so only bother if replies would be answered
+        if TriggerSource::Reply.enabled(&guild) && Handler::replies_to_us(&ctx, &msg, own_id) {
+            sources.push(TriggerSource::Reply);
+        }
+        if trigger.is_some() {
+            sources.push(TriggerSource::Phrase);
+        }
+        let source = match arbitrate(&sources, &guild) {
+            Some(source) => source,
+            None => return,
+        };
+        if !Handler::claim_message(&ctx, msg.id, source) {
+            return;
+        }
+
+        let trigger = match (source, trigger) {
+            (TriggerSource::Phrase, Some(trigger)) => trigger,
+            (TriggerSource::Mention, _) | (TriggerSource::Reply, _) => Trigger::Countdown {
+                text: s,
+                hide: false,
+            },
+            _ => {
+                // Lowercasing is ASCII-only, so the prefix is the same length
+                // in the original content
+                commands::dispatch(&ctx, &msg, &msg.content[4..]);
+                return;
             }
-        } else {
-            // These repeated string searches could be optimized
-            if s.contains("tues") {
-                Handler::handle_tuesday(s, &ctx, msg);
+        };
+        if commands::in_maintenance(&ctx) {
+            println!(
+                "Ignoring trigger in channel {} during maintenance",
+                msg.channel_id
+            );
+            commands::notify_maintenance(&ctx, &msg);
+            return;
+        }
+
+        match trigger {
+            Trigger::Today => commands::dispatch(&ctx, &msg, "today"),
+            Trigger::TuesdaysLeft => commands::dispatch(&ctx, &msg, "tuesdays list"),
+            Trigger::IsItTuesday => Handler::handle_impatience(&ctx, &msg),
+            Trigger::Countdown { text, hide } => Handler::handle_tuesday(text, &ctx, msg, hide),
+        }
+    }
+
+    // Reacting with the configured emoji is a quieter way to ask for the
+    // countdown than typing out a message.
+    fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        if pages::turn_page(&ctx, &reaction) {
+            return;
+        }
+        let own_id = ctx.cache.read().user.id;
+        let trigger = {
+            let data = ctx.data.read();
+            data.get::<ConfigKey>()
+                .expect("Expected to find config in context")
+                .reaction_trigger
+                .clone()
+        };
+        if !is_reaction_trigger(&reaction, trigger.as_deref(), own_id)
+            || commands::in_maintenance(&ctx)
+            || !Handler::reactions_enabled(&ctx, reaction.guild_id)
+            || Handler::on_trigger_cooldown(&ctx, reaction.channel_id, reaction.guild_id)
+            || !Handler::claim_message(&ctx, reaction.message_id, TriggerSource::Reaction)
+        {
+            return;
+        }
+
+        let output = Handler::countdown_message(
+            String::new(),
+            &ctx,
+            reaction.guild_id,
+            Some(reaction.user_id),
+        );
+        if Handler::is_repeat(&ctx, reaction.channel_id, &output) {
+            return;
+        }
+        match Handler::send(
+            &ctx,
+            reaction.channel_id,
+            reaction.guild_id,
+            &output,
+            Some(reaction.message_id),
+        ) {
+            Ok(reply) => Handler::track_reply(&ctx, &reply, reaction.guild_id),
+            Err(why) => report_send_error(&why),
+        }
+    }
+
+    // Taking a page reaction back off turns the page too, so a list can be
+    // paged through where we can't clear people's reactions, like in DMs.
+    fn reaction_remove(&self, ctx: Context, reaction: Reaction) {
+        pages::turn_page(&ctx, &reaction);
+    }
+
+    // Called whenever someone joins, leaves, or moves between voice (and
+    // stage) channels. When a call starts in one of the configured channels,
+    // post the countdown in its paired text channel.
+    fn voice_state_update(
+        &self,
+        ctx: Context,
+        guild_id: Option<GuildId>,
+        old: Option<VoiceState>,
+        new: VoiceState,
+    ) {
+        let channel_id = match new.channel_id {
+            Some(channel_id) => channel_id,
+            None => return,
+        };
+        if commands::in_maintenance(&ctx) {
+            return;
+        }
+        let joined = old.and_then(|old| old.channel_id) != Some(channel_id);
+        let occupants = match guild_id.and_then(|id| id.to_guild_cached(&ctx.cache)) {
+            Some(guild) => guild
+                .read()
+                .voice_states
+                .values()
+                .filter(|state| state.channel_id == Some(channel_id))
+                .count(),
+            None => return,
+        };
+
+        let text_channel = {
+            let mut data = ctx.data.write();
+            let config = data
+                .get::<ConfigKey>()
+                .expect("Expected to find config in context");
+            let text_channel = match config
+                .voice_countdowns
+                .iter()
+                .find(|countdown| countdown.voice_channel == channel_id.0)
+            {
+                Some(countdown) => countdown.text_channel,
+                None => return,
+            };
+            let debounce = Duration::from_secs(config.voice_debounce_secs);
+            let announce = data
+                .get_mut::<VoiceDebounceKey>()
+                .expect("Expected to find voice debounce in context")
+                .should_announce(channel_id.0, joined, occupants, debounce, Instant::now());
+            if !announce {
+                return;
             }
+            ChannelId(text_channel)
+        };
+
+        let output = Handler::countdown_message(String::new(), &ctx, guild_id, None);
+        if let Err(why) = Handler::send(&ctx, text_channel, guild_id, &output, None) {
+            report_send_error(&why);
         }
     }
 
+    // Events this version of serenity doesn't model end up here as raw JSON.
+    // We use it to notice scheduled events created on a Tuesday.
+    fn unknown(&self, ctx: Context, name: String, raw: serde_json::Value) {
+        if name != SCHEDULED_EVENT_CREATE || commands::in_maintenance(&ctx) {
+            return;
+        }
+        let event = match ScheduledEvent::from_raw(&raw) {
+            Some(event) => event,
+            None => {
+                println!("Couldn't parse scheduled event: {}", raw);
+                return;
+            }
+        };
+
+        let (channel, output) = {
+            let mut data = ctx.data.write();
+            let guild = data
+                .get::<StateKey>()
+                .expect("Expected to find bot state in context")
+                .guild_by_id(event.guild_id);
+            let channel = match guild.event_channel {
+                Some(channel) => ChannelId(channel),
+                None => return,
+            };
+            let zone = zone_for(&data, Some(GuildId(event.guild_id)));
+            let output = match event.countdown(zone.now(), zone, &default_unit(), &guild) {
+                Some(output) => output,
+                None => return,
+            };
+            let debounce = Duration::from_secs(
+                data.get::<ConfigKey>()
+                    .expect("Expected to find config in context")
+                    .event_debounce_secs,
+            );
+            let debounced = data
+                .get_mut::<EventCooldownKey>()
+                .expect("Expected to find cooldowns in context")
+                .check(event.guild_id, debounce, Instant::now())
+                .is_some();
+            if debounced {
+                println!("Skipping comment on scheduled event {}", event.id);
+                return;
+            }
+            (channel, output)
+        };
+
+        // Fall back somewhere else in the guild if the event channel went
+        // away or we lost access to it
+        let guild = ctx.cache.read().guild(GuildId(event.guild_id));
+        let channel = match guild {
+            Some(guild) => match pick_announcement_channel(&ctx, &guild.read(), Some(channel)) {
+                Some(channel) => channel,
+                None => return,
+            },
+            None => channel,
+        };
+        if let Err(why) = channel.say(&ctx.http, output) {
+            println!("Error sending message: {:?}", why);
+        }
+    }
+
+    // Sent for every guild once we connect, and when we join a new one.
+    // Guilds carry hints about where they are, which we use to guess a
+    // timezone until an admin sets one.
+    fn guild_create(&self, ctx: Context, guild: Guild, is_new: bool) {
+        if let Some(minutes) = infer_utc_offset(&guild.region, &guild.preferred_locale) {
+            let mut data = ctx.data.write();
+            data.get_mut::<ZoneHintKey>()
+                .expect("Expected to find zone hints in context")
+                .insert(guild.id.0, minutes);
+        }
+        welcome::greet(&ctx, &guild, is_new);
+    }
+
     // Set a handler to be called on the `ready` event. This is called when a
     // shard is booted, and a READY payload is sent by Discord. This payload
     // contains data like the current user's guild Ids, current user data,
     // private channels, and more.
     //
-    // In this case, just print what the current user's username is.
-    fn ready(&self, _: Context, ready: Ready) {
+    // In this case, print what the current user's username is, and kick off
+    // the background jobs.
+    fn ready(&self, ctx: Context, ready: Ready) {
         println!("{} is connected!", ready.user.name);
+        scheduler::start(ctx);
     }
 }
 
 fn main() {
+    if env::args().any(|arg| arg == "--mock") {
+        mock::run();
+        return;
+    }
+    if env::args().any(|arg| arg == "--selftest") {
+        match self_test(&Config::load()) {
+            Ok(()) => println!("Self-test passed"),
+            Err(why) => {
+                println!("Self-test failed: {}", why);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
     println!("Attempting to load token");
     // Configure bot with token read from file
     let mut file = File::open("oauth2.tok").expect("Error opening oauth2.tok");
     let mut token = String::new();
     file.read_to_string(&mut token)
         .expect("Error reading oauth2.tok");
+    let token = match clean_token(&token) {
+        Ok(token) => token,
+        Err(why) => {
+            println!("{}", why);
+            process::exit(1);
+        }
+    };
 
     // Create a new instance of the Client, logging in as a bot. This will
     // automatically prepend your bot token with "Bot ", which is a requirement
     // by Discord for bot users.
     println!("Attempting to create client struct");
-    let mut client = Client::new(&token, Handler).expect("Err creating client");
+    let mut client = Client::new(token, Handler).expect("Err creating client");
+
+    println!("Loading config");
+    Handler::initialize_config(&client);
+    let cached_messages = client
+        .data
+        .read()
+        .get::<ConfigKey>()
+        .expect("Expected to find config in context")
+        .cached_messages;
+    client
+        .cache_and_http
+        .cache
+        .write()
+        .settings_mut()
+        .max_messages(cached_messages);
 
     // Compile and add regexes to the cache
     println!("Initializing Regexes");
     Handler::initialize_regex(&client);
 
+    println!("Loading saved state");
+    Handler::initialize_state(&client);
+
+    let failure = {
+        let data = client.data.read();
+        let config = data
+            .get::<ConfigKey>()
+            .expect("Expected to find config in context");
+        if config.self_test_on_start {
+            self_test(config).err()
+        } else {
+            None
+        }
+    };
+    if let Some(why) = failure {
+        println!("Self-test failed, not starting: {}", why);
+        process::exit(1);
+    }
+
     // Finally, start a single shard, and start listening to events.
     //
     // Shards will automatically attempt to reconnect, and will perform
@@ -0,0 +1,70 @@
+/// Removes every `||spoiler||` span from `s`. An unclosed `||` isn't a
+/// spoiler as far as Discord is concerned, so it's left alone.
+pub fn strip_spoilers(s: &str) -> String {
+    let mut visible = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("||") {
+        match rest[start + 2..].find("||") {
+            Some(len) => {
+                visible.push_str(&rest[..start]);
+                rest = &rest[start + 2 + len + 2..];
+            }
+            None => break,
+        }
+    }
+    visible.push_str(rest);
+    visible
+}
+
+/// Removes every blockquoted line from `s`: lines starting with `> `, and
+/// everything from a line starting with `>>> ` to the end, which Discord
+/// quotes as a whole.
+pub fn strip_blockquotes(s: &str) -> String {
+    let mut unquoted = Vec::new();
+    for line in s.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with(">>> ") {
+            break;
+        }
+        if !trimmed.starts_with("> ") {
+            unquoted.push(line);
+        }
+    }
+    unquoted.join("\n")
+}
+
+/// Wraps `s` in spoiler tags
+pub fn spoiler(s: &str) -> String {
+    format!("||{}||", s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_closed_spoilers() {
+        assert_eq!(strip_spoilers("see you ||tuesday|| then"), "see you  then");
+        assert_eq!(strip_spoilers("||a|| b ||c||"), " b ");
+    }
+
+    #[test]
+    fn leaves_unclosed_spoilers_alone() {
+        assert_eq!(strip_spoilers("a || tuesday"), "a || tuesday");
+        assert_eq!(strip_spoilers("||a|| || tuesday"), " || tuesday");
+    }
+
+    #[test]
+    fn strips_quoted_lines() {
+        assert_eq!(
+            strip_blockquotes("> see you tuesday\nsounds good\n  > tues?"),
+            "sounds good"
+        );
+        assert_eq!(strip_blockquotes(">not a quote"), ">not a quote");
+    }
+
+    #[test]
+    fn strips_everything_after_a_multiline_quote() {
+        assert_eq!(strip_blockquotes("hours?\n>>> tuesday\nand more"), "hours?");
+    }
+}
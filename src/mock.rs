@@ -0,0 +1,143 @@
+use std::io::{self, prelude::*};
+
+use rand::Rng;
+
+use crate::{
+    commands::today_response,
+    config::Config,
+    dates::next_tuesday,
+    markdown::spoiler,
+    pipeline::{classify, countdown_text, Trigger},
+    response::{impatient_response, tuesdays_left_response},
+    state::{BotState, GuildConfig, TimeFormat, UserState},
+    timezone::{resolve_zone, Zone},
+    units::{load_custom_units, CustomRegexCache},
+};
+
+/// Everything a reply depends on, minus Discord
+pub struct MockBot<R> {
+    pub config: Config,
+    pub guild: GuildConfig,
+    pub zone: Zone,
+    pub regex_cache: CustomRegexCache,
+    pub rng: R,
+    /// The one user typing, for anything that's counted per user
+    pub user: UserState,
+}
+
+impl<R: Rng> MockBot<R> {
+    /// What the bot would say to a message saying `line`, if anything
+    pub fn reply(&mut self, line: &str) -> Option<String> {
+        let s = line.to_ascii_lowercase();
+        if s.starts_with("tue!") {
+            return Some("(Commands need a real Discord connection.)".to_string());
+        }
+        let now = self.zone.now();
+        let reply = match classify(&s, &self.guild)? {
+            Trigger::Today => {
+                let format = self.guild.time_format.unwrap_or(TimeFormat::TwentyFourHour);
+                today_response(now, self.zone, format)
+            }
+            Trigger::TuesdaysLeft => tuesdays_left_response(now.date(), true, &self.guild),
+            Trigger::IsItTuesday => {
+                let asks = self.user.ask_yet(next_tuesday(now).date());
+                impatient_response(asks, now, &self.guild)
+            }
+            Trigger::Countdown { text, hide } => {
+                let output = countdown_text(
+                    &text,
+                    now,
+                    &self.guild,
+                    &self.config,
+                    &self.regex_cache,
+                    &mut self.rng,
+                );
+                if hide {
+                    spoiler(&output)
+                } else {
+                    output
+                }
+            }
+        };
+        Some(reply)
+    }
+
+    /// Answers each line of `input` as a message, writing replies to `output`
+    pub fn run_lines<I: BufRead, O: Write>(&mut self, input: I, mut output: O) -> io::Result<()> {
+        for line in input.lines() {
+            if let Some(reply) = self.reply(&line?) {
+                writeln!(output, "{}", reply)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `--mock`: reads messages from stdin and prints the replies, using
+/// config.json and the settings saved for DMs, without connecting to
+/// Discord.
+pub fn run() {
+    let config = Config::load();
+    let guild = BotState::load().guild(None);
+    let zone = resolve_zone(&config, &guild, None);
+    let regex_cache = CustomRegexCache::new(load_custom_units(&config.custom_units));
+    let rng = config.rng();
+    let mut bot = MockBot {
+        config,
+        guild,
+        zone,
+        regex_cache,
+        rng,
+        user: UserState::default(),
+    };
+
+    println!("Mock mode: type a message and press enter. Ctrl-D quits.");
+    let stdin = io::stdin();
+    if let Err(why) = bot.run_lines(stdin.lock(), io::stdout()) {
+        println!("Error in mock mode: {:?}", why);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn bot() -> MockBot<StdRng> {
+        MockBot {
+            config: Config::default(),
+            guild: GuildConfig::default(),
+            zone: Zone::from_minutes(0).unwrap(),
+            regex_cache: CustomRegexCache::new(Vec::new()),
+            rng: StdRng::seed_from_u64(0),
+            user: UserState::default(),
+        }
+    }
+
+    #[test]
+    fn answers_only_messages_that_trigger() {
+        let mut bot = bot();
+        assert!(bot.reply("How long until TUESDAY?").is_some());
+        assert_eq!(bot.reply("good morning"), None);
+    }
+
+    #[test]
+    fn explains_that_commands_need_discord() {
+        assert_eq!(
+            bot().reply("tue!help"),
+            Some("(Commands need a real Discord connection.)".to_string())
+        );
+    }
+
+    #[test]
+    fn writes_one_line_per_reply() {
+        let mut output = Vec::new();
+        bot()
+            .run_lines("hello\nwhat day is it\nbye\n".as_bytes(), &mut output)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().count(), 1);
+        assert!(output.starts_with("It's "), "{}", output);
+        assert!(output.trim_end().ends_with("(UTC+00:00)."), "{}", output);
+    }
+}
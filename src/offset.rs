@@ -0,0 +1,104 @@
+use chrono::Duration;
+
+use crate::{format::words_to_number, units::TIME_UNITS};
+
+/// Words that can sit between the terms of an offset without meaning anything
+const FILLER: [&str; 4] = ["and", "in", "from", "now"];
+
+/// Which of `TIME_UNITS` a word names, accepting singulars and the usual
+/// abbreviations like "hrs" or "secs"
+fn unit_index(word: &str) -> Option<usize> {
+    let word = word.trim_end_matches('s');
+    TIME_UNITS
+        .iter()
+        .position(|unit| word == unit.singular || word == unit.abbreviation)
+}
+
+/// Reads one amount, in digits like "1.5" or written out like "twenty one"
+fn parse_amount(words: &[&str]) -> Option<f64> {
+    match words {
+        [word] => match word.parse::<f64>() {
+            Ok(n) if n.is_finite() && n >= 0.0 => Some(n),
+            Ok(_) => None,
+            Err(_) => words_to_number(words).map(|n| n as f64),
+        },
+        _ => words_to_number(words).map(|n| n as f64),
+    }
+}
+
+/// Reads an offset like "3 weeks and 2 days from now" or "two hours, ten
+/// minutes" into one `Duration`, summing every amount and unit in it. Gives
+/// up on anything it can't account for, rather than guessing.
+pub fn parse_offset(s: &str) -> Option<Duration> {
+    let lowered = s.to_ascii_lowercase().replace(',', " ");
+    let mut seconds = 0.0;
+    let mut amount: Vec<&str> = Vec::new();
+    let mut terms = 0;
+    for word in lowered.split_whitespace() {
+        if amount.is_empty() && FILLER.contains(&word) {
+            continue;
+        }
+        match unit_index(word) {
+            Some(index) => {
+                seconds += parse_amount(&amount)? * TIME_UNITS[index].seconds as f64;
+                amount.clear();
+                terms += 1;
+            }
+            None => amount.push(word),
+        }
+    }
+    // A dangling amount with no unit means we misread something
+    if terms == 0 || !amount.is_empty() {
+        return None;
+    }
+    // Keeps a silly offset from overflowing chrono
+    if seconds > 1e12 {
+        return None;
+    }
+    Some(Duration::milliseconds((seconds * 1000.0).round() as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sums_every_term() {
+        assert_eq!(
+            parse_offset("3 weeks and 2 days from now"),
+            Some(Duration::days(23))
+        );
+        assert_eq!(
+            parse_offset("two hours, ten minutes"),
+            Some(Duration::minutes(130))
+        );
+        assert_eq!(parse_offset("in 1.5 hrs"), Some(Duration::minutes(90)));
+    }
+
+    #[test]
+    fn reads_written_out_amounts() {
+        assert_eq!(parse_offset("twenty one secs"), Some(Duration::seconds(21)));
+    }
+
+    #[test]
+    fn gives_up_on_anything_left_over() {
+        assert_eq!(parse_offset("3 weeks and 2"), None);
+        assert_eq!(parse_offset("a few days"), None);
+        assert_eq!(parse_offset("soon"), None);
+        assert_eq!(parse_offset(""), None);
+        assert_eq!(parse_offset("-3 days"), None);
+    }
+
+    #[test]
+    fn refuses_offsets_too_big_for_chrono() {
+        assert_eq!(parse_offset("99999999999 years"), None);
+    }
+
+    #[test]
+    fn refuses_written_out_amounts_too_big_to_count() {
+        let s = format!("{}days", "hundred ".repeat(10));
+        assert_eq!(parse_offset(&s), None);
+        assert_eq!(words_to_number(&["hundred"; 10]), None);
+        assert_eq!(words_to_number(&["hundred"; 9]), Some(10u64.pow(18)));
+    }
+}
@@ -0,0 +1,295 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use serenity::{
+    model::{
+        channel::{Reaction, ReactionType},
+        id::ChannelId,
+    },
+    prelude::*,
+};
+
+use crate::{
+    status::{chunk_lines, MESSAGE_LIMIT},
+    ConfigKey,
+};
+
+/// Reactions that turn the pages of a list. Serenity 0.8 can't send
+/// buttons or receive interactions, so these stand in for the Prev and Next
+/// buttons a newer bot would use.
+pub const PREVIOUS_PAGE: &str = "◀️";
+pub const NEXT_PAGE: &str = "▶️";
+
+/// How many pages `lines` take up at `per_page` lines each. An empty list
+/// still gets one, saying so.
+pub fn page_count(lines: usize, per_page: usize) -> usize {
+    let per_page = per_page.max(1);
+    lines.div_ceil(per_page).max(1)
+}
+
+/// The lines on `page`, counting from 0. Past the last page is empty.
+pub fn page_slice(lines: &[String], page: usize, per_page: usize) -> &[String] {
+    let per_page = per_page.max(1);
+    let start = page.saturating_mul(per_page).min(lines.len());
+    let end = start.saturating_add(per_page).min(lines.len());
+    &lines[start..end]
+}
+
+/// A long list being shown one page at a time in a single message
+pub struct PagedList {
+    title: String,
+    lines: Vec<String>,
+    per_page: usize,
+    page: usize,
+    /// When someone last turned the page, for expiring the list
+    touched: Instant,
+}
+
+impl PagedList {
+    /// The message for the current page: the title with the total count,
+    /// the page's lines, and how to get to the others
+    pub fn render(&self) -> String {
+        let pages = page_count(self.lines.len(), self.per_page);
+        let mut output = format!("{} ({} total", self.title, self.lines.len());
+        if pages > 1 {
+            output.push_str(&format!(", page {} of {}", self.page + 1, pages));
+        }
+        output.push_str("):");
+        for line in page_slice(&self.lines, self.page, self.per_page) {
+            output.push('\n');
+            output.push_str(line);
+        }
+        if pages > 1 {
+            output.push_str(&format!(
+                "\nReact {} or {} to turn the page.",
+                PREVIOUS_PAGE, NEXT_PAGE
+            ));
+        }
+        output.chars().take(MESSAGE_LIMIT).collect()
+    }
+
+    /// Moves `by` pages forward or back, staying within the list. Returns
+    /// whether the page changed.
+    fn turn(&mut self, by: isize, now: Instant) -> bool {
+        self.touched = now;
+        let last = page_count(self.lines.len(), self.per_page) - 1;
+        let page = (self.page as isize + by).max(0).min(last as isize) as usize;
+        let changed = page != self.page;
+        self.page = page;
+        changed
+    }
+}
+
+/// Every list still being paged through, keyed by the ID of the message
+/// showing it
+#[derive(Default)]
+pub struct Paginator {
+    lists: HashMap<u64, PagedList>,
+}
+
+impl Paginator {
+    /// Forgets lists nobody has touched for `ttl`
+    fn expire(&mut self, ttl: Duration, now: Instant) {
+        self.lists
+            .retain(|_, list| now.duration_since(list.touched) < ttl);
+    }
+}
+
+pub struct PaginatorKey;
+impl TypeMapKey for PaginatorKey {
+    type Value = Paginator;
+}
+
+/// Which way `emoji` turns the page, if it's one of ours
+fn direction(emoji: &ReactionType) -> Option<isize> {
+    match emoji {
+        ReactionType::Unicode(emoji) if emoji == PREVIOUS_PAGE => Some(-1),
+        ReactionType::Unicode(emoji) if emoji == NEXT_PAGE => Some(1),
+        _ => None,
+    }
+}
+
+/// Sends `lines` under `title` to `channel`. In compact mode, a list longer
+/// than a page is shown one page at a time, turned with reactions;
+/// otherwise it's split across as many messages as it takes.
+pub fn send_list(ctx: &Context, channel: ChannelId, title: &str, lines: Vec<String>) {
+    let (compact, per_page, ttl) = {
+        let data = ctx.data.read();
+        let config = data
+            .get::<ConfigKey>()
+            .expect("Expected to find config in context");
+        (
+            config.compact_lists,
+            config.list_page_size.max(1),
+            Duration::from_secs(config.list_page_ttl_secs),
+        )
+    };
+
+    if !compact || lines.len() <= per_page {
+        let mut all = vec![format!("{} ({} total):", title, lines.len())];
+        all.extend(lines);
+        for chunk in chunk_lines(&all, MESSAGE_LIMIT) {
+            if let Err(why) = channel.say(&ctx.http, &chunk) {
+                println!("Error sending message: {:?}", why);
+                break;
+            }
+        }
+        return;
+    }
+
+    let list = PagedList {
+        title: title.to_string(),
+        lines,
+        per_page,
+        page: 0,
+        touched: Instant::now(),
+    };
+    let sent = match channel.say(&ctx.http, list.render()) {
+        Ok(sent) => sent,
+        Err(why) => {
+            println!("Error sending message: {:?}", why);
+            return;
+        }
+    };
+    for emoji in &[PREVIOUS_PAGE, NEXT_PAGE] {
+        if let Err(why) = sent.react(ctx, *emoji) {
+            println!("Error adding page reaction: {:?}", why);
+        }
+    }
+    let mut data = ctx.data.write();
+    let paginator = data
+        .get_mut::<PaginatorKey>()
+        .expect("Expected to find paginator in context");
+    paginator.expire(ttl, Instant::now());
+    paginator.lists.insert(sent.id.0, list);
+}
+
+/// Turns the page of a paged list when someone adds or removes one of the
+/// page reactions on it. Either counts, since in DMs we can't take the
+/// reaction back off for them. Returns whether `reaction` was one of ours.
+pub fn turn_page(ctx: &Context, reaction: &Reaction) -> bool {
+    let by = match direction(&reaction.emoji) {
+        Some(by) => by,
+        None => return false,
+    };
+    if reaction.user_id == ctx.cache.read().user.id {
+        return false;
+    }
+    let content = {
+        let mut data = ctx.data.write();
+        let ttl = Duration::from_secs(
+            data.get::<ConfigKey>()
+                .expect("Expected to find config in context")
+                .list_page_ttl_secs,
+        );
+        let now = Instant::now();
+        let paginator = data
+            .get_mut::<PaginatorKey>()
+            .expect("Expected to find paginator in context");
+        paginator.expire(ttl, now);
+        let list = match paginator.lists.get_mut(&reaction.message_id.0) {
+            Some(list) => list,
+            None => return false,
+        };
+        if !list.turn(by, now) {
+            return true;
+        }
+        list.render()
+    };
+    let edited = reaction
+        .channel_id
+        .edit_message(&ctx.http, reaction.message_id, |m| m.content(content));
+    if let Err(why) = edited {
+        println!("Error turning the page: {:?}", why);
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(n: usize) -> Vec<String> {
+        (1..=n).map(|i| format!("line {}", i)).collect()
+    }
+
+    fn paged(n: usize, per_page: usize) -> PagedList {
+        PagedList {
+            title: "Things".to_string(),
+            lines: lines(n),
+            per_page,
+            page: 0,
+            touched: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn counts_pages() {
+        assert_eq!(page_count(0, 10), 1);
+        assert_eq!(page_count(10, 10), 1);
+        assert_eq!(page_count(11, 10), 2);
+        // A page always holds at least one line
+        assert_eq!(page_count(3, 0), 3);
+    }
+
+    #[test]
+    fn slices_out_one_page() {
+        let all = lines(5);
+        assert_eq!(page_slice(&all, 0, 2), &all[0..2]);
+        assert_eq!(page_slice(&all, 2, 2), &all[4..5]);
+        assert!(page_slice(&all, 3, 2).is_empty());
+        assert!(page_slice(&all, usize::MAX, 2).is_empty());
+    }
+
+    #[test]
+    fn stays_within_the_list_when_turning_pages() {
+        let mut list = paged(5, 2);
+        let now = Instant::now();
+        assert!(!list.turn(-1, now));
+        assert!(list.turn(1, now));
+        assert!(list.turn(5, now));
+        assert_eq!(list.page, 2);
+        assert!(!list.turn(1, now));
+    }
+
+    #[test]
+    fn renders_the_current_page() {
+        let mut list = paged(3, 2);
+        assert_eq!(
+            list.render(),
+            "Things (3 total, page 1 of 2):\nline 1\nline 2\nReact ◀️ or ▶️ to turn the page."
+        );
+        list.turn(1, Instant::now());
+        assert!(list
+            .render()
+            .starts_with("Things (3 total, page 2 of 2):\nline 3\n"));
+        // One page needs no page numbers or arrows
+        assert_eq!(paged(1, 2).render(), "Things (1 total):\nline 1");
+    }
+
+    #[test]
+    fn forgets_lists_nobody_touched() {
+        let mut paginator = Paginator::default();
+        paginator.lists.insert(1, paged(3, 2));
+        let later = Instant::now() + Duration::from_secs(120);
+        paginator.expire(Duration::from_secs(300), later);
+        assert_eq!(paginator.lists.len(), 1);
+        paginator.expire(Duration::from_secs(60), later);
+        assert!(paginator.lists.is_empty());
+    }
+
+    #[test]
+    fn turns_pages_only_with_the_arrows() {
+        assert_eq!(
+            direction(&ReactionType::Unicode(NEXT_PAGE.to_string())),
+            Some(1)
+        );
+        assert_eq!(
+            direction(&ReactionType::Unicode(PREVIOUS_PAGE.to_string())),
+            Some(-1)
+        );
+        assert_eq!(direction(&ReactionType::Unicode("🎉".to_string())), None);
+    }
+}
@@ -0,0 +1,695 @@
+use chrono::{NaiveDateTime, NaiveTime, Weekday};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    config::Config,
+    dates::{next_nth_weekday_at, next_weekday_at},
+    markdown::{strip_blockquotes, strip_spoilers},
+    response::{
+        anchored_progress_response, compute_response, compute_response_to, day_progress_response,
+    },
+    state::{GuildConfig, SpoilerPolicy},
+    target::{mentioned_weekdays, nth_tuesday, pick_weekdays, target_time},
+    units::{
+        auto_unit, find_multiplier_from, is_allowed, nearest_allowed, parse_query, random_unit,
+        worst_unit, CustomRegexCache, Unit, TIME_UNITS,
+    },
+};
+
+/// What a message that isn't a command asks the bot for
+#[derive(Debug, PartialEq)]
+pub enum Trigger {
+    /// "what day is it"
+    Today,
+    /// "how many tuesdays are left"
+    TuesdaysLeft,
+    /// "is it tuesday yet", when the guild answers it impatiently
+    IsItTuesday,
+    /// Anything else mentioning Tuesday. `text` is what to read the unit
+    /// from, and `hide` means the answer goes in spoiler tags.
+    Countdown { text: String, hide: bool },
+}
+
+/// Everything that can get the bot to answer a message, strongest first.
+/// Each message gets at most one automatic answer, from the strongest source
+/// it matches, so a `tue!` command with "tuesday" in its arguments only runs
+/// the command, and reacting to a message that was already answered does
+/// nothing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TriggerSource {
+    /// A `tue!` command
+    Command,
+    /// @mentioning the bot
+    Mention,
+    /// Replying to one of the bot's messages
+    Reply,
+    /// A phrase `classify` picks up
+    Phrase,
+    /// The configured reaction emoji
+    Reaction,
+}
+
+impl TriggerSource {
+    /// Whether the guild answers this source at all. Commands always work,
+    /// and in strict mode they're all that does.
+    pub fn enabled(self, guild: &GuildConfig) -> bool {
+        let sources = &guild.trigger_sources;
+        match self {
+            TriggerSource::Command => true,
+            _ if guild.strict_mode => false,
+            TriggerSource::Mention => sources.mention,
+            TriggerSource::Reply => sources.reply,
+            TriggerSource::Phrase => sources.phrase,
+            TriggerSource::Reaction => sources.reaction,
+        }
+    }
+}
+
+/// The source that gets to answer, out of all the ones a message matched,
+/// skipping any the guild turned off
+pub fn arbitrate(sources: &[TriggerSource], guild: &GuildConfig) -> Option<TriggerSource> {
+    sources
+        .iter()
+        .filter(|source| source.enabled(guild))
+        .min()
+        .copied()
+}
+
+/// Works out what the lowercase message `s` wants, if anything
+pub fn classify(s: &str, guild: &GuildConfig) -> Option<Trigger> {
+    if guild.strict_mode {
+        return None;
+    }
+    let unquoted;
+    let s = if guild.ignore_quotes {
+        unquoted = strip_blockquotes(s);
+        unquoted.as_str()
+    } else {
+        s
+    };
+    let visible = strip_spoilers(s);
+    let text = match guild.spoiler_policy {
+        SpoilerPolicy::Skip => visible.clone(),
+        SpoilerPolicy::Ignore | SpoilerPolicy::Hide => s.to_string(),
+    };
+
+    // These repeated string searches could be optimized
+    if text.contains("what day is it") {
+        Some(Trigger::Today)
+    } else if text.contains("tuesdays this year") || text.contains("tuesdays left") {
+        Some(Trigger::TuesdaysLeft)
+    } else if guild.impatience && text.contains("is it tuesday yet") {
+        Some(Trigger::IsItTuesday)
+    } else if text.contains("tues") {
+        let hide = guild.spoiler_policy == SpoilerPolicy::Hide && !visible.contains("tues");
+        Some(Trigger::Countdown { text, hide })
+    } else {
+        None
+    }
+}
+
+/// Spellings of the trigger word that make up a whole message on their own
+const TRIGGER_WORDS: [&str; 3] = ["tues", "tuesday", "tuesdays"];
+
+/// Whether the lowercase message `s` is just the trigger word, give or take
+/// punctuation and spacing around it, like "tuesday?!" but not "see you
+/// tuesday afternoon"
+pub fn is_trigger_only(s: &str) -> bool {
+    let word = s.trim_matches(|c: char| !c.is_alphanumeric());
+    TRIGGER_WORDS.contains(&word)
+}
+
+/// The countdown reply to the query `s` made at `now`, without the role
+/// mention. Nothing here needs Discord, so it's shared with `--mock`.
+pub fn countdown_text<R: Rng>(
+    s: &str,
+    now: NaiveDateTime,
+    guild: &GuildConfig,
+    config: &Config,
+    regex_cache: &CustomRegexCache,
+    rng: &mut R,
+) -> String {
+    let bare;
+    let guild = match guild.trigger_only_style {
+        Some(style) if is_trigger_only(s) => {
+            bare = GuildConfig {
+                style,
+                ..guild.clone()
+            };
+            &bare
+        }
+        _ => guild,
+    };
+
+    // "What percent of the day is left", when that's a sensible framing
+    if s.contains("percent") || s.contains("% of the day") {
+        if let Some(anchor) = guild.percent_anchor {
+            return anchored_progress_response(now, anchor, guild);
+        }
+        if let Some(progress) = day_progress_response(now, guild) {
+            return progress;
+        }
+    }
+
+    let (destination, unit) = resolve(s, now, guild, config, regex_cache, rng);
+    render(s, now, guild, regex_cache, &destination, unit).0
+}
+
+/// The countdown to `destination` in `unit`, held to the guild's allowed
+/// units, along with the unit it ended up in. A refusal still comes with the
+/// unit that was asked for.
+fn render(
+    s: &str,
+    now: NaiveDateTime,
+    guild: &GuildConfig,
+    regex_cache: &CustomRegexCache,
+    destination: &Destination,
+    unit: Unit,
+) -> (String, Unit) {
+    let (unit, note) = match allowed_unit(unit.clone(), guild) {
+        Ok(allowed) => allowed,
+        Err(refusal) => return (refusal, unit),
+    };
+    let mut output = match destination.ordinal {
+        Some(ordinal) => {
+            let occasion = format!("the {} {}", ordinal, guild.occasion_for(Weekday::Tue));
+            compute_response_to(
+                now,
+                destination.target,
+                Weekday::Tue,
+                &occasion,
+                &unit,
+                guild,
+            )
+        }
+        None => destination
+            .days
+            .iter()
+            .map(|&(day, at)| compute_response(now, day, at, &unit, guild))
+            .collect::<Vec<String>>()
+            .join("\n"),
+    };
+    if let Some(note) = note {
+        output.push_str(&note);
+    }
+    if guild.explain_units && destination.ordinal.is_none() {
+        if let Some(clarification) = clarification(s, &unit, regex_cache) {
+            output.push_str(&clarification);
+        }
+    }
+    (output, unit)
+}
+
+/// What a countdown query counts down to
+pub struct Destination {
+    /// When the first countdown in the answer ends
+    pub target: NaiveDateTime,
+    /// How "the 2nd Tuesday" and the like were written, when that's what
+    /// was asked for
+    pub ordinal: Option<&'static str>,
+    /// Every day the answer counts down to, soonest first, with the time on
+    /// each. Empty for "the 2nd Tuesday".
+    pub days: Vec<(Weekday, NaiveTime)>,
+}
+
+/// Where the query `s` made at `now` counts down to, and the unit it asks
+/// for, before the guild's allowed units are applied. `countdown_text` and
+/// `evaluate` both go through here, so they can't disagree about either.
+pub fn resolve<R: Rng>(
+    s: &str,
+    now: NaiveDateTime,
+    guild: &GuildConfig,
+    config: &Config,
+    regex_cache: &CustomRegexCache,
+    rng: &mut R,
+) -> (Destination, Unit) {
+    if let Some((n, ordinal, span)) = nth_tuesday(s) {
+        let at = target_time(s, &regex_cache.time_of_day, guild);
+        if let Some(target) = next_nth_weekday_at(now, n, Weekday::Tue, at) {
+            let until = target.signed_duration_since(now).num_milliseconds();
+            let mut rest = s.to_string();
+            rest.replace_range(span, "");
+            let unit = pick_unit(&rest, until, guild, config, regex_cache, rng);
+            let destination = Destination {
+                target,
+                ordinal: Some(ordinal),
+                days: Vec::new(),
+            };
+            return (destination, unit);
+        }
+    }
+
+    // Times of day are only read for Tuesday, the occasion
+    let tuesday_at = target_time(s, &regex_cache.time_of_day, guild);
+    let days: Vec<(Weekday, NaiveTime)> =
+        pick_weekdays(&mentioned_weekdays(s), now, guild.multi_day_policy)
+            .into_iter()
+            .map(|day| {
+                if day == Weekday::Tue {
+                    (day, tuesday_at)
+                } else {
+                    (day, NaiveTime::from_hms(0, 0, 0))
+                }
+            })
+            .collect();
+    let (day, at) = days[0];
+    let target = next_weekday_at(now, day, at);
+    let until = target.signed_duration_since(now).num_milliseconds();
+    let unit = pick_unit(s, until, guild, config, regex_cache, rng);
+    let destination = Destination {
+        target,
+        ordinal: None,
+        days,
+    };
+    (destination, unit)
+}
+
+/// When `s` names more than one time unit, says which one the answer went
+/// with and how to ask for another instead. Nothing when there was only one
+/// to pick, or the unit didn't come from the query at all.
+pub fn clarification(s: &str, unit: &Unit, regex_cache: &CustomRegexCache) -> Option<String> {
+    let chosen = unit.base?;
+    let mut mentioned: Vec<usize> = parse_query(s, regex_cache)
+        .times
+        .iter()
+        .map(|time| time.index)
+        .collect();
+    mentioned.sort();
+    mentioned.dedup();
+    if !mentioned.contains(&chosen) {
+        return None;
+    }
+    let other = *mentioned.iter().find(|&&index| index != chosen)?;
+    Some(format!(
+        " (I read that as {}. For {}, ask \"how many {} until Tuesday\".)",
+        TIME_UNITS[chosen].plural, TIME_UNITS[other].plural, TIME_UNITS[other].plural
+    ))
+}
+
+/// Holds `unit` to the guild's allowed units. A unit that isn't allowed is
+/// swapped for the nearest one that is, with a note saying so, or refused
+/// outright if the guild prefers.
+fn allowed_unit(unit: Unit, guild: &GuildConfig) -> Result<(Unit, Option<String>), String> {
+    if is_allowed(&unit, guild) {
+        return Ok((unit, None));
+    }
+    let refusal = format!("Sorry, I can't count in {} here.", unit.name);
+    if guild.refuse_disallowed_units {
+        return Err(refusal);
+    }
+    match nearest_allowed(&unit, guild) {
+        Some(nearest) => {
+            let note = format!(
+                " ({} aren't allowed here, so I used {}.)",
+                unit.name, nearest.name
+            );
+            Ok((nearest, Some(note)))
+        }
+        None => Err(refusal),
+    }
+}
+
+/// The unit to answer `s` in, with `until` milliseconds left
+fn pick_unit<R: Rng>(
+    s: &str,
+    until: i64,
+    guild: &GuildConfig,
+    config: &Config,
+    regex_cache: &CustomRegexCache,
+    rng: &mut R,
+) -> Unit {
+    match easter_egg_unit(s, config) {
+        Some(unit) => unit,
+        // Whatever they asked for, dad knows worse
+        None if guild.dad_mode => worst_unit(until),
+        None if s.contains("auto") => auto_unit(until),
+        None if s.contains("surprise") || s.contains("random") => random_unit(until, rng),
+        None => find_multiplier_from(
+            s,
+            regex_cache,
+            config.prefix_cap(),
+            config.simplify_si_prefixes,
+        ),
+    }
+}
+
+/// The value and unit name the countdown for `s` at `now` comes out to with
+/// default settings, after running the whole parse and compute path. `s`
+/// can be anything at all, which is what makes this useful for fuzzing.
+pub fn evaluate(s: &str, now: NaiveDateTime, regex_cache: &CustomRegexCache) -> (f64, String) {
+    let s = s.to_lowercase();
+    let guild = GuildConfig::default();
+    let config = Config::default();
+    // Seeded, so a random unit comes out the same each time
+    let mut rng = StdRng::seed_from_u64(0);
+    let (destination, unit) = resolve(&s, now, &guild, &config, regex_cache, &mut rng);
+    // Rendered too, so anything that panics while writing the reply shows up
+    let (_, unit) = render(&s, now, &guild, regex_cache, &destination, unit);
+    let until = destination
+        .target
+        .signed_duration_since(now)
+        .num_milliseconds();
+    (unit.value(until), unit.name)
+}
+
+/// Picks out the joke units, when the operator has enabled them.
+fn easter_egg_unit(s: &str, config: &Config) -> Option<Unit> {
+    if !config.easter_eggs {
+        return None;
+    }
+    if s.contains("heartbeat") && config.heartbeat_bpm > 0.0 {
+        return Some(Unit::heartbeats(config.heartbeat_bpm));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::{
+        state::{ResponseStyle, TriggerSources},
+        units::build_unit,
+    };
+
+    /// A Wednesday at noon, 132 hours before the next Tuesday
+    fn wednesday() -> NaiveDateTime {
+        NaiveDate::from_ymd(2024, 1, 3).and_hms(12, 0, 0)
+    }
+
+    fn answer(s: &str, guild: &GuildConfig, config: &Config) -> String {
+        let regex_cache = CustomRegexCache::new(Vec::new());
+        let mut rng = StdRng::seed_from_u64(0);
+        countdown_text(s, wednesday(), guild, config, &regex_cache, &mut rng)
+    }
+
+    fn strict_guild() -> GuildConfig {
+        GuildConfig {
+            strict_mode: true,
+            ..GuildConfig::default()
+        }
+    }
+
+    #[test]
+    fn counts_heartbeats_as_an_easter_egg() {
+        let guild = GuildConfig::default();
+        let config = Config {
+            easter_eggs: true,
+            ..Config::default()
+        };
+        assert_eq!(
+            answer("how many heartbeats until tuesday", &guild, &config),
+            "It is ≈ 554,400 heartbeats until Tuesday."
+        );
+        // Without easter eggs, it's just a countdown in the default unit
+        assert!(!answer(
+            "how many heartbeats until tuesday",
+            &guild,
+            &Config::default()
+        )
+        .contains("heartbeats"));
+    }
+
+    #[test]
+    fn evaluates_the_value_and_unit_of_a_countdown() {
+        let regex_cache = CustomRegexCache::new(Vec::new());
+        assert_eq!(
+            evaluate("How many HOURS until Tuesday", wednesday(), &regex_cache),
+            (132.0, "hours".to_string())
+        );
+        assert_eq!(
+            evaluate("kiloseconds until tues", wednesday(), &regex_cache),
+            (475.2, "kiloseconds".to_string())
+        );
+        let (value, _) = evaluate("days until the 2nd tuesday", wednesday(), &regex_cache);
+        assert_eq!(value, 5.5);
+    }
+
+    #[test]
+    fn dad_mode_overrides_the_unit_asked_for() {
+        let guild = GuildConfig {
+            dad_mode: true,
+            ..GuildConfig::default()
+        };
+        assert_eq!(
+            answer("how many hours until tuesday", &guild, &Config::default()),
+            "It is 475200 seconds until Tuesday."
+        );
+    }
+
+    #[test]
+    fn counts_down_to_the_nth_tuesday() {
+        let regex_cache = CustomRegexCache::new(Vec::new());
+        let mut rng = StdRng::seed_from_u64(0);
+        let (destination, unit) = resolve(
+            "seconds until the second tuesday",
+            wednesday(),
+            &GuildConfig::default(),
+            &Config::default(),
+            &regex_cache,
+            &mut rng,
+        );
+        assert_eq!(
+            destination.target,
+            NaiveDate::from_ymd(2024, 1, 9).and_hms(0, 0, 0)
+        );
+        assert_eq!(destination.ordinal, Some("second"));
+        // "second" picks the Tuesday, so it doesn't count as a unit
+        assert_eq!(unit.name, "seconds");
+        let (_, unit) = resolve(
+            "how long until the second tuesday",
+            wednesday(),
+            &GuildConfig::default(),
+            &Config::default(),
+            &regex_cache,
+            &mut rng,
+        );
+        assert_eq!(unit.name, "hours");
+    }
+
+    #[test]
+    fn can_ignore_tuesdays_in_quotes() {
+        let guild = GuildConfig {
+            ignore_quotes: true,
+            ..GuildConfig::default()
+        };
+        assert_eq!(classify("> see you tuesday\nlol", &guild), None);
+        assert!(classify("> see you tuesday\nlol", &GuildConfig::default()).is_some());
+    }
+
+    #[test]
+    fn switches_to_an_allowed_unit_and_says_so() {
+        let guild = GuildConfig {
+            allowed_units: vec!["hours".to_string()],
+            ..GuildConfig::default()
+        };
+        assert_eq!(
+            answer("how many seconds until tuesday", &guild, &Config::default()),
+            "It is 132 hours until Tuesday. (seconds aren't allowed here, so I used hours.)"
+        );
+    }
+
+    #[test]
+    fn can_refuse_disallowed_units() {
+        let guild = GuildConfig {
+            allowed_units: vec!["hours".to_string()],
+            refuse_disallowed_units: true,
+            ..GuildConfig::default()
+        };
+        assert_eq!(
+            answer("how many seconds until tuesday", &guild, &Config::default()),
+            "Sorry, I can't count in seconds here."
+        );
+    }
+
+    fn spoiler_guild(spoiler_policy: SpoilerPolicy) -> GuildConfig {
+        GuildConfig {
+            spoiler_policy,
+            ..GuildConfig::default()
+        }
+    }
+
+    #[test]
+    fn skips_tuesdays_inside_spoilers() {
+        let guild = spoiler_guild(SpoilerPolicy::Skip);
+        assert_eq!(classify("see you ||tuesday||", &guild), None);
+        assert!(classify("||hours|| until tuesday", &guild).is_some());
+    }
+
+    #[test]
+    fn answers_spoilered_tuesdays_in_spoilers() {
+        let guild = spoiler_guild(SpoilerPolicy::Hide);
+        assert_eq!(
+            classify("see you ||tuesday||", &guild),
+            Some(Trigger::Countdown {
+                text: "see you ||tuesday||".to_string(),
+                hide: true
+            })
+        );
+        assert_eq!(
+            classify("tuesday ||soon||", &guild),
+            Some(Trigger::Countdown {
+                text: "tuesday ||soon||".to_string(),
+                hide: false
+            })
+        );
+    }
+
+    #[test]
+    fn counts_spoilers_like_other_text_when_ignored() {
+        assert_eq!(
+            classify("||tuesday||", &spoiler_guild(SpoilerPolicy::Ignore)),
+            Some(Trigger::Countdown {
+                text: "||tuesday||".to_string(),
+                hide: false
+            })
+        );
+    }
+
+    #[test]
+    fn the_strongest_source_answers() {
+        let guild = GuildConfig::default();
+        let sources = [TriggerSource::Phrase, TriggerSource::Command];
+        assert_eq!(arbitrate(&sources, &guild), Some(TriggerSource::Command));
+        assert_eq!(arbitrate(&[], &guild), None);
+    }
+
+    #[test]
+    fn clarifies_which_of_several_units_it_used() {
+        let regex_cache = CustomRegexCache::new(Vec::new());
+        let hours = build_unit(2, &[]);
+        assert_eq!(
+            clarification("hours or minutes until tuesday", &hours, &regex_cache).as_deref(),
+            Some(" (I read that as hours. For minutes, ask \"how many minutes until Tuesday\".)")
+        );
+        // One unit named, nothing to clarify
+        assert_eq!(
+            clarification("hours until tuesday", &hours, &regex_cache),
+            None
+        );
+        // The answer wasn't in any unit the query named
+        let days = build_unit(3, &[]);
+        assert_eq!(
+            clarification("hours or minutes until tuesday", &days, &regex_cache),
+            None
+        );
+    }
+
+    #[test]
+    fn only_explains_units_when_asked() {
+        let explaining = GuildConfig {
+            explain_units: true,
+            ..GuildConfig::default()
+        };
+        let s = "how many hours or minutes until tuesday";
+        assert!(answer(s, &explaining, &Config::default()).contains("(I read that as"));
+        assert!(!answer(s, &GuildConfig::default(), &Config::default()).contains("(I read that as"));
+        assert!(!answer(
+            "how many hours until tuesday",
+            &explaining,
+            &Config::default()
+        )
+        .contains("(I read that as"));
+    }
+
+    #[test]
+    fn only_answers_impatiently_when_asked_to() {
+        let impatient = GuildConfig {
+            impatience: true,
+            ..GuildConfig::default()
+        };
+        assert_eq!(
+            classify("is it tuesday yet", &impatient),
+            Some(Trigger::IsItTuesday)
+        );
+        assert!(matches!(
+            classify("is it tuesday yet", &GuildConfig::default()),
+            Some(Trigger::Countdown { .. })
+        ));
+    }
+
+    #[test]
+    fn spots_messages_that_are_just_the_trigger() {
+        assert!(is_trigger_only("tuesday"));
+        assert!(is_trigger_only("  tues?!"));
+        assert!(is_trigger_only("**tuesdays**"));
+        assert!(!is_trigger_only("see you tuesday afternoon"));
+        assert!(!is_trigger_only("tuesday tuesday"));
+        assert!(!is_trigger_only(""));
+    }
+
+    #[test]
+    fn answers_a_bare_trigger_in_its_own_style() {
+        let guild = GuildConfig {
+            trigger_only_style: Some(ResponseStyle::Eta),
+            ..GuildConfig::default()
+        };
+        assert!(answer("tuesday?", &guild, &Config::default()).starts_with("🚏"));
+        assert_eq!(
+            answer("hours until tuesday", &guild, &Config::default()),
+            "It is 132 hours until Tuesday."
+        );
+    }
+
+    #[test]
+    fn skips_sources_the_guild_turned_off() {
+        let mut guild = GuildConfig::default();
+        guild.trigger_sources.phrase = false;
+        let sources = [TriggerSource::Phrase, TriggerSource::Reaction];
+        assert_eq!(arbitrate(&sources, &guild), Some(TriggerSource::Reaction));
+        assert_eq!(arbitrate(&[TriggerSource::Phrase], &guild), None);
+    }
+
+    #[test]
+    fn mentions_and_replies_are_off_by_default() {
+        let guild = GuildConfig::default();
+        assert_eq!(arbitrate(&[TriggerSource::Mention], &guild), None);
+        assert_eq!(arbitrate(&[TriggerSource::Reply], &guild), None);
+    }
+
+    #[test]
+    fn commands_cannot_be_turned_off() {
+        let guild = GuildConfig {
+            trigger_sources: TriggerSources {
+                phrase: false,
+                mention: false,
+                reply: false,
+                reaction: false,
+            },
+            ..GuildConfig::default()
+        };
+        assert_eq!(
+            arbitrate(&[TriggerSource::Command], &guild),
+            Some(TriggerSource::Command)
+        );
+    }
+
+    #[test]
+    fn strict_mode_ignores_phrases() {
+        assert_eq!(classify("see you tuesday", &strict_guild()), None);
+        assert_eq!(classify("what day is it", &strict_guild()), None);
+    }
+
+    #[test]
+    fn strict_mode_still_answers_commands() {
+        let guild = strict_guild();
+        let sources = [TriggerSource::Command, TriggerSource::Phrase];
+        assert_eq!(arbitrate(&sources, &guild), Some(TriggerSource::Command));
+        assert_eq!(arbitrate(&[TriggerSource::Phrase], &guild), None);
+    }
+
+    #[test]
+    fn strict_mode_wins_over_other_trigger_sources() {
+        let mut guild = strict_guild();
+        guild.trigger_sources.mention = true;
+        guild.trigger_sources.reply = true;
+        for source in [
+            TriggerSource::Mention,
+            TriggerSource::Reply,
+            TriggerSource::Reaction,
+        ] {
+            assert_eq!(arbitrate(&[source], &guild), None);
+        }
+    }
+}
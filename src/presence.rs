@@ -0,0 +1,66 @@
+use serenity::model::{guild::Guild, id::RoleId, user::OnlineStatus};
+
+/// Whether someone with `status` is around to see a ping. Members we have no
+/// presence for are offline, since Discord only sends presences for members
+/// who aren't.
+fn is_available(status: Option<OnlineStatus>) -> bool {
+    matches!(
+        status,
+        Some(OnlineStatus::Online) | Some(OnlineStatus::Idle)
+    )
+}
+
+/// Whether pinging members with these `statuses` would mostly disturb
+/// people who are busy or away: fewer than `threshold` (0 to 1) of them are
+/// online. Without any members to go on, pings aren't held back.
+pub fn should_suppress_ping(statuses: &[Option<OnlineStatus>], threshold: f64) -> bool {
+    if statuses.is_empty() {
+        return false;
+    }
+    let available = statuses
+        .iter()
+        .filter(|&&status| is_available(status))
+        .count();
+    (available as f64 / statuses.len() as f64) < threshold
+}
+
+/// The status of every cached member of `guild` who has `role`
+pub fn role_statuses(guild: &Guild, role: RoleId) -> Vec<Option<OnlineStatus>> {
+    guild
+        .members
+        .iter()
+        .filter(|(_, member)| member.roles.contains(&role))
+        .map(|(id, _)| guild.presences.get(id).map(|presence| presence.status))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_idle_members_as_around() {
+        assert!(is_available(Some(OnlineStatus::Online)));
+        assert!(is_available(Some(OnlineStatus::Idle)));
+        assert!(!is_available(Some(OnlineStatus::DoNotDisturb)));
+        assert!(!is_available(Some(OnlineStatus::Offline)));
+        assert!(!is_available(None));
+    }
+
+    #[test]
+    fn holds_back_pings_when_most_of_the_role_is_away() {
+        let statuses = [
+            Some(OnlineStatus::Online),
+            Some(OnlineStatus::DoNotDisturb),
+            None,
+            None,
+        ];
+        assert!(should_suppress_ping(&statuses, 0.5));
+        assert!(!should_suppress_ping(&statuses, 0.25));
+    }
+
+    #[test]
+    fn pings_a_role_nobody_has() {
+        assert!(!should_suppress_ping(&[], 1.0));
+    }
+}
@@ -0,0 +1,64 @@
+use serenity::model::{
+    channel::{Reaction, ReactionType},
+    id::UserId,
+};
+
+/// Whether `reaction` asks for the countdown: it has to be the configured
+/// `trigger` emoji, and not one the bot (`own_id`) added itself.
+pub fn is_reaction_trigger(reaction: &Reaction, trigger: Option<&str>, own_id: UserId) -> bool {
+    let trigger = match trigger {
+        Some(trigger) => trigger,
+        None => return false,
+    };
+    if reaction.user_id == own_id {
+        return false;
+    }
+    match &reaction.emoji {
+        ReactionType::Unicode(emoji) => emoji == trigger,
+        // Custom emoji can be configured as `name:id`, like they're written
+        // in messages without the angle brackets
+        ReactionType::Custom { id, name, .. } => name
+            .as_ref()
+            .is_some_and(|name| format!("{}:{}", name, id) == trigger),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOT: UserId = UserId(1);
+
+    /// A reaction from user 2, parsed like the gateway sends it
+    fn reaction(emoji: serde_json::Value) -> Reaction {
+        serde_json::from_value(serde_json::json!({
+            "channel_id": "10",
+            "message_id": "11",
+            "user_id": "2",
+            "emoji": emoji,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn matches_the_configured_unicode_emoji() {
+        let reaction = reaction(serde_json::json!({ "id": null, "name": "📅" }));
+        assert!(is_reaction_trigger(&reaction, Some("📅"), BOT));
+        assert!(!is_reaction_trigger(&reaction, Some("🗓️"), BOT));
+        assert!(!is_reaction_trigger(&reaction, None, BOT));
+    }
+
+    #[test]
+    fn matches_custom_emoji_by_name_and_id() {
+        let reaction = reaction(serde_json::json!({ "id": "42", "name": "tuesday" }));
+        assert!(is_reaction_trigger(&reaction, Some("tuesday:42"), BOT));
+        assert!(!is_reaction_trigger(&reaction, Some("tuesday"), BOT));
+    }
+
+    #[test]
+    fn ignores_the_bots_own_reactions() {
+        let reaction = reaction(serde_json::json!({ "id": null, "name": "📅" }));
+        assert!(!is_reaction_trigger(&reaction, Some("📅"), UserId(2)));
+    }
+}
@@ -0,0 +1,884 @@
+use std::fmt::Write;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+
+use crate::{
+    chart::bar_chart,
+    dates::{
+        days_until_weekday, fraction_of_day, next_tuesday, next_weekday_at, previous_weekday_at,
+        round_datetime, round_to_grid, tuesdays_left_in_year,
+    },
+    format::{format_percent, format_timer},
+    state::{GuildConfig, PercentAnchor, ResponseStyle, SmallValueStyle},
+    units::Unit,
+};
+
+/// Most dates `tuesdays_left_response` will list before summarizing the rest
+const MAX_LISTED_DATES: usize = 20;
+
+/// Builds the countdown sentence for a query made at `now`, counting down to
+/// `at` on the next `day`, not including any role mention.
+pub fn compute_response(
+    now: NaiveDateTime,
+    day: Weekday,
+    at: NaiveTime,
+    unit: &Unit,
+    guild: &GuildConfig,
+) -> String {
+    let now = guild.frozen_now(now);
+    let occasion = guild.occasion_for(day);
+    compute_response_to(
+        now,
+        next_weekday_at(now, day, at),
+        day,
+        &occasion,
+        unit,
+        guild,
+    )
+}
+
+/// Like `compute_response`, but counting down to `target`, a `day` called
+/// `occasion` in the reply. This is for targets further out than the next
+/// `day`, like "the 2nd Tuesday".
+pub fn compute_response_to(
+    now: NaiveDateTime,
+    target: NaiveDateTime,
+    day: Weekday,
+    occasion: &str,
+    unit: &Unit,
+    guild: &GuildConfig,
+) -> String {
+    let now = guild.frozen_now(now);
+    let body = countdown_body(now, target, day, occasion, unit, guild);
+    match &guild.proximity {
+        Some(proximity) => {
+            let remaining = target.signed_duration_since(now);
+            format!("{} {}", proximity.emoji(remaining), body)
+        }
+        None => body,
+    }
+}
+
+fn countdown_body(
+    now: NaiveDateTime,
+    target: NaiveDateTime,
+    day: Weekday,
+    occasion: &str,
+    unit: &Unit,
+    guild: &GuildConfig,
+) -> String {
+    let (now, tuesday) = match guild.granularity {
+        Some(granularity) => (
+            round_datetime(now, granularity.duration()),
+            round_datetime(target, granularity.duration()),
+        ),
+        None => (now, target),
+    };
+    let until = tuesday.signed_duration_since(now).num_milliseconds();
+    let holiday = until > 0 && guild.is_holiday(target.date());
+    let this_week = until < Duration::weeks(1).num_milliseconds();
+
+    if until <= 0 && !guild.show_elapsed {
+        return format!("It's {}! 🎉", occasion);
+    }
+    let dated;
+    let occasion = match &guild.date_format {
+        Some(format) if until > 0 => {
+            dated = with_date(occasion, target, format);
+            dated.as_str()
+        }
+        _ => occasion,
+    };
+    // Time spent on vacation doesn't count, but it's still not Tuesday yet
+    let until = match guild.vacation {
+        Some(vacation) if until > 0 => {
+            (until - vacation.overlap(now, tuesday).num_milliseconds()).max(0)
+        }
+        _ => until,
+    };
+    match guild.style {
+        ResponseStyle::Eta => return eta_response(until, occasion, guild),
+        ResponseStyle::Chart if until > 0 => {
+            return format!(
+                "Here's what's left until {}:\n{}",
+                occasion,
+                bar_chart(until, guild.chart_width)
+            );
+        }
+        ResponseStyle::Chart | ResponseStyle::Sentence => {}
+    }
+    // Close enough that whatever unit was asked for, tenths of a second are
+    // what people actually want to see. Rounded before comparing, so a value
+    // that rounds up to the threshold isn't shown as "60.0 seconds".
+    let seconds = (until as f64 / 100.0).round() / 10.0;
+    if until > 0 && seconds < guild.final_countdown_secs {
+        return format!("It is {:.1} seconds until {}.", seconds, occasion);
+    }
+
+    if guild.whole_weeks {
+        if let Some(phrase) = whole_weeks_response(until, occasion, guild) {
+            return phrase;
+        }
+    }
+
+    let mut output = countdown_sentence(now, day, tuesday.time(), until, occasion, unit, guild);
+    if guild.small_values == SmallValueStyle::Note && guild.is_small(unit.value(until)) {
+        output.push_str(" (That's a very small number.)");
+    }
+    if let Some(minutes) = guild.grid_minutes.filter(|&minutes| minutes > 0) {
+        output.push_str(&format!(
+            " (Rounded to the nearest {}.)",
+            describe_grid(minutes)
+        ));
+    }
+    match unit.ignored_prefixes {
+        0 => {}
+        1 => output.push_str(" (I ignored 1 extra SI prefix.)"),
+        n => output.push_str(&format!(" (I ignored {} extra SI prefixes.)", n)),
+    }
+    if holiday && this_week {
+        output.push_str(&format!(
+            " ({} is a holiday this week! 🎉)",
+            guild.occasion_for(day)
+        ));
+    } else if holiday {
+        output.push_str(" (It's a holiday, too! 🎉)");
+    }
+    output
+}
+
+/// `occasion` followed by the date of `target` in `format`, like "Tuesday
+/// (Dec 3)"
+pub fn with_date(occasion: &str, target: NaiveDateTime, format: &str) -> String {
+    // A format saved before `tue!showdate` checked for timezones would make
+    // `format!` panic, so a date that can't be written is left off instead
+    let mut date = String::new();
+    if write!(date, "{}", target.format(format)).is_err() {
+        return occasion.to_string();
+    }
+    format!("{} ({})", occasion, date)
+}
+
+/// "It is exactly 2 weeks until Tuesday.", but only when the `until`
+/// milliseconds left, after snapping to the guild's grid, are a whole number
+/// of weeks. Even a millisecond off doesn't count.
+pub fn whole_weeks_response(until: i64, occasion: &str, guild: &GuildConfig) -> Option<String> {
+    let until = match guild.grid() {
+        Some(grid) => round_to_grid(Duration::milliseconds(until), grid).num_milliseconds(),
+        None => until,
+    };
+    let week = Duration::weeks(1).num_milliseconds();
+    if until <= 0 || until % week != 0 {
+        return None;
+    }
+    Some(match until / week {
+        1 => format!("It is exactly 1 week until {}.", occasion),
+        weeks => format!("It is exactly {} weeks until {}.", weeks, occasion),
+    })
+}
+
+/// The answer to "is it tuesday yet" at `now`, for the `asks`th time someone
+/// asked while waiting for this Tuesday. The more they ask, the less there
+/// is to say.
+pub fn impatient_response(asks: u32, now: NaiveDateTime, guild: &GuildConfig) -> String {
+    let occasion = guild.occasion_name();
+    if next_tuesday(now) <= now {
+        return format!("Yes! It's {}! 🎉", occasion);
+    }
+    match asks {
+        0 | 1 => "No.".to_string(),
+        2 => "Still no.".to_string(),
+        3 => format!("No. It's still not {}.", occasion),
+        _ => "I'll tell you the moment it is.".to_string(),
+    }
+}
+
+/// "We're 83% of the way through the day before Tuesday", which only makes
+/// sense when Tuesday is today or tomorrow.
+pub fn day_progress_response(now: NaiveDateTime, guild: &GuildConfig) -> Option<String> {
+    let occasion = guild.occasion_name();
+    // Rounded down, so the last minutes of a day don't claim 100%
+    let percent = format_percent(fraction_of_day(now), guild.percent_decimals);
+    match days_until_weekday(now.weekday(), Weekday::Tue) {
+        0 => Some(format!(
+            "We're {}% of the way through {}.",
+            percent, occasion
+        )),
+        1 => Some(format!(
+            "We're {}% of the way through the day before {}.",
+            percent, occasion
+        )),
+        _ => None,
+    }
+}
+
+/// The time left until `target` in whole seconds and milliseconds, straight
+/// from the `Duration` with no units, rounding, or number styles in the way
+pub fn exact_response(now: NaiveDateTime, target: NaiveDateTime, occasion: &str) -> String {
+    let ms = target.signed_duration_since(now).num_milliseconds();
+    if ms <= 0 {
+        return format!("It's {}! 🎉", occasion);
+    }
+    format!(
+        "It is exactly {} seconds and {} milliseconds until {} ({} ms).",
+        ms / 1000,
+        ms % 1000,
+        occasion,
+        ms
+    )
+}
+
+/// How far `now` is between `anchor`'s start and the next Tuesday, like
+/// "We're 43% of the way from last Tuesday to Tuesday."
+pub fn anchored_progress_response(
+    now: NaiveDateTime,
+    anchor: PercentAnchor,
+    guild: &GuildConfig,
+) -> String {
+    let occasion = guild.occasion_name();
+    let end = next_tuesday(now);
+    if end <= now {
+        return format!("It's {}! 🎉", occasion);
+    }
+    let start = match anchor.start(now) {
+        Some(start) => start,
+        None => return format!("It isn't {} yet.", anchor.describe()),
+    };
+    let total = end.signed_duration_since(start).num_milliseconds();
+    let done = now.signed_duration_since(start).num_milliseconds();
+    // Rounded down, so we never claim 100% before it's actually Tuesday
+    let percent = format_percent(done as f64 / total as f64, guild.percent_decimals);
+    format!(
+        "We're {}% of the way from {} to {}.",
+        percent,
+        anchor.describe(),
+        occasion
+    )
+}
+
+/// The transit board style countdown, ignoring units since a board wouldn't
+/// show them either
+pub fn eta_response(until: i64, occasion: &str, guild: &GuildConfig) -> String {
+    if until <= 0 {
+        return format!("🚏 {} — arrived", occasion);
+    }
+    let until = match guild.grid() {
+        Some(grid) => round_to_grid(Duration::milliseconds(until), grid).num_milliseconds(),
+        None => until,
+    };
+    format!("🚏 {} — ETA {}", occasion, format_timer(until))
+}
+
+/// Names a grid size for the rounding note, like "15 minutes" or "day"
+pub fn describe_grid(minutes: u32) -> String {
+    let (count, name) = if minutes.is_multiple_of(1440) {
+        (minutes / 1440, "day")
+    } else if minutes.is_multiple_of(60) {
+        (minutes / 60, "hour")
+    } else {
+        (minutes, "minute")
+    };
+    if count == 1 {
+        name.to_string()
+    } else {
+        format!("{} {}s", count, name)
+    }
+}
+
+/// The plain countdown sentence, or with `show_elapsed`, also how long it's
+/// been since the last `day` began at `at`.
+fn countdown_sentence(
+    now: NaiveDateTime,
+    day: Weekday,
+    at: NaiveTime,
+    until: i64,
+    occasion: &str,
+    unit: &Unit,
+    guild: &GuildConfig,
+) -> String {
+    let snap = |ms: i64| match guild.grid() {
+        Some(grid) => round_to_grid(Duration::milliseconds(ms), grid).num_milliseconds(),
+        None => ms,
+    };
+    let until = snap(until);
+
+    if !guild.show_elapsed {
+        return format!("It is {} until {}.", unit.render(until, guild), occasion);
+    }
+
+    let since = now
+        .signed_duration_since(previous_weekday_at(now, day, at))
+        .num_milliseconds();
+    let since = snap(since);
+    if until <= 0 {
+        // It's that day right now, so there's no "next one" to count down to
+        format!(
+            "It's {}! It started {} ago.",
+            occasion,
+            unit.render(since, guild)
+        )
+    } else {
+        format!(
+            "It's been {} since {}; {} until the next one.",
+            unit.render(since, guild),
+            occasion,
+            unit.render(until, guild)
+        )
+    }
+}
+
+/// Answers "how many tuesdays are left this year", optionally listing them.
+pub fn tuesdays_left_response(today: NaiveDate, list: bool, guild: &GuildConfig) -> String {
+    let tuesdays = tuesdays_left_in_year(today);
+    let occasion = guild.occasion_name();
+    let mut output = match tuesdays.len() {
+        0 => format!("There are no {}s left this year.", occasion),
+        1 => format!("There is 1 {} left this year.", occasion),
+        n => format!("There are {} {}s left this year.", n, occasion),
+    };
+    if list && !tuesdays.is_empty() {
+        let listed: Vec<String> = tuesdays
+            .iter()
+            .take(MAX_LISTED_DATES)
+            .map(|date| date.format("%b %-d").to_string())
+            .collect();
+        output.push_str(&format!(" {}", listed.join(", ")));
+        if tuesdays.len() > MAX_LISTED_DATES {
+            output.push_str(&format!(", and {} more", tuesdays.len() - MAX_LISTED_DATES));
+        }
+        output.push('.');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        state::{Granularity, PercentAnchor, Proximity, Vacation},
+        units::build_unit,
+    };
+
+    /// Hours, which most countdowns in these tests are answered in
+    const HOURS: usize = 2;
+    const SECONDS: usize = 0;
+    const GIGA: usize = 14;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        // January 2024 starts on a Monday, so the 2nd is a Tuesday
+        NaiveDate::from_ymd(2024, 1, day).and_hms(hour, 0, 0)
+    }
+
+    fn midnight() -> NaiveTime {
+        NaiveTime::from_hms(0, 0, 0)
+    }
+
+    fn elapsed_guild() -> GuildConfig {
+        GuildConfig {
+            show_elapsed: true,
+            ..GuildConfig::default()
+        }
+    }
+
+    fn hours_response(now: NaiveDateTime, at: NaiveTime, guild: &GuildConfig) -> String {
+        compute_response(now, Weekday::Tue, at, &build_unit(HOURS, &[]), guild)
+    }
+
+    #[test]
+    fn shows_elapsed_mid_week() {
+        assert_eq!(
+            hours_response(at(3, 12), midnight(), &elapsed_guild()),
+            "It's been 36 hours since Tuesday; 132 hours until the next one."
+        );
+    }
+
+    #[test]
+    fn shows_elapsed_on_monday() {
+        assert_eq!(
+            hours_response(at(1, 12), midnight(), &elapsed_guild()),
+            "It's been 156 hours since Tuesday; 12 hours until the next one."
+        );
+    }
+
+    #[test]
+    fn shows_elapsed_on_tuesday() {
+        assert_eq!(
+            hours_response(at(2, 12), midnight(), &elapsed_guild()),
+            "It's Tuesday! It started 12 hours ago."
+        );
+    }
+
+    #[test]
+    fn measures_elapsed_from_the_occasion_time() {
+        let guild = elapsed_guild();
+        let six = NaiveTime::from_hms(18, 0, 0);
+        assert_eq!(
+            hours_response(at(2, 20), six, &guild),
+            "It's Tuesday! It started 2 hours ago."
+        );
+        // Before it starts on Tuesday, the last one was a week ago
+        assert_eq!(
+            hours_response(at(2, 10), six, &guild),
+            "It's been 160 hours since Tuesday; 8 hours until the next one."
+        );
+    }
+
+    #[test]
+    fn counts_down_to_the_occasion_name() {
+        let guild = GuildConfig {
+            occasion: Some("Taco Tuesday".to_string()),
+            ..GuildConfig::default()
+        };
+        assert_eq!(
+            hours_response(at(3, 12), midnight(), &guild),
+            "It is 132 hours until Taco Tuesday."
+        );
+    }
+
+    fn gigaseconds_response(guild: &GuildConfig) -> String {
+        let unit = build_unit(SECONDS, &[GIGA]);
+        compute_response(at(3, 12), Weekday::Tue, midnight(), &unit, guild)
+    }
+
+    fn small_values(small_values: SmallValueStyle) -> GuildConfig {
+        GuildConfig {
+            small_values,
+            ..GuildConfig::default()
+        }
+    }
+
+    #[test]
+    fn notes_very_small_values() {
+        assert_eq!(
+            gigaseconds_response(&small_values(SmallValueStyle::Note)),
+            "It is 0.0004752 gigaseconds until Tuesday. (That's a very small number.)"
+        );
+    }
+
+    #[test]
+    fn writes_very_small_values_in_scientific_notation() {
+        assert_eq!(
+            gigaseconds_response(&small_values(SmallValueStyle::Scientific)),
+            "It is 4.752e-4 gigaseconds until Tuesday."
+        );
+    }
+
+    #[test]
+    fn can_leave_very_small_values_alone() {
+        assert_eq!(
+            gigaseconds_response(&small_values(SmallValueStyle::Plain)),
+            "It is 0.0004752 gigaseconds until Tuesday."
+        );
+    }
+
+    #[test]
+    fn counts_and_lists_tuesdays_left() {
+        let guild = GuildConfig::default();
+        let today = NaiveDate::from_ymd(2024, 12, 20);
+        assert_eq!(
+            tuesdays_left_response(today, false, &guild),
+            "There are 2 Tuesdays left this year."
+        );
+        assert_eq!(
+            tuesdays_left_response(today, true, &guild),
+            "There are 2 Tuesdays left this year. Dec 24, Dec 31."
+        );
+        assert_eq!(
+            tuesdays_left_response(NaiveDate::from_ymd(2023, 12, 27), true, &guild),
+            "There are no Tuesdays left this year."
+        );
+    }
+
+    #[test]
+    fn summarizes_long_lists_of_tuesdays() {
+        let output = tuesdays_left_response(
+            NaiveDate::from_ymd(2024, 1, 1),
+            true,
+            &GuildConfig::default(),
+        );
+        assert!(output.starts_with("There are 53 Tuesdays left this year. Jan 2, Jan 9,"));
+        assert!(output.ends_with(", and 33 more."));
+    }
+
+    /// `ms` milliseconds before Tuesday, Jan 2
+    fn before_tuesday(ms: i64) -> NaiveDateTime {
+        at(2, 0) - Duration::milliseconds(ms)
+    }
+
+    #[test]
+    fn shows_tenths_of_a_second_in_the_final_countdown() {
+        let guild = GuildConfig::default();
+        assert_eq!(
+            hours_response(before_tuesday(12_345), midnight(), &guild),
+            "It is 12.3 seconds until Tuesday."
+        );
+        assert_eq!(
+            hours_response(before_tuesday(100), midnight(), &guild),
+            "It is 0.1 seconds until Tuesday."
+        );
+    }
+
+    #[test]
+    fn final_countdown_starts_at_the_threshold() {
+        let guild = GuildConfig::default();
+        assert_eq!(
+            hours_response(before_tuesday(59_949), midnight(), &guild),
+            "It is 59.9 seconds until Tuesday."
+        );
+        // Rounds up to a whole minute, so it's past the final countdown
+        assert_eq!(
+            hours_response(before_tuesday(59_999), midnight(), &guild),
+            "It is 0.01666638888888889 hours until Tuesday."
+        );
+        assert_eq!(
+            hours_response(before_tuesday(60_000), midnight(), &guild),
+            "It is 0.016666666666666666 hours until Tuesday."
+        );
+    }
+
+    #[test]
+    fn final_countdown_ends_in_celebration() {
+        assert_eq!(
+            hours_response(at(2, 0), midnight(), &GuildConfig::default()),
+            "It's Tuesday! 🎉"
+        );
+    }
+
+    fn vacation_guild(start: u32, end: u32) -> GuildConfig {
+        GuildConfig {
+            vacation: Some(Vacation {
+                start: NaiveDate::from_ymd(2024, 1, start),
+                end: NaiveDate::from_ymd(2024, 1, end),
+            }),
+            ..GuildConfig::default()
+        }
+    }
+
+    #[test]
+    fn leaves_vacation_days_out_of_the_countdown() {
+        // Thursday and Friday off
+        assert_eq!(
+            hours_response(at(3, 12), midnight(), &vacation_guild(4, 5)),
+            "It is 84 hours until Tuesday."
+        );
+        // Already on vacation, so only the rest of it is skipped
+        assert_eq!(
+            hours_response(at(4, 12), midnight(), &vacation_guild(4, 5)),
+            "It is 72 hours until Tuesday."
+        );
+    }
+
+    #[test]
+    fn ignores_vacations_outside_the_countdown() {
+        assert_eq!(
+            hours_response(at(3, 12), midnight(), &vacation_guild(10, 12)),
+            "It is 132 hours until Tuesday."
+        );
+    }
+
+    #[test]
+    fn rounds_to_the_grid_and_says_so() {
+        let guild = GuildConfig {
+            grid_minutes: Some(60),
+            ..GuildConfig::default()
+        };
+        let now = at(3, 12) - Duration::minutes(10);
+        assert_eq!(
+            hours_response(now, midnight(), &guild),
+            "It is 132 hours until Tuesday. (Rounded to the nearest hour.)"
+        );
+    }
+
+    #[test]
+    fn answers_like_a_transit_board() {
+        let guild = GuildConfig {
+            style: ResponseStyle::Eta,
+            ..GuildConfig::default()
+        };
+        assert_eq!(
+            hours_response(at(3, 12), midnight(), &guild),
+            "🚏 Tuesday — ETA 5d 12h"
+        );
+        assert_eq!(eta_response(0, "Tuesday", &guild), "🚏 Tuesday — arrived");
+    }
+
+    #[test]
+    fn says_how_far_through_the_day_before_tuesday_we_are() {
+        let guild = GuildConfig::default();
+        assert_eq!(
+            day_progress_response(at(1, 18), &guild),
+            Some("We're 75% of the way through the day before Tuesday.".to_string())
+        );
+        assert_eq!(
+            day_progress_response(at(2, 6), &guild),
+            Some("We're 25% of the way through Tuesday.".to_string())
+        );
+    }
+
+    #[test]
+    fn has_no_day_progress_further_from_tuesday() {
+        assert_eq!(
+            day_progress_response(at(3, 12), &GuildConfig::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn rounds_now_to_the_granularity() {
+        let guild = GuildConfig {
+            granularity: Some(Granularity::Hour),
+            ..GuildConfig::default()
+        };
+        let now = at(3, 12) + Duration::minutes(20);
+        assert_eq!(
+            hours_response(now, midnight(), &guild),
+            "It is 132 hours until Tuesday."
+        );
+        // Rounded up into Tuesday, it's already here
+        let now = at(1, 23) + Duration::minutes(40);
+        assert_eq!(hours_response(now, midnight(), &guild), "It's Tuesday! 🎉");
+    }
+
+    #[test]
+    fn leads_with_the_proximity_emoji() {
+        let guild = GuildConfig {
+            proximity: Some(Proximity::default()),
+            ..GuildConfig::default()
+        };
+        assert_eq!(
+            hours_response(at(1, 12), midnight(), &guild),
+            "🙂 It is 12 hours until Tuesday."
+        );
+        assert_eq!(
+            hours_response(at(2, 12), midnight(), &guild),
+            "🎉 It's Tuesday! 🎉"
+        );
+    }
+
+    #[test]
+    fn gives_the_exact_wait_in_milliseconds() {
+        let now = at(1, 23) + Duration::milliseconds(1);
+        assert_eq!(
+            exact_response(now, at(2, 0), "Tuesday"),
+            "It is exactly 3599 seconds and 999 milliseconds until Tuesday (3599999 ms)."
+        );
+        assert_eq!(
+            exact_response(at(2, 0), at(2, 0), "Tuesday"),
+            "It's Tuesday! 🎉"
+        );
+    }
+
+    #[test]
+    fn names_the_target_date() {
+        let guild = GuildConfig {
+            date_format: Some("%b %-d".to_string()),
+            ..GuildConfig::default()
+        };
+        assert_eq!(
+            hours_response(at(3, 12), midnight(), &guild),
+            "It is 132 hours until Tuesday (Jan 9)."
+        );
+    }
+
+    #[test]
+    fn leaves_off_dates_that_cannot_be_written() {
+        assert_eq!(with_date("Tuesday", at(9, 0), "%H:%M %Z"), "Tuesday");
+    }
+
+    fn frozen_guild(days: &[u32]) -> GuildConfig {
+        GuildConfig {
+            frozen_dates: days
+                .iter()
+                .map(|&day| NaiveDate::from_ymd(2024, 1, day))
+                .collect(),
+            ..GuildConfig::default()
+        }
+    }
+
+    #[test]
+    fn holds_the_countdown_still_on_frozen_dates() {
+        let guild = frozen_guild(&[3]);
+        assert_eq!(
+            hours_response(at(3, 12), midnight(), &guild),
+            "It is 144 hours until Tuesday."
+        );
+        assert_eq!(
+            hours_response(at(3, 20), midnight(), &guild),
+            "It is 144 hours until Tuesday."
+        );
+        // The day after, it picks up again
+        assert_eq!(
+            hours_response(at(4, 0), midnight(), &guild),
+            "It is 120 hours until Tuesday."
+        );
+    }
+
+    #[test]
+    fn a_frozen_tuesday_stays_tuesday_all_day() {
+        let guild = GuildConfig {
+            show_elapsed: true,
+            ..frozen_guild(&[2])
+        };
+        assert_eq!(
+            hours_response(at(2, 20), midnight(), &guild),
+            "It's Tuesday! It started 0 hours ago."
+        );
+    }
+
+    #[test]
+    fn measures_progress_from_the_anchor() {
+        let guild = GuildConfig::default();
+        assert_eq!(
+            anchored_progress_response(at(3, 12), PercentAnchor::Tuesday, &guild),
+            "We're 21% of the way from last Tuesday to Tuesday."
+        );
+        assert_eq!(
+            anchored_progress_response(at(3, 12), PercentAnchor::Month, &guild),
+            "We're 31% of the way from the start of the month to Tuesday."
+        );
+        let since = PercentAnchor::Date(NaiveDate::from_ymd(2023, 12, 26));
+        assert_eq!(
+            anchored_progress_response(at(3, 12), since, &guild),
+            "We're 60% of the way from Dec 26, 2023 to Tuesday."
+        );
+    }
+
+    #[test]
+    fn has_no_progress_from_a_future_anchor() {
+        let anchor = PercentAnchor::Date(NaiveDate::from_ymd(2024, 2, 1));
+        assert_eq!(
+            anchored_progress_response(at(3, 12), anchor, &GuildConfig::default()),
+            "It isn't Feb 1, 2024 yet."
+        );
+    }
+
+    #[test]
+    fn calls_out_an_exact_number_of_weeks() {
+        let week = Duration::weeks(1).num_milliseconds();
+        let guild = GuildConfig::default();
+        assert_eq!(
+            whole_weeks_response(week, "Tuesday", &guild),
+            Some("It is exactly 1 week until Tuesday.".to_string())
+        );
+        assert_eq!(
+            whole_weeks_response(2 * week, "Tuesday", &guild),
+            Some("It is exactly 2 weeks until Tuesday.".to_string())
+        );
+        assert_eq!(whole_weeks_response(week - 1, "Tuesday", &guild), None);
+        assert_eq!(whole_weeks_response(0, "Tuesday", &guild), None);
+    }
+
+    #[test]
+    fn snaps_to_the_grid_before_checking_for_whole_weeks() {
+        let guild = GuildConfig {
+            grid_minutes: Some(60),
+            ..GuildConfig::default()
+        };
+        let almost = Duration::weeks(1).num_milliseconds() - 1000;
+        assert_eq!(
+            whole_weeks_response(almost, "Tuesday", &guild),
+            Some("It is exactly 1 week until Tuesday.".to_string())
+        );
+    }
+
+    #[test]
+    fn whole_weeks_replace_the_usual_countdown() {
+        let guild = GuildConfig {
+            whole_weeks: true,
+            ..GuildConfig::default()
+        };
+        assert_eq!(
+            compute_response_to(
+                at(2, 0),
+                at(16, 0),
+                Weekday::Tue,
+                "Tuesday",
+                &build_unit(HOURS, &[]),
+                &guild
+            ),
+            "It is exactly 2 weeks until Tuesday."
+        );
+    }
+
+    fn holiday_guild(dates: &[NaiveDate]) -> GuildConfig {
+        GuildConfig {
+            holidays: dates.to_vec(),
+            ..GuildConfig::default()
+        }
+    }
+
+    #[test]
+    fn points_out_a_holiday_this_week() {
+        let guild = holiday_guild(&[NaiveDate::from_ymd(2024, 1, 2)]);
+        assert_eq!(
+            hours_response(at(1, 0), midnight(), &guild),
+            "It is 24 hours until Tuesday. (Tuesday is a holiday this week! 🎉)"
+        );
+    }
+
+    #[test]
+    fn says_nothing_when_tuesday_is_not_a_holiday() {
+        let guild = holiday_guild(&[NaiveDate::from_ymd(2024, 1, 9)]);
+        assert_eq!(
+            hours_response(at(1, 0), midnight(), &guild),
+            "It is 24 hours until Tuesday."
+        );
+    }
+
+    #[test]
+    fn points_out_a_holiday_further_off() {
+        let guild = holiday_guild(&[NaiveDate::from_ymd(2024, 1, 16)]);
+        assert_eq!(
+            compute_response_to(
+                at(2, 0),
+                at(16, 0),
+                Weekday::Tue,
+                "Tuesday",
+                &build_unit(HOURS, &[]),
+                &guild
+            ),
+            "It is 336 hours until Tuesday. (It's a holiday, too! 🎉)"
+        );
+    }
+
+    #[test]
+    fn grows_impatient_with_repeat_askers() {
+        let guild = GuildConfig::default();
+        let now = at(1, 12);
+        assert_eq!(impatient_response(1, now, &guild), "No.");
+        assert_eq!(impatient_response(2, now, &guild), "Still no.");
+        assert_eq!(
+            impatient_response(3, now, &guild),
+            "No. It's still not Tuesday."
+        );
+        assert_eq!(
+            impatient_response(7, now, &guild),
+            "I'll tell you the moment it is."
+        );
+    }
+
+    #[test]
+    fn says_yes_once_it_is_tuesday() {
+        assert_eq!(
+            impatient_response(5, at(2, 9), &GuildConfig::default()),
+            "Yes! It's Tuesday! 🎉"
+        );
+    }
+
+    #[test]
+    fn names_grid_sizes() {
+        assert_eq!(describe_grid(15), "15 minutes");
+        assert_eq!(describe_grid(60), "hour");
+        assert_eq!(describe_grid(180), "3 hours");
+        assert_eq!(describe_grid(1440), "day");
+        assert_eq!(describe_grid(90), "90 minutes");
+    }
+
+    #[test]
+    fn leaves_out_elapsed_unless_asked() {
+        assert_eq!(
+            hours_response(at(3, 12), midnight(), &GuildConfig::default()),
+            "It is 132 hours until Tuesday."
+        );
+    }
+}
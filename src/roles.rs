@@ -0,0 +1,132 @@
+use chrono::{Datelike, Weekday};
+use serenity::{model::id::GuildId, prelude::*};
+
+use crate::{
+    scheduler::discord_error_code, state::GuildConfig, timezone::zone_for, ConfigKey, StateKey,
+};
+
+/// Discord's error code for a member who isn't in the guild (anymore)
+const UNKNOWN_MEMBER: isize = 10007;
+
+/// Who should get the Tuesday role and who should lose it. On Tuesday every
+/// opted-in member gets it; otherwise, or once someone opts out, it's taken
+/// back from everyone we gave it to.
+pub fn role_changes(guild: &GuildConfig, is_tuesday: bool) -> (Vec<u64>, Vec<u64>) {
+    let grant = if is_tuesday {
+        guild
+            .role_optins
+            .iter()
+            .filter(|user| !guild.role_granted.contains(user))
+            .copied()
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let revoke = guild
+        .role_granted
+        .iter()
+        .filter(|user| !is_tuesday || !guild.role_optins.contains(user))
+        .copied()
+        .collect();
+    (grant, revoke)
+}
+
+/// Hands out and takes back every guild's Tuesday role as needed.
+pub fn update_tuesday_roles(ctx: &Context) {
+    let changes: Vec<(u64, u64, Vec<u64>, Vec<u64>)> = {
+        let data = ctx.data.read();
+        let enabled = data
+            .get::<ConfigKey>()
+            .expect("Expected to find config in context")
+            .tuesday_roles;
+        if !enabled {
+            return;
+        }
+        data.get::<StateKey>()
+            .expect("Expected to find bot state in context")
+            .guilds()
+            .filter_map(|(id, guild)| {
+                let role = guild.tuesday_role?;
+                let is_tuesday = zone_for(&data, Some(GuildId(id))).now().weekday() == Weekday::Tue;
+                let (grant, revoke) = role_changes(guild, is_tuesday);
+                if grant.is_empty() && revoke.is_empty() {
+                    None
+                } else {
+                    Some((id, role, grant, revoke))
+                }
+            })
+            .collect()
+    };
+
+    for (guild_id, role, grant, revoke) in changes {
+        let mut granted = Vec::new();
+        let mut revoked = Vec::new();
+        let mut left = Vec::new();
+        for user in grant {
+            match ctx.http.add_member_role(guild_id, user, role) {
+                Ok(()) => granted.push(user),
+                Err(why) if discord_error_code(&why) == Some(UNKNOWN_MEMBER) => left.push(user),
+                Err(why) => println!(
+                    "Error giving role {} in guild {}: {:?}",
+                    role, guild_id, why
+                ),
+            }
+        }
+        for user in revoke {
+            match ctx.http.remove_member_role(guild_id, user, role) {
+                Ok(()) => revoked.push(user),
+                // Nobody to take it back from
+                Err(why) if discord_error_code(&why) == Some(UNKNOWN_MEMBER) => left.push(user),
+                Err(why) => {
+                    println!(
+                        "Error removing role {} in guild {}: {:?}",
+                        role, guild_id, why
+                    )
+                }
+            }
+        }
+
+        let mut data = ctx.data.write();
+        let state = data
+            .get_mut::<StateKey>()
+            .expect("Expected to find bot state in context");
+        let guild = state.guild_mut(guild_id);
+        guild.role_granted.extend(granted);
+        guild
+            .role_granted
+            .retain(|user| !revoked.contains(user) && !left.contains(user));
+        guild.role_optins.retain(|user| !left.contains(user));
+        state.save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guild(optins: &[u64], granted: &[u64]) -> GuildConfig {
+        GuildConfig {
+            role_optins: optins.to_vec(),
+            role_granted: granted.to_vec(),
+            ..GuildConfig::default()
+        }
+    }
+
+    #[test]
+    fn grants_the_role_to_new_optins_on_tuesday() {
+        assert_eq!(role_changes(&guild(&[1, 2], &[1]), true), (vec![2], vec![]));
+    }
+
+    #[test]
+    fn takes_the_role_back_after_tuesday() {
+        assert_eq!(
+            role_changes(&guild(&[1, 2], &[1, 2]), false),
+            (vec![], vec![1, 2])
+        );
+    }
+
+    #[test]
+    fn takes_the_role_back_on_opt_out() {
+        assert_eq!(role_changes(&guild(&[2], &[1, 2]), true), (vec![], vec![1]));
+    }
+}
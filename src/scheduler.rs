@@ -0,0 +1,87 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration as StdDuration,
+};
+
+use chrono::Duration;
+use serenity::{model::id::ChannelId, prelude::*, Error};
+
+use crate::{
+    clock, followup::send_followups, roles::update_tuesday_roles, topic::update_topics, ConfigKey,
+    StateKey,
+};
+
+/// How often background jobs run
+const TICK: StdDuration = StdDuration::from_secs(60);
+
+/// Discord's error code for a message that doesn't exist (anymore)
+const UNKNOWN_MESSAGE: isize = 10008;
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Starts the background job thread. `ready` fires again on every reconnect,
+/// so only the first call does anything.
+pub fn start(ctx: Context) {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    thread::spawn(move || loop {
+        tick(&ctx);
+        thread::sleep(TICK);
+    });
+}
+
+fn tick(ctx: &Context) {
+    cleanup_stale_replies(ctx);
+    update_topics(ctx);
+    send_followups(ctx);
+    update_tuesday_roles(ctx);
+}
+
+/// The error code Discord answered a failed request with, if any
+pub fn discord_error_code(why: &Error) -> Option<isize> {
+    match why {
+        Error::Http(http) => match http.as_ref() {
+            serenity::http::HttpError::UnsuccessfulRequest(response) => Some(response.error.code),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether `why` means the message we tried to touch is already gone
+pub fn is_unknown_message(why: &Error) -> bool {
+    discord_error_code(why) == Some(UNKNOWN_MESSAGE)
+}
+
+fn cleanup_stale_replies(ctx: &Context) {
+    let stale = {
+        let mut data = ctx.data.write();
+        let config = data
+            .get::<ConfigKey>()
+            .expect("Expected to find config in context");
+        if !config.cleanup_stale_replies {
+            return;
+        }
+        let age = Duration::seconds(config.stale_reply_age_secs as i64);
+        let state = data
+            .get_mut::<StateKey>()
+            .expect("Expected to find bot state in context");
+        let stale = state.take_stale_replies(clock::now(), age);
+        if !stale.is_empty() {
+            state.save();
+        }
+        stale
+    };
+
+    for reply in stale {
+        let channel_id = ChannelId(reply.channel_id);
+        if let Err(why) = channel_id.delete_message(&ctx.http, reply.message_id) {
+            // Someone beat us to it, which is fine
+            if !is_unknown_message(&why) {
+                println!("Error deleting stale reply: {:?}", why);
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::{
+    config::Config,
+    pipeline::evaluate,
+    units::{load_custom_units, CustomRegexCache},
+};
+
+/// Queries that between them go through most of the parsing: plain and
+/// prefixed units, stacked prefixes, times of day, and other weekdays
+const SAMPLE_QUERIES: [&str; 8] = [
+    "tuesday",
+    "how many hours until tuesday",
+    "kiloseconds until tues",
+    "how many megakilominutes until tuesday",
+    "weeks until tuesday at 5pm",
+    "days until friday",
+    "how long until the 2nd tuesday",
+    "surprise me, how long until tuesday",
+];
+
+/// A fixed Wednesday noon, so the self-test doesn't depend on when it runs
+fn sample_now() -> NaiveDateTime {
+    NaiveDate::from_ymd(2024, 1, 3).and_hms(12, 0, 0)
+}
+
+/// Runs a handful of representative queries, plus one for each custom unit
+/// in `config`, through the whole countdown path. The first one that panics,
+/// comes out non-finite, or ends up with no unit name is reported, since any
+/// of those means something is misconfigured that would otherwise only show
+/// up once someone asks.
+pub fn self_test(config: &Config) -> Result<(), String> {
+    let custom_units = load_custom_units(&config.custom_units);
+    let mut queries: Vec<String> = SAMPLE_QUERIES.iter().map(|s| s.to_string()).collect();
+    queries.extend(
+        custom_units
+            .iter()
+            .map(|unit| format!("how many {} until tuesday", unit.name)),
+    );
+    let regex_cache = CustomRegexCache::new(custom_units);
+    let now = sample_now();
+    for query in &queries {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| evaluate(query, now, &regex_cache)));
+        match result {
+            Err(_) => return Err(format!("{:?} panicked", query)),
+            Ok((value, _)) if !value.is_finite() => {
+                return Err(format!("{:?} came out as {}", query, value))
+            }
+            Ok((_, name)) if name.trim().is_empty() => {
+                return Err(format!("{:?} came out with no unit name", query))
+            }
+            Ok(_) => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_with_the_default_config() {
+        assert_eq!(self_test(&Config::default()), Ok(()));
+    }
+
+    #[test]
+    fn tries_each_custom_unit() {
+        let mut config = Config::default();
+        config.custom_units.insert("coffee".to_string(), 900.0);
+        assert_eq!(self_test(&config), Ok(()));
+        // Short enough that the countdown overflows
+        config.custom_units.insert("blink".to_string(), 1e-320);
+        let error = self_test(&config).unwrap_err();
+        assert!(error.contains("blink"), "{}", error);
+    }
+}
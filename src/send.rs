@@ -0,0 +1,343 @@
+use std::{
+    sync::{Arc, Condvar, Mutex as StdMutex},
+    time::{Duration, Instant},
+};
+
+use serde_json::{json, Value};
+use serenity::{
+    model::{
+        channel::Message,
+        id::{ChannelId, MessageId},
+    },
+    prelude::*,
+    Error, Result,
+};
+
+use crate::{
+    state::{MentionMode, ReplyMode},
+    ConfigKey,
+};
+
+/// What `send_countdown` fails with when it drops a countdown instead of
+/// queueing it behind a global rate limit
+const RATE_LIMITED: &str = "Dropped while globally rate limited";
+
+/// Sends that are still waiting on Discord. Serenity sleeps through a global
+/// rate limit inside the request, so one send hanging for a long time is the
+/// only sign we get that every other send would hang too.
+#[derive(Default)]
+pub struct GlobalRateLimit {
+    in_flight: usize,
+    oldest: Option<Instant>,
+    warned: bool,
+}
+
+impl GlobalRateLimit {
+    /// Whether a send has been stuck for at least `stall` as of `now`, so
+    /// new ones should be dropped.
+    pub fn is_limited(&self, stall: Duration, now: Instant) -> bool {
+        self.oldest
+            .is_some_and(|oldest| now.duration_since(oldest) >= stall)
+    }
+
+    fn start(&mut self, now: Instant) {
+        self.in_flight += 1;
+        self.oldest.get_or_insert(now);
+    }
+
+    fn finish(&mut self) {
+        self.in_flight = self.in_flight.saturating_sub(1);
+        if self.in_flight == 0 {
+            self.oldest = None;
+            self.warned = false;
+        }
+    }
+}
+
+pub struct GlobalRateLimitKey;
+impl TypeMapKey for GlobalRateLimitKey {
+    type Value = GlobalRateLimit;
+}
+
+/// Caps how many sends can be waiting on Discord at once, so a burst of
+/// triggers doesn't turn into a burst of requests. Sends past the cap wait
+/// their turn. A cap of 0 means no cap.
+pub struct SendLimiter {
+    max: usize,
+    // A std mutex, since parking_lot's doesn't pair with a std `Condvar`
+    in_flight: StdMutex<usize>,
+    freed: Condvar,
+}
+
+impl SendLimiter {
+    pub fn new(max: usize) -> SendLimiter {
+        SendLimiter {
+            max,
+            in_flight: StdMutex::new(0),
+            freed: Condvar::new(),
+        }
+    }
+
+    /// Blocks until there's room for another send, then holds its place
+    /// until the returned permit is dropped.
+    pub fn acquire(&self) -> SendPermit<'_> {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        while self.max > 0 && *in_flight >= self.max {
+            in_flight = self
+                .freed
+                .wait(in_flight)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        *in_flight += 1;
+        SendPermit { limiter: self }
+    }
+}
+
+/// One send's place under a `SendLimiter`'s cap
+pub struct SendPermit<'a> {
+    limiter: &'a SendLimiter,
+}
+
+impl Drop for SendPermit<'_> {
+    fn drop(&mut self) {
+        let mut in_flight = self
+            .limiter
+            .in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        *in_flight = in_flight.saturating_sub(1);
+        self.limiter.freed.notify_one();
+    }
+}
+
+/// Shared so a send can wait on it without holding the whole data lock
+pub struct SendLimiterKey;
+impl TypeMapKey for SendLimiterKey {
+    type Value = Arc<SendLimiter>;
+}
+
+/// Logs a failed send, except for countdowns dropped during a global rate
+/// limit, which were already logged once for the whole limit.
+pub fn report_send_error(why: &Error) {
+    match why {
+        Error::Other(RATE_LIMITED) => {}
+        _ => println!("Error sending message: {:?}", why),
+    }
+}
+
+/// The `allowed_mentions` for a countdown that may mention `role`. Nothing
+/// else in a countdown should ever ping anyone, so it's always this strict,
+/// apart from the author of the message being replied to if `ping_author`.
+pub fn allowed_mentions(mode: MentionMode, role: Option<u64>, ping_author: bool) -> Value {
+    let mut allowed = match (mode, role) {
+        (MentionMode::Ping, Some(role)) => json!({ "parse": [], "roles": [role.to_string()] }),
+        _ => json!({ "parse": [] }),
+    };
+    allowed["replied_user"] = json!(ping_author);
+    allowed
+}
+
+/// The message to send for a countdown. With a `reply_to` and a reply mode
+/// other than plain, it's a Discord reply to that message, which still goes
+/// through as a plain message if the original was deleted in the meantime.
+pub fn countdown_payload(
+    content: &str,
+    mode: MentionMode,
+    role: Option<u64>,
+    reply_to: Option<(MessageId, ReplyMode)>,
+) -> Value {
+    let ping_author = matches!(reply_to, Some((_, ReplyMode::Ping)));
+    let mut map = json!({
+        "content": content,
+        "allowed_mentions": allowed_mentions(mode, role, ping_author),
+    });
+    match reply_to {
+        Some((message_id, ReplyMode::Reply)) | Some((message_id, ReplyMode::Ping)) => {
+            map["message_reference"] = json!({
+                "message_id": message_id.0.to_string(),
+                "fail_if_not_exists": false,
+            });
+        }
+        Some((_, ReplyMode::Plain)) | None => {}
+    }
+    map
+}
+
+/// Posts a countdown to `channel_id`, letting `role` notify people only if
+/// `mode` says so, and replying to `reply_to` if given.
+pub fn send_countdown(
+    ctx: &Context,
+    channel_id: ChannelId,
+    content: &str,
+    mode: MentionMode,
+    role: Option<u64>,
+    reply_to: Option<(MessageId, ReplyMode)>,
+) -> Result<Message> {
+    let map = countdown_payload(content, mode, role, reply_to);
+    let limiter = Arc::clone(
+        ctx.data
+            .read()
+            .get::<SendLimiterKey>()
+            .expect("Expected to find send limiter in context"),
+    );
+    // Waiting here rather than after the rate limit check, so time spent
+    // queued doesn't look like Discord stalling
+    let _permit = limiter.acquire();
+    {
+        let mut data = ctx.data.write();
+        let stall = data
+            .get::<ConfigKey>()
+            .expect("Expected to find config in context")
+            .global_rate_limit_secs;
+        let limit = data
+            .get_mut::<GlobalRateLimitKey>()
+            .expect("Expected to find global rate limit in context");
+        if stall > 0 && limit.is_limited(Duration::from_secs(stall), Instant::now()) {
+            if !limit.warned {
+                println!("Globally rate limited, dropping countdowns until it clears");
+                limit.warned = true;
+            }
+            return Err(Error::Other(RATE_LIMITED));
+        }
+        limit.start(Instant::now());
+    }
+    let sent = ctx.http.send_message(channel_id.0, &map);
+    ctx.data
+        .write()
+        .get_mut::<GlobalRateLimitKey>()
+        .expect("Expected to find global rate limit in context")
+        .finish();
+    sent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_limited_once_a_send_stalls() {
+        let mut limit = GlobalRateLimit::default();
+        let start = Instant::now();
+        let stall = Duration::from_secs(5);
+        assert!(!limit.is_limited(stall, start));
+        limit.start(start);
+        limit.start(start + Duration::from_secs(3));
+        assert!(!limit.is_limited(stall, start + Duration::from_secs(4)));
+        // Measured from the oldest send still waiting
+        assert!(limit.is_limited(stall, start + Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn clears_once_every_send_finishes() {
+        let mut limit = GlobalRateLimit::default();
+        let start = Instant::now();
+        let stall = Duration::from_secs(5);
+        limit.start(start);
+        limit.start(start);
+        limit.finish();
+        assert!(limit.is_limited(stall, start + stall));
+        limit.finish();
+        assert!(!limit.is_limited(stall, start + stall));
+        // Finishing more than started doesn't underflow
+        limit.finish();
+        assert!(!limit.is_limited(stall, start + stall));
+    }
+
+    #[test]
+    fn lets_only_the_tuesday_role_ping() {
+        assert_eq!(
+            allowed_mentions(MentionMode::Ping, Some(5), false),
+            json!({ "parse": [], "roles": ["5"], "replied_user": false })
+        );
+    }
+
+    #[test]
+    fn sends_a_plain_message_without_a_reply_mode() {
+        let payload = countdown_payload("hi", MentionMode::Off, None, None);
+        assert_eq!(payload["content"], "hi");
+        assert!(payload.get("message_reference").is_none());
+        let plain = countdown_payload(
+            "hi",
+            MentionMode::Off,
+            None,
+            Some((MessageId(3), ReplyMode::Plain)),
+        );
+        assert!(plain.get("message_reference").is_none());
+    }
+
+    #[test]
+    fn replies_to_the_trigger_and_pings_only_if_asked() {
+        let reply = countdown_payload(
+            "hi",
+            MentionMode::Off,
+            None,
+            Some((MessageId(3), ReplyMode::Reply)),
+        );
+        assert_eq!(
+            reply["message_reference"],
+            json!({ "message_id": "3", "fail_if_not_exists": false })
+        );
+        assert_eq!(reply["allowed_mentions"]["replied_user"], false);
+        let ping = countdown_payload(
+            "hi",
+            MentionMode::Off,
+            None,
+            Some((MessageId(3), ReplyMode::Ping)),
+        );
+        assert_eq!(ping["allowed_mentions"]["replied_user"], true);
+    }
+
+    #[test]
+    fn silent_mentions_ping_nobody() {
+        for mode in [MentionMode::Silent, MentionMode::Off] {
+            assert_eq!(
+                allowed_mentions(mode, Some(5), false),
+                json!({ "parse": [], "replied_user": false })
+            );
+        }
+        assert_eq!(
+            allowed_mentions(MentionMode::Ping, None, false),
+            json!({ "parse": [], "replied_user": false })
+        );
+    }
+
+    fn in_flight(limiter: &SendLimiter) -> usize {
+        *limiter.in_flight.lock().unwrap()
+    }
+
+    #[test]
+    fn holds_a_place_until_the_permit_drops() {
+        let limiter = SendLimiter::new(2);
+        let first = limiter.acquire();
+        let second = limiter.acquire();
+        assert_eq!(in_flight(&limiter), 2);
+        drop(first);
+        assert_eq!(in_flight(&limiter), 1);
+        drop(second);
+        assert_eq!(in_flight(&limiter), 0);
+    }
+
+    #[test]
+    fn makes_sends_past_the_cap_wait() {
+        let limiter = Arc::new(SendLimiter::new(1));
+        let permit = limiter.acquire();
+        let (sent, done) = std::sync::mpsc::channel();
+        let waiting = Arc::clone(&limiter);
+        let handle = std::thread::spawn(move || {
+            let _permit = waiting.acquire();
+            sent.send(()).unwrap();
+        });
+        assert!(done.recv_timeout(Duration::from_millis(50)).is_err());
+        drop(permit);
+        assert!(done.recv_timeout(Duration::from_secs(5)).is_ok());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn never_waits_without_a_cap() {
+        let limiter = SendLimiter::new(0);
+        let permits: Vec<_> = (0..10).map(|_| limiter.acquire()).collect();
+        assert_eq!(in_flight(&limiter), 10);
+        drop(permits);
+    }
+}
@@ -0,0 +1,865 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::prelude::*,
+};
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::dates::{is_calendar_holiday, overlap, previous_weekday};
+
+const STATE_FILE: &str = "state.json";
+
+/// How the numeric part of a countdown gets rendered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberStyle {
+    #[default]
+    Digits,
+    Words,
+    Binary,
+    Octal,
+    Hex,
+    /// Scaled to between 1 and 1000 with an SI prefix on the unit, like
+    /// "1.6 kiloseconds"
+    Engineering,
+}
+
+/// What to do with values too small to read comfortably, like the number of
+/// gigaseconds until Tuesday.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmallValueStyle {
+    /// Print them like any other value
+    Plain,
+    /// Switch to scientific notation
+    Scientific,
+    /// Print them normally, but point out how small they are
+    Note,
+}
+
+/// How to treat trigger words hidden in `||spoilers||`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpoilerPolicy {
+    /// Spoilers count like any other text
+    Ignore,
+    /// Words inside spoilers never trigger a reply
+    Skip,
+    /// Words inside spoilers trigger a reply that's spoilered too
+    Hide,
+}
+
+/// Where a yearly date of Feb 29 lands in years that don't have one
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LeapDayPolicy {
+    /// The day before, Feb 28
+    #[default]
+    Clamp,
+    /// The day after, Mar 1
+    Roll,
+}
+
+impl LeapDayPolicy {
+    /// `month` and `day` in `year`. Feb 29 always lands somewhere under this
+    /// policy; any other date that doesn't exist is `None`.
+    pub fn date_in(self, year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+        match NaiveDate::from_ymd_opt(year, month, day) {
+            Some(date) => Some(date),
+            None if (month, day) == (2, 29) => match self {
+                LeapDayPolicy::Clamp => NaiveDate::from_ymd_opt(year, 2, 28),
+                LeapDayPolicy::Roll => NaiveDate::from_ymd_opt(year, 3, 1),
+            },
+            None => None,
+        }
+    }
+}
+
+/// Settings a guild's admins can change with `tue!` commands.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuildConfig {
+    pub number_style: NumberStyle,
+    /// Also say how long it's been since the last Tuesday
+    pub show_elapsed: bool,
+    /// What to call Tuesday in responses, e.g. "Taco Tuesday"
+    pub occasion: Option<String>,
+    pub small_values: SmallValueStyle,
+    /// Values with a magnitude below this get `small_values` treatment
+    pub small_value_threshold: f64,
+    /// Only answer `tue!` commands, ignoring "tues" and every other phrase
+    /// in ordinary messages. This wins over any other trigger setting.
+    pub strict_mode: bool,
+    /// Don't answer "tues" when it only shows up in `> quoted` lines, which
+    /// usually means someone's quoting an earlier message
+    pub ignore_quotes: bool,
+    /// Within this many seconds of Tuesday, count down in tenths of a
+    /// second no matter what unit was asked for
+    pub final_countdown_secs: f64,
+    /// Channel to comment in when a scheduled event lands on a Tuesday
+    pub event_channel: Option<u64>,
+    /// Channel whose topic is kept up to date with the countdown
+    pub topic_channel: Option<u64>,
+    /// Timezone as minutes east of UTC, overriding the operator's default
+    pub utc_offset_minutes: Option<i32>,
+    /// Custom commands, mapping a name (without `tue!`) to the query it
+    /// stands for
+    pub aliases: HashMap<String, String>,
+    /// How to write each of `TIME_UNITS` in countdowns, like "hrs"
+    /// for hours. Empty, or any other length, means the usual names.
+    pub unit_names: Vec<String>,
+    pub spoiler_policy: SpoilerPolicy,
+    /// Dates during which time doesn't count toward the countdown
+    pub vacation: Option<Vacation>,
+    /// Round countdowns to the nearest this many minutes
+    pub grid_minutes: Option<u32>,
+    /// 12 or 24 hour clock. Unset follows the guild's locale.
+    pub time_format: Option<TimeFormat>,
+    /// Answer in the least convenient unit possible, on purpose
+    pub dad_mode: bool,
+    pub style: ResponseStyle,
+    /// Style for messages that are nothing but the trigger word, if they
+    /// get a different one
+    pub trigger_only_style: Option<ResponseStyle>,
+    /// When on Tuesday the occasion starts, if not at midnight
+    pub occasion_time: Option<NaiveTime>,
+    /// Role handed out for the day to members who opted in
+    pub tuesday_role: Option<u64>,
+    pub role_optins: Vec<u64>,
+    /// Members currently holding `tuesday_role` because we gave it to them
+    pub role_granted: Vec<u64>,
+    /// Characters in the longest bar of the chart style
+    pub chart_width: usize,
+    pub multi_day_policy: MultiDayPolicy,
+    /// Round instants so asking twice in the same minute (or hour) gives the
+    /// same answer. Countdowns are then only accurate to half of this, and
+    /// Tuesday can start up to half of it early.
+    pub granularity: Option<Granularity>,
+    pub mention_mode: MentionMode,
+    /// When set, a ping turns silent unless at least this fraction (0 to 1)
+    /// of the role's members are online
+    pub quiet_ping_threshold: Option<f64>,
+    pub reply_mode: ReplyMode,
+    /// When set, countdowns name the date they count down to, written with
+    /// this strftime format, like "Dec 3" for "%b %-d"
+    pub date_format: Option<String>,
+    /// Days the countdown stands still, reading all day as it did at the
+    /// start of the day
+    pub frozen_dates: Vec<NaiveDate>,
+    pub percent_anchor: Option<PercentAnchor>,
+    /// Decimal places percentages are shown with, up to
+    /// `MAX_PERCENT_DECIMALS`
+    pub percent_decimals: u8,
+    /// Days countdowns point out as holidays
+    pub holidays: Vec<NaiveDate>,
+    /// Holidays on the same (month, day) every year
+    pub yearly_holidays: Vec<(u32, u32)>,
+    /// Where a yearly holiday on Feb 29 goes outside of leap years
+    pub leap_day: LeapDayPolicy,
+    /// One of `HOLIDAY_CALENDARS` whose holidays count too
+    pub holiday_calendar: Option<String>,
+    /// Which of `TIME_UNITS` countdowns may be given in. Empty means
+    /// all of them. Custom and joke units aren't affected.
+    pub allowed_units: Vec<String>,
+    /// Whether SI prefixes may be put on units
+    pub si_prefixes_allowed: bool,
+    /// Refuse to answer in a unit that isn't allowed, instead of switching
+    /// to the nearest one that is
+    pub refuse_disallowed_units: bool,
+    /// Say "exactly 2 weeks" when that's precisely what's left, instead of
+    /// the usual countdown
+    pub whole_weeks: bool,
+    pub trigger_sources: TriggerSources,
+    /// When a query names several units, say which one the answer is in
+    pub explain_units: bool,
+    /// Answer "is it tuesday yet" with a plain no, getting shorter with
+    /// each person who keeps asking
+    pub impatience: bool,
+    /// Show how close Tuesday is with an emoji, when set
+    pub proximity: Option<Proximity>,
+}
+
+/// How a countdown reply is laid out
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseStyle {
+    /// "It is 76 hours until Tuesday."
+    #[default]
+    Sentence,
+    /// "🚏 Tuesday — ETA 3d 4h", like a transit board
+    Eta,
+    /// A bar per unit, sized by how much of the wait it makes up
+    Chart,
+}
+
+/// What to do when a message names more than one weekday
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MultiDayPolicy {
+    /// Count down to whichever comes first
+    #[default]
+    Earliest,
+    /// Count down to each of them
+    All,
+}
+
+/// What "now" and the target get rounded to before counting down
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    Minute,
+    Hour,
+}
+
+impl Granularity {
+    pub fn duration(self) -> Duration {
+        match self {
+            Granularity::Minute => Duration::minutes(1),
+            Granularity::Hour => Duration::hours(1),
+        }
+    }
+}
+
+/// Whether the Tuesday role mention in a countdown notifies anyone
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MentionMode {
+    /// Mention the role and notify its members, if it's mentionable
+    #[default]
+    Ping,
+    /// Show the mention without notifying anyone
+    Silent,
+    /// Leave the mention out
+    Off,
+}
+
+/// Where "what percent of the way to Tuesday are we" starts counting from.
+/// Without one, percent questions get how far through today we are, but
+/// only the day before Tuesday or on it.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PercentAnchor {
+    /// The start of the most recent Tuesday, so the week goes 0 to 100%
+    Tuesday,
+    /// The start of the month
+    Month,
+    /// The start of a fixed date, like when a project began
+    Date(NaiveDate),
+}
+
+impl PercentAnchor {
+    /// When the interval containing `now` started. `None` if that's a date
+    /// still in the future.
+    pub fn start(self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = match self {
+            PercentAnchor::Tuesday => previous_weekday(now, Weekday::Tue),
+            PercentAnchor::Month => now.date().with_day(1)?.and_hms(0, 0, 0),
+            PercentAnchor::Date(date) => date.and_hms(0, 0, 0),
+        };
+        if start <= now {
+            Some(start)
+        } else {
+            None
+        }
+    }
+
+    pub fn describe(self) -> String {
+        match self {
+            PercentAnchor::Tuesday => "last Tuesday".to_string(),
+            PercentAnchor::Month => "the start of the month".to_string(),
+            PercentAnchor::Date(date) => date.format("%b %-d, %Y").to_string(),
+        }
+    }
+}
+
+/// Which ways of asking for a countdown a guild answers. `tue!` commands
+/// always work, so admins can't lock themselves out.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TriggerSources {
+    /// "tues" anywhere in a message
+    pub phrase: bool,
+    /// @mentioning the bot
+    pub mention: bool,
+    /// Replying to one of the bot's messages
+    pub reply: bool,
+    /// Reacting with the configured emoji
+    pub reaction: bool,
+}
+
+impl Default for TriggerSources {
+    fn default() -> Self {
+        TriggerSources {
+            phrase: true,
+            mention: false,
+            reply: false,
+            reaction: true,
+        }
+    }
+}
+
+/// Whether countdowns are posted as Discord replies to the message that
+/// asked for them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplyMode {
+    /// A plain message in the channel
+    #[default]
+    Plain,
+    /// A reply that doesn't notify the author
+    Reply,
+    /// A reply that notifies the author
+    Ping,
+}
+
+/// Emoji put in front of countdowns to show how close Tuesday is
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Proximity {
+    pub far: String,
+    pub close: String,
+    pub imminent: String,
+    pub today: String,
+    /// Under this many hours away is close
+    pub close_hours: f64,
+    /// Under this many hours away is imminent
+    pub imminent_hours: f64,
+}
+
+impl Default for Proximity {
+    fn default() -> Self {
+        Proximity {
+            far: "😴".to_string(),
+            close: "🙂".to_string(),
+            imminent: "🤩".to_string(),
+            today: "🎉".to_string(),
+            close_hours: 48.0,
+            imminent_hours: 6.0,
+        }
+    }
+}
+
+impl Proximity {
+    /// The emoji for Tuesday being `remaining` away. It's "today" once
+    /// nothing remains.
+    pub fn emoji(&self, remaining: Duration) -> &str {
+        let hours = remaining.num_milliseconds() as f64 / 3_600_000.0;
+        if hours <= 0.0 {
+            &self.today
+        } else if hours < self.imminent_hours {
+            &self.imminent
+        } else if hours < self.close_hours {
+            &self.close
+        } else {
+            &self.far
+        }
+    }
+}
+
+/// How clock times are written
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TimeFormat {
+    #[serde(rename = "12h")]
+    TwelveHour,
+    #[serde(rename = "24h")]
+    TwentyFourHour,
+}
+
+impl TimeFormat {
+    /// The usual clock for a Discord locale like "en-US"
+    pub fn for_locale(locale: &str) -> TimeFormat {
+        match locale {
+            "en-US" | "en-CA" | "en-AU" | "hi" | "ko" | "zh-TW" => TimeFormat::TwelveHour,
+            _ => TimeFormat::TwentyFourHour,
+        }
+    }
+}
+
+/// A range of days, both inclusive, in the guild's timezone
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Vacation {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl Vacation {
+    /// How much of the time from `from` to `to` is spent on vacation
+    pub fn overlap(&self, from: NaiveDateTime, to: NaiveDateTime) -> Duration {
+        let start = self.start.and_hms(0, 0, 0);
+        let end = self.end.succ().and_hms(0, 0, 0);
+        overlap(from, to, start, end)
+    }
+}
+
+impl Default for GuildConfig {
+    fn default() -> Self {
+        GuildConfig {
+            number_style: NumberStyle::Digits,
+            show_elapsed: false,
+            occasion: None,
+            small_values: SmallValueStyle::Note,
+            small_value_threshold: 0.001,
+            strict_mode: false,
+            ignore_quotes: false,
+            final_countdown_secs: 60.0,
+            event_channel: None,
+            topic_channel: None,
+            utc_offset_minutes: None,
+            aliases: HashMap::new(),
+            unit_names: Vec::new(),
+            spoiler_policy: SpoilerPolicy::Ignore,
+            vacation: None,
+            grid_minutes: None,
+            time_format: None,
+            dad_mode: false,
+            style: ResponseStyle::Sentence,
+            trigger_only_style: None,
+            occasion_time: None,
+            tuesday_role: None,
+            role_optins: Vec::new(),
+            role_granted: Vec::new(),
+            chart_width: 20,
+            multi_day_policy: MultiDayPolicy::Earliest,
+            granularity: None,
+            mention_mode: MentionMode::Ping,
+            quiet_ping_threshold: None,
+            reply_mode: ReplyMode::Plain,
+            date_format: None,
+            frozen_dates: Vec::new(),
+            percent_anchor: None,
+            percent_decimals: 0,
+            holidays: Vec::new(),
+            yearly_holidays: Vec::new(),
+            leap_day: LeapDayPolicy::default(),
+            holiday_calendar: None,
+            allowed_units: Vec::new(),
+            si_prefixes_allowed: true,
+            refuse_disallowed_units: false,
+            whole_weeks: false,
+            trigger_sources: TriggerSources::default(),
+            explain_units: false,
+            impatience: false,
+            proximity: None,
+        }
+    }
+}
+
+impl GuildConfig {
+    pub fn occasion_name(&self) -> &str {
+        self.occasion.as_deref().unwrap_or("Tuesday")
+    }
+
+    /// Whether `value` is nonzero but below the small-value threshold
+    pub fn is_small(&self, value: f64) -> bool {
+        value != 0.0 && value.abs() < self.small_value_threshold
+    }
+
+    /// Whether `date` is one of this guild's holidays
+    pub fn is_holiday(&self, date: NaiveDate) -> bool {
+        self.holidays.contains(&date)
+            || self
+                .yearly_holidays
+                .iter()
+                .any(|&(month, day)| self.leap_day.date_in(date.year(), month, day) == Some(date))
+            || self
+                .holiday_calendar
+                .as_deref()
+                .is_some_and(|calendar| is_calendar_holiday(calendar, date))
+    }
+
+    /// The time to count down from at `now`. On a frozen date that's the
+    /// start of the day, so a frozen Tuesday reads as Tuesday all day.
+    pub fn frozen_now(&self, now: NaiveDateTime) -> NaiveDateTime {
+        if self.frozen_dates.contains(&now.date()) {
+            now.date().and_hms(0, 0, 0)
+        } else {
+            now
+        }
+    }
+
+    /// What to call `day`. Only Tuesday gets renamed by the occasion.
+    pub fn occasion_for(&self, day: Weekday) -> String {
+        match day {
+            Weekday::Tue => self.occasion_name().to_string(),
+            Weekday::Mon => "Monday".to_string(),
+            Weekday::Wed => "Wednesday".to_string(),
+            Weekday::Thu => "Thursday".to_string(),
+            Weekday::Fri => "Friday".to_string(),
+            Weekday::Sat => "Saturday".to_string(),
+            Weekday::Sun => "Sunday".to_string(),
+        }
+    }
+
+    /// The grid countdowns get rounded to, if any
+    pub fn grid(&self) -> Option<Duration> {
+        self.grid_minutes
+            .filter(|&minutes| minutes > 0)
+            .map(|minutes| Duration::minutes(minutes as i64))
+    }
+}
+
+/// A countdown the bot posted, remembered so it can be cleaned up later
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrackedReply {
+    pub channel_id: u64,
+    pub message_id: u64,
+    /// Start of the Tuesday the reply counted down to
+    pub tuesday: DateTime<Utc>,
+}
+
+impl TrackedReply {
+    /// A reply goes stale `age` after the start of its Tuesday
+    pub fn is_stale(&self, now: DateTime<Utc>, age: Duration) -> bool {
+        now >= self.tuesday + age
+    }
+}
+
+/// What the bot remembers about one user, across every guild
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserState {
+    /// Overrides the guild's timezone for this user's own queries
+    pub utc_offset_minutes: Option<i32>,
+    /// How many countdowns this user has set off
+    pub triggers: u64,
+    /// How many times they've asked "is it tuesday yet" while waiting for
+    /// `yet_tuesday`
+    pub yet_asks: u32,
+    pub yet_tuesday: Option<NaiveDate>,
+}
+
+impl UserState {
+    /// Counts another "is it tuesday yet" while waiting for `tuesday`, and
+    /// returns how many that makes. Asking about a new Tuesday starts over.
+    pub fn ask_yet(&mut self, tuesday: NaiveDate) -> u32 {
+        if self.yet_tuesday != Some(tuesday) {
+            self.yet_tuesday = Some(tuesday);
+            self.yet_asks = 0;
+        }
+        self.yet_asks += 1;
+        self.yet_asks
+    }
+}
+
+/// Someone who asked how long until Tuesday, remembered so we can tell them
+/// when it arrives
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrackedQuery {
+    pub channel_id: u64,
+    pub message_id: u64,
+    pub user_id: u64,
+    /// Start of the Tuesday they were waiting for
+    pub tuesday: DateTime<Utc>,
+}
+
+impl TrackedQuery {
+    /// Whether their Tuesday has arrived at `now`. Once the day is over it's
+    /// too late to bother following up.
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        now >= self.tuesday && now < self.tuesday + Duration::days(1)
+    }
+
+    /// Whether this query no longer needs following up at `now`
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now >= self.tuesday + Duration::days(1)
+    }
+}
+
+/// Everything the bot remembers between restarts, saved as JSON next to
+/// oauth2.tok.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BotState {
+    guilds: HashMap<u64, GuildConfig>,
+    users: HashMap<u64, UserState>,
+    replies: Vec<TrackedReply>,
+    queries: Vec<TrackedQuery>,
+    /// While set, the bot answers nothing but `tue!maintenance`
+    pub maintenance: bool,
+    /// Guilds that already got the welcome message
+    greeted: HashSet<u64>,
+}
+
+impl BotState {
+    pub fn load() -> BotState {
+        let mut file = match File::open(STATE_FILE) {
+            Ok(file) => file,
+            // No state yet, so start fresh
+            Err(_) => return BotState::default(),
+        };
+        let mut contents = String::new();
+        if let Err(why) = file.read_to_string(&mut contents) {
+            println!("Error reading {}: {:?}", STATE_FILE, why);
+            return BotState::default();
+        }
+        match serde_json::from_str(&contents) {
+            Ok(state) => state,
+            Err(why) => {
+                println!("Error parsing {}: {:?}", STATE_FILE, why);
+                BotState::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let contents = match serde_json::to_string_pretty(self) {
+            Ok(contents) => contents,
+            Err(why) => {
+                println!("Error serializing state: {:?}", why);
+                return;
+            }
+        };
+        let result =
+            File::create(STATE_FILE).and_then(|mut file| file.write_all(contents.as_bytes()));
+        if let Err(why) = result {
+            println!("Error writing {}: {:?}", STATE_FILE, why);
+        }
+    }
+
+    /// Settings for the guild a message came from. DMs get the defaults.
+    pub fn guild(&self, guild_id: Option<u64>) -> GuildConfig {
+        guild_id
+            .and_then(|id| self.guilds.get(&id))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn guilds(&self) -> impl Iterator<Item = (u64, &GuildConfig)> {
+        self.guilds.iter().map(|(id, guild)| (*id, guild))
+    }
+
+    pub fn guild_by_id(&self, guild_id: u64) -> GuildConfig {
+        self.guild(Some(guild_id))
+    }
+
+    pub fn guild_mut(&mut self, guild_id: u64) -> &mut GuildConfig {
+        self.guilds.entry(guild_id).or_default()
+    }
+
+    pub fn user(&self, user_id: u64) -> UserState {
+        self.users.get(&user_id).cloned().unwrap_or_default()
+    }
+
+    pub fn user_mut(&mut self, user_id: u64) -> &mut UserState {
+        self.users.entry(user_id).or_default()
+    }
+
+    pub fn track_reply(&mut self, reply: TrackedReply) {
+        self.replies.push(reply);
+    }
+
+    pub fn greeted(&self, guild: u64) -> bool {
+        self.greeted.contains(&guild)
+    }
+
+    pub fn mark_greeted(&mut self, guild: u64) {
+        self.greeted.insert(guild);
+    }
+
+    /// Remembers `query`, replacing any earlier one from the same user for
+    /// the same Tuesday so nobody gets followed up on twice.
+    pub fn track_query(&mut self, query: TrackedQuery) {
+        self.queries
+            .retain(|tracked| tracked.user_id != query.user_id || tracked.tuesday != query.tuesday);
+        self.queries.push(query);
+    }
+
+    /// Stops tracking every query that's due or expired at `now`, and
+    /// returns the due ones.
+    pub fn take_arrived_queries(&mut self, now: DateTime<Utc>) -> Vec<TrackedQuery> {
+        let (done, waiting): (Vec<TrackedQuery>, Vec<TrackedQuery>) = self
+            .queries
+            .drain(..)
+            .partition(|query| query.is_due(now) || query.is_expired(now));
+        self.queries = waiting;
+        done.into_iter().filter(|query| query.is_due(now)).collect()
+    }
+
+    /// Stops tracking every reply that is stale at `now`, and returns them.
+    pub fn take_stale_replies(&mut self, now: DateTime<Utc>, age: Duration) -> Vec<TrackedReply> {
+        let (stale, fresh) = self
+            .replies
+            .drain(..)
+            .partition(|reply| reply.is_stale(now, age));
+        self.replies = fresh;
+        stale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(day: u32, hour: u32) -> DateTime<Utc> {
+        DateTime::from_utc(NaiveDate::from_ymd(2024, 1, day).and_hms(hour, 0, 0), Utc)
+    }
+
+    fn reply(message_id: u64, tuesday: DateTime<Utc>) -> TrackedReply {
+        TrackedReply {
+            channel_id: 1,
+            message_id,
+            tuesday,
+        }
+    }
+
+    #[test]
+    fn takes_only_the_stale_replies() {
+        let mut state = BotState::default();
+        state.track_reply(reply(1, utc(2, 0)));
+        state.track_reply(reply(2, utc(9, 0)));
+        let stale = state.take_stale_replies(utc(3, 0), Duration::hours(12));
+        let stale: Vec<u64> = stale.iter().map(|reply| reply.message_id).collect();
+        assert_eq!(stale, vec![1]);
+        // What's left is still tracked, until it goes stale too
+        let later = state.take_stale_replies(utc(10, 0), Duration::hours(12));
+        assert_eq!(later.len(), 1);
+        assert!(state
+            .take_stale_replies(utc(20, 0), Duration::hours(12))
+            .is_empty());
+    }
+
+    #[test]
+    fn a_reply_goes_stale_once_its_age_is_up() {
+        let reply = reply(1, utc(2, 0));
+        assert!(!reply.is_stale(utc(2, 11), Duration::hours(12)));
+        assert!(reply.is_stale(utc(2, 12), Duration::hours(12)));
+    }
+
+    fn query(message_id: u64, user_id: u64, tuesday: DateTime<Utc>) -> TrackedQuery {
+        TrackedQuery {
+            channel_id: 1,
+            message_id,
+            user_id,
+            tuesday,
+        }
+    }
+
+    #[test]
+    fn a_query_is_due_only_on_its_tuesday() {
+        let query = query(1, 1, utc(2, 0));
+        assert!(!query.is_due(utc(1, 23)));
+        assert!(query.is_due(utc(2, 0)));
+        assert!(query.is_due(utc(2, 23)));
+        assert!(!query.is_due(utc(3, 0)));
+        assert!(query.is_expired(utc(3, 0)));
+    }
+
+    #[test]
+    fn takes_due_queries_and_drops_expired_ones() {
+        let mut state = BotState::default();
+        state.track_query(query(1, 1, utc(2, 0)));
+        state.track_query(query(2, 2, utc(9, 0)));
+        let due = state.take_arrived_queries(utc(2, 12));
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].message_id, 1);
+        // Missed entirely, so there's nothing to follow up on
+        assert!(state.take_arrived_queries(utc(11, 0)).is_empty());
+        assert!(state.take_arrived_queries(utc(9, 12)).is_empty());
+    }
+
+    #[test]
+    fn tracks_one_query_per_user_and_tuesday() {
+        let mut state = BotState::default();
+        state.track_query(query(1, 1, utc(2, 0)));
+        state.track_query(query(2, 1, utc(2, 0)));
+        let due = state.take_arrived_queries(utc(2, 12));
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].message_id, 2);
+    }
+
+    #[test]
+    fn unknown_users_get_default_settings() {
+        let mut state = BotState::default();
+        assert_eq!(state.user(1).utc_offset_minutes, None);
+        state.user_mut(1).utc_offset_minutes = Some(-300);
+        assert_eq!(state.user(1).utc_offset_minutes, Some(-300));
+        assert_eq!(state.user(2).utc_offset_minutes, None);
+    }
+
+    #[test]
+    fn picks_the_proximity_emoji_by_hours_left() {
+        let proximity = Proximity::default();
+        assert_eq!(proximity.emoji(Duration::hours(100)), "😴");
+        assert_eq!(proximity.emoji(Duration::hours(48)), "😴");
+        assert_eq!(proximity.emoji(Duration::hours(47)), "🙂");
+        assert_eq!(proximity.emoji(Duration::hours(5)), "🤩");
+        assert_eq!(proximity.emoji(Duration::zero()), "🎉");
+    }
+
+    #[test]
+    fn picks_the_clock_for_a_locale() {
+        assert_eq!(TimeFormat::for_locale("en-US"), TimeFormat::TwelveHour);
+        assert_eq!(TimeFormat::for_locale("en-GB"), TimeFormat::TwentyFourHour);
+        assert_eq!(TimeFormat::for_locale("de"), TimeFormat::TwentyFourHour);
+    }
+
+    #[test]
+    fn maintenance_is_off_in_older_state() {
+        let state: BotState = serde_json::from_str(r#"{"guilds": {}}"#).unwrap();
+        assert!(!state.maintenance);
+    }
+
+    #[test]
+    fn maintenance_survives_a_restart() {
+        let state = BotState {
+            maintenance: true,
+            ..BotState::default()
+        };
+        let saved = serde_json::to_string(&state).unwrap();
+        let loaded: BotState = serde_json::from_str(&saved).unwrap();
+        assert!(loaded.maintenance);
+    }
+
+    #[test]
+    fn counts_holidays_from_the_guild_calendar() {
+        let guild = GuildConfig {
+            holiday_calendar: Some("uk".to_string()),
+            ..GuildConfig::default()
+        };
+        assert!(guild.is_holiday(NaiveDate::from_ymd(2029, 12, 25)));
+        assert!(!guild.is_holiday(NaiveDate::from_ymd(2029, 7, 4)));
+    }
+
+    #[test]
+    fn moves_leap_days_outside_leap_years() {
+        assert_eq!(
+            LeapDayPolicy::Clamp.date_in(2025, 2, 29),
+            Some(NaiveDate::from_ymd(2025, 2, 28))
+        );
+        assert_eq!(
+            LeapDayPolicy::Roll.date_in(2025, 2, 29),
+            Some(NaiveDate::from_ymd(2025, 3, 1))
+        );
+        // In a leap year Feb 29 is just Feb 29
+        assert_eq!(
+            LeapDayPolicy::Roll.date_in(2024, 2, 29),
+            Some(NaiveDate::from_ymd(2024, 2, 29))
+        );
+        assert_eq!(LeapDayPolicy::Clamp.date_in(2025, 4, 31), None);
+    }
+
+    #[test]
+    fn counts_yearly_holidays_every_year() {
+        let guild = GuildConfig {
+            yearly_holidays: vec![(10, 31), (2, 29)],
+            leap_day: LeapDayPolicy::Roll,
+            ..GuildConfig::default()
+        };
+        assert!(guild.is_holiday(NaiveDate::from_ymd(2024, 10, 31)));
+        assert!(guild.is_holiday(NaiveDate::from_ymd(2031, 10, 31)));
+        assert!(guild.is_holiday(NaiveDate::from_ymd(2025, 3, 1)));
+        assert!(!guild.is_holiday(NaiveDate::from_ymd(2024, 3, 1)));
+    }
+
+    #[test]
+    fn counts_asks_per_tuesday() {
+        let mut user = UserState::default();
+        let tuesday = NaiveDate::from_ymd(2024, 1, 2);
+        assert_eq!(user.ask_yet(tuesday), 1);
+        assert_eq!(user.ask_yet(tuesday), 2);
+        // The next Tuesday starts the count over
+        assert_eq!(user.ask_yet(NaiveDate::from_ymd(2024, 1, 9)), 1);
+    }
+}
@@ -0,0 +1,125 @@
+use chrono::NaiveDateTime;
+
+use crate::{dates::next_tuesday, format::format_timer, timezone::Zone};
+
+/// Discord's limit on message length
+pub const MESSAGE_LIMIT: usize = 2000;
+
+/// What `tue!allstatus` reports for one guild
+pub struct GuildStatus {
+    pub id: u64,
+    pub name: String,
+    pub role: Option<u64>,
+    pub zone: Zone,
+}
+
+impl GuildStatus {
+    /// One line of the report, counting down from `now` in the guild's zone
+    pub fn line(&self, now: NaiveDateTime) -> String {
+        let until = next_tuesday(now)
+            .signed_duration_since(now)
+            .num_milliseconds();
+        let countdown = if until <= 0 {
+            "it's Tuesday".to_string()
+        } else {
+            format!("{} to go", format_timer(until))
+        };
+        let role = match self.role {
+            Some(role) => format!("role {}", role),
+            None => "no role".to_string(),
+        };
+        format!(
+            "**{}** ({}): {}, {}, {}",
+            self.name,
+            self.id,
+            role,
+            self.zone.name(),
+            countdown
+        )
+    }
+}
+
+/// Joins `lines` into as few messages as fit under `limit` characters. A
+/// single line that's too long on its own gets cut short.
+pub fn chunk_lines(lines: &[String], limit: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for line in lines {
+        let line: String = line.chars().take(limit).collect();
+        let needed = if current.is_empty() {
+            line.chars().count()
+        } else {
+            current.chars().count() + 1 + line.chars().count()
+        };
+        if needed > limit {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(&line);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// One line of the `tue!allstatus` report per guild, sorted by name
+pub fn status_lines(mut guilds: Vec<GuildStatus>) -> Vec<String> {
+    guilds.sort_by_key(|guild| guild.name.to_lowercase());
+    guilds
+        .iter()
+        .map(|guild| guild.line(guild.zone.now()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn status(name: &str, role: Option<u64>) -> GuildStatus {
+        GuildStatus {
+            id: 7,
+            name: name.to_string(),
+            role,
+            zone: Zone::from_minutes(60).unwrap(),
+        }
+    }
+
+    #[test]
+    fn reports_a_guilds_countdown() {
+        let now = NaiveDate::from_ymd(2024, 1, 3).and_hms(12, 0, 0);
+        assert_eq!(
+            status("Tuesday Club", Some(9)).line(now),
+            "**Tuesday Club** (7): role 9, UTC+01:00, 5d 12h to go"
+        );
+        let tuesday = NaiveDate::from_ymd(2024, 1, 2).and_hms(12, 0, 0);
+        assert_eq!(
+            status("Quiet", None).line(tuesday),
+            "**Quiet** (7): no role, UTC+01:00, it's Tuesday"
+        );
+    }
+
+    #[test]
+    fn sorts_guilds_by_name() {
+        let lines = status_lines(vec![
+            status("b", None),
+            status("A", None),
+            status("c", None),
+        ]);
+        let names: Vec<&str> = lines.iter().map(|line| &line[2..3]).collect();
+        assert_eq!(names, vec!["A", "b", "c"]);
+    }
+
+    #[test]
+    fn packs_lines_into_as_few_messages_as_fit() {
+        let lines: Vec<String> = ["aaaa", "bbb", "cc", "dddddddddd"]
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        assert_eq!(chunk_lines(&lines, 8), vec!["aaaa\nbbb", "cc", "dddddddd"]);
+        assert!(chunk_lines(&[], 8).is_empty());
+    }
+}
@@ -0,0 +1,264 @@
+use std::ops::Range;
+
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+use regex::{Captures, Regex};
+
+use crate::{
+    dates::days_until_weekday,
+    state::{GuildConfig, MultiDayPolicy},
+};
+
+/// Finds a time of day attached to "Tuesday" in a query.
+///
+/// Precedence, from strongest to weakest:
+/// 1. A time right after the weekday: "tuesday 5", "tuesday at 5pm",
+///    "tues at 17:30". A bare hour only counts at the end of a clause, so
+///    "tuesday 5 minutes from now" isn't read as 5 o'clock.
+/// 2. A time right before it: "5pm tuesday", "5:30 on tuesday". This needs
+///    am/pm, o'clock, or minutes, since "5 tuesdays" is a count.
+/// 3. The guild's configured occasion time.
+/// 4. Midnight, when the day starts.
+pub struct TimeOfDayRegex {
+    after: Regex,
+    before: Regex,
+    /// A time on its own, for commands that take one
+    clock: Regex,
+}
+
+impl Default for TimeOfDayRegex {
+    fn default() -> Self {
+        TimeOfDayRegex::new()
+    }
+}
+
+impl TimeOfDayRegex {
+    pub fn new() -> TimeOfDayRegex {
+        let after = Regex::new(
+            r"\btues(?:day)?\s+(?:at\s+)?(\d{1,2})(?::(\d{2}))?(?:\s*(am|pm|o'clock)\b|\s*(?:$|[?.!,;]))",
+        )
+        .expect("Error building time of day regex");
+        let before =
+            Regex::new(r"\b(\d{1,2})(?::(\d{2}))?\s*(am|pm|o'clock)?\s+(?:on\s+)?tues(?:day)?\b")
+                .expect("Error building time of day regex");
+        let clock = Regex::new(r"^(\d{1,2})(?::(\d{2}))?\s*(am|pm|o'clock)?$")
+            .expect("Error building clock regex");
+        TimeOfDayRegex {
+            after,
+            before,
+            clock,
+        }
+    }
+
+    /// The time of day `s` (already lowercase) asks about, if it names one
+    pub fn parse(&self, s: &str) -> Option<NaiveTime> {
+        if let Some(time) = self.after.captures(s).and_then(|caps| to_time(&caps)) {
+            return Some(time);
+        }
+        self.before
+            .captures(s)
+            .filter(|caps| caps.get(2).is_some() || caps.get(3).is_some())
+            .and_then(|caps| to_time(&caps))
+    }
+
+    /// Reads a standalone time like "5pm", "17:30", or "9 o'clock"
+    pub fn parse_clock(&self, s: &str) -> Option<NaiveTime> {
+        self.clock
+            .captures(&s.trim().to_ascii_lowercase())
+            .and_then(|caps| to_time(&caps))
+    }
+}
+
+fn to_time(caps: &Captures) -> Option<NaiveTime> {
+    let hour: u32 = caps.get(1)?.as_str().parse().ok()?;
+    let minute: u32 = match caps.get(2) {
+        Some(minute) => minute.as_str().parse().ok()?,
+        None => 0,
+    };
+    let hour = match caps.get(3).map(|suffix| suffix.as_str()) {
+        Some("am") if (1..=12).contains(&hour) => hour % 12,
+        Some("pm") if (1..=12).contains(&hour) => hour % 12 + 12,
+        Some("am") | Some("pm") => return None,
+        _ => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+/// When on Tuesday the countdown for `s` should end, following the
+/// precedence on `TimeOfDayRegex`
+pub fn target_time(s: &str, regex: &TimeOfDayRegex, guild: &GuildConfig) -> NaiveTime {
+    regex
+        .parse(s)
+        .or(guild.occasion_time)
+        .unwrap_or_else(|| NaiveTime::from_hms(0, 0, 0))
+}
+
+/// How each weekday can be written. "tues" covers "tuesday" too, and is what
+/// triggers the bot in the first place.
+const WEEKDAY_NAMES: [(&str, Weekday); 7] = [
+    ("monday", Weekday::Mon),
+    ("tues", Weekday::Tue),
+    ("wednesday", Weekday::Wed),
+    ("thursday", Weekday::Thu),
+    ("friday", Weekday::Fri),
+    ("saturday", Weekday::Sat),
+    ("sunday", Weekday::Sun),
+];
+
+/// Ordinals that can pick out one Tuesday of the month, like "2nd tuesday"
+const ORDINALS: [(&str, u8); 10] = [
+    ("1st", 1),
+    ("first", 1),
+    ("2nd", 2),
+    ("second", 2),
+    ("3rd", 3),
+    ("third", 3),
+    ("4th", 4),
+    ("fourth", 4),
+    ("5th", 5),
+    ("fifth", 5),
+];
+
+/// Which Tuesday of the month the lowercase `s` asks about, if it says
+/// something like "the 2nd tuesday", along with the ordinal as written and
+/// where it is in `s`, so exactly that can be kept out of unit parsing
+/// ("second" isn't seconds here, but it is in "seconds until the second
+/// tuesday").
+pub fn nth_tuesday(s: &str) -> Option<(u8, &'static str, Range<usize>)> {
+    ORDINALS.iter().find_map(|&(ordinal, n)| {
+        let start = s.find(&format!("{} tues", ordinal))?;
+        Some((n, ordinal, start..start + ordinal.len()))
+    })
+}
+
+/// Every weekday the lowercase `s` names, in the order they first appear
+pub fn mentioned_weekdays(s: &str) -> Vec<Weekday> {
+    let mut found: Vec<(usize, Weekday)> = WEEKDAY_NAMES
+        .iter()
+        .filter_map(|&(name, day)| Some((s.find(name)?, day)))
+        .collect();
+    found.sort_by_key(|&(position, _)| position);
+    found.into_iter().map(|(_, day)| day).collect()
+}
+
+/// Which of the `mentioned` days to count down to from `now`, soonest
+/// first. Nothing mentioned means Tuesday.
+pub fn pick_weekdays(
+    mentioned: &[Weekday],
+    now: NaiveDateTime,
+    policy: MultiDayPolicy,
+) -> Vec<Weekday> {
+    if mentioned.is_empty() {
+        return vec![Weekday::Tue];
+    }
+    let mut days = mentioned.to_vec();
+    days.sort_by_key(|&day| days_until_weekday(now.weekday(), day));
+    match policy {
+        MultiDayPolicy::Earliest => days.truncate(1),
+        MultiDayPolicy::All => {}
+    }
+    days
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hour: u32, minute: u32) -> Option<NaiveTime> {
+        Some(NaiveTime::from_hms(hour, minute, 0))
+    }
+
+    #[test]
+    fn reads_times_after_the_weekday() {
+        let regex = TimeOfDayRegex::new();
+        assert_eq!(regex.parse("tuesday at 5pm"), time(17, 0));
+        assert_eq!(regex.parse("tues 17:30?"), time(17, 30));
+        assert_eq!(regex.parse("until tuesday 5"), time(5, 0));
+        assert_eq!(regex.parse("tuesday 9 o'clock"), time(9, 0));
+    }
+
+    #[test]
+    fn reads_times_before_the_weekday_only_when_clear() {
+        let regex = TimeOfDayRegex::new();
+        assert_eq!(regex.parse("5pm tuesday"), time(17, 0));
+        assert_eq!(regex.parse("5:30 on tuesday"), time(5, 30));
+        assert_eq!(regex.parse("5 tuesdays"), None);
+    }
+
+    #[test]
+    fn does_not_read_counts_as_times() {
+        let regex = TimeOfDayRegex::new();
+        assert_eq!(regex.parse("tuesday 5 minutes from now"), None);
+        assert_eq!(regex.parse("tuesday at 13pm"), None);
+    }
+
+    #[test]
+    fn prefers_the_query_over_the_occasion_time() {
+        let regex = TimeOfDayRegex::new();
+        let guild = GuildConfig {
+            occasion_time: time(18, 0),
+            ..GuildConfig::default()
+        };
+        assert_eq!(
+            target_time("tuesday at 9am", &regex, &guild),
+            time(9, 0).unwrap()
+        );
+        assert_eq!(target_time("tuesday", &regex, &guild), time(18, 0).unwrap());
+        assert_eq!(
+            target_time("tuesday", &regex, &GuildConfig::default()),
+            time(0, 0).unwrap()
+        );
+    }
+
+    /// A Wednesday at noon
+    fn wednesday() -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd(2024, 1, 3).and_hms(12, 0, 0)
+    }
+
+    #[test]
+    fn lists_weekdays_in_the_order_they_appear() {
+        assert_eq!(
+            mentioned_weekdays("friday or tuesday, not monday"),
+            vec![Weekday::Fri, Weekday::Tue, Weekday::Mon]
+        );
+        assert!(mentioned_weekdays("next week").is_empty());
+    }
+
+    #[test]
+    fn picks_the_soonest_weekday() {
+        let mentioned = [Weekday::Tue, Weekday::Fri];
+        assert_eq!(
+            pick_weekdays(&mentioned, wednesday(), MultiDayPolicy::Earliest),
+            vec![Weekday::Fri]
+        );
+        assert_eq!(
+            pick_weekdays(&mentioned, wednesday(), MultiDayPolicy::All),
+            vec![Weekday::Fri, Weekday::Tue]
+        );
+    }
+
+    #[test]
+    fn picks_tuesday_when_nothing_is_mentioned() {
+        assert_eq!(
+            pick_weekdays(&[], wednesday(), MultiDayPolicy::All),
+            vec![Weekday::Tue]
+        );
+    }
+
+    #[test]
+    fn finds_the_ordinal_and_where_it_is() {
+        assert_eq!(
+            nth_tuesday("seconds until the second tuesday"),
+            Some((2, "second", 18..24))
+        );
+        assert_eq!(nth_tuesday("the 3rd tues"), Some((3, "3rd", 4..7)));
+        assert_eq!(nth_tuesday("second to tuesday"), None);
+    }
+
+    #[test]
+    fn reads_standalone_clock_times() {
+        let regex = TimeOfDayRegex::new();
+        assert_eq!(regex.parse_clock(" 5PM "), time(17, 0));
+        assert_eq!(regex.parse_clock("12am"), time(0, 0));
+        assert_eq!(regex.parse_clock("24:00"), None);
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Utc};
+use serenity::{
+    model::id::{GuildId, UserId},
+    prelude::*,
+};
+
+use crate::{clock, config::Config, state::GuildConfig, ConfigKey, StateKey};
+
+/// The timezone "now" and "Tuesday" are measured in for a guild. We only
+/// know fixed UTC offsets, so anyone observing daylight saving time has to
+/// update theirs twice a year.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Zone {
+    /// Whatever timezone the bot's host is set to
+    Host,
+    Fixed(FixedOffset),
+}
+
+impl Zone {
+    pub fn from_minutes(minutes: i32) -> Option<Zone> {
+        FixedOffset::east_opt(minutes * 60).map(Zone::Fixed)
+    }
+
+    pub fn now(self) -> NaiveDateTime {
+        self.to_local(clock::now())
+    }
+
+    pub fn to_local(self, time: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Zone::Host => time.with_timezone(&Local).naive_local(),
+            Zone::Fixed(offset) => time.with_timezone(&offset).naive_local(),
+        }
+    }
+
+    pub fn to_utc(self, time: NaiveDateTime) -> DateTime<Utc> {
+        match self {
+            // Local times skipped by a DST change don't exist, so fall back
+            // to pretending they're UTC rather than failing
+            Zone::Host => Local
+                .from_local_datetime(&time)
+                .earliest()
+                .map(|time| time.with_timezone(&Utc))
+                .unwrap_or_else(|| DateTime::from_utc(time, Utc)),
+            Zone::Fixed(offset) => DateTime::from_utc(time - offset, Utc),
+        }
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            Zone::Host => "server time".to_string(),
+            Zone::Fixed(offset) => format_utc_offset(offset),
+        }
+    }
+}
+
+/// Reads offsets like "UTC+2", "+05:30", "-0800", "GMT", or "utc-3:30"
+pub fn parse_utc_offset(s: &str) -> Option<FixedOffset> {
+    let lower = s.trim().to_ascii_lowercase();
+    let rest = lower
+        .trim_start_matches("utc")
+        .trim_start_matches("gmt")
+        .trim();
+    if rest.is_empty() {
+        return FixedOffset::east_opt(0);
+    }
+    let (sign, rest) = match rest.chars().next()? {
+        '+' => (1, &rest[1..]),
+        '-' => (-1, &rest[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = match rest.find(':') {
+        Some(colon) => (&rest[..colon], &rest[colon + 1..]),
+        None if rest.len() > 2 => (&rest[..rest.len() - 2], &rest[rest.len() - 2..]),
+        None => (rest, "0"),
+    };
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if hours > 14 || minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+pub fn format_utc_offset(offset: FixedOffset) -> String {
+    let secs = offset.local_minus_utc();
+    let sign = if secs < 0 { '-' } else { '+' };
+    let secs = secs.abs();
+    format!("UTC{}{:02}:{:02}", sign, secs / 3600, secs % 3600 / 60)
+}
+
+/// Picks the timezone for a guild: its own setting if it has one, then the
+/// operator's default, then the host's.
+pub fn resolve_zone(config: &Config, guild: &GuildConfig, hint: Option<i32>) -> Zone {
+    resolve_zone_source(None, config, guild, hint).0
+}
+
+/// Where a resolved timezone came from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ZoneSource {
+    User,
+    Guild,
+    Config,
+    Guessed,
+    Host,
+}
+
+impl ZoneSource {
+    pub fn describe(self) -> &'static str {
+        match self {
+            ZoneSource::User => "your own setting",
+            ZoneSource::Guild => "this server's setting",
+            ZoneSource::Config => "the bot's default",
+            ZoneSource::Guessed => "a guess from this server's region or language",
+            ZoneSource::Host => "the bot's server clock",
+        }
+    }
+}
+
+/// Resolves a timezone in order: the user's own, then the guild's, then
+/// the operator's, then the guess from `hint`, then the host's.
+pub fn resolve_zone_source(
+    user: Option<i32>,
+    config: &Config,
+    guild: &GuildConfig,
+    hint: Option<i32>,
+) -> (Zone, ZoneSource) {
+    let candidates = [
+        (user, ZoneSource::User),
+        (guild.utc_offset_minutes, ZoneSource::Guild),
+        (config.utc_offset_minutes, ZoneSource::Config),
+        (hint, ZoneSource::Guessed),
+    ];
+    candidates
+        .iter()
+        .find_map(|&(minutes, source)| Some((Zone::from_minutes(minutes?)?, source)))
+        .unwrap_or((Zone::Host, ZoneSource::Host))
+}
+
+/// A best-effort guess at a guild's UTC offset, in minutes, from its voice
+/// region or else its preferred locale. It's only a guess: plenty of guilds
+/// aren't where their language suggests, and regions don't say anything
+/// about daylight saving time. Locales spoken across many timezones, like
+/// "en-US", don't give a guess at all.
+pub fn infer_utc_offset(region: &str, locale: &str) -> Option<i32> {
+    let from_region = match region {
+        "us-west" => Some(-8 * 60),
+        "us-central" | "us-south" => Some(-6 * 60),
+        "us-east" => Some(-5 * 60),
+        "brazil" => Some(-3 * 60),
+        "europe" | "eu-west" | "london" => Some(0),
+        "eu-central" | "amsterdam" | "frankfurt" => Some(60),
+        "southafrica" => Some(2 * 60),
+        "russia" => Some(3 * 60),
+        "dubai" => Some(4 * 60),
+        "india" => Some(5 * 60 + 30),
+        "singapore" | "hongkong" => Some(8 * 60),
+        "japan" | "south-korea" => Some(9 * 60),
+        "sydney" => Some(10 * 60),
+        _ => None,
+    };
+    from_region.or(match locale {
+        "en-GB" | "pt-PT" => Some(0),
+        "da" | "de" | "es-ES" | "fr" | "hr" | "hu" | "it" | "nl" | "no" | "pl" | "sv-SE" | "cs" => {
+            Some(60)
+        }
+        "bg" | "el" | "fi" | "lt" | "ro" | "uk" => Some(2 * 60),
+        "ru" | "tr" => Some(3 * 60),
+        "hi" => Some(5 * 60 + 30),
+        "th" | "vi" => Some(7 * 60),
+        "zh-CN" | "zh-TW" => Some(8 * 60),
+        "ja" | "ko" => Some(9 * 60),
+        "pt-BR" => Some(-3 * 60),
+        _ => None,
+    })
+}
+
+/// Guessed UTC offsets by guild ID, filled in as guilds arrive from the
+/// gateway
+pub struct ZoneHintKey;
+impl TypeMapKey for ZoneHintKey {
+    type Value = HashMap<u64, i32>;
+}
+
+/// `resolve_zone` for a guild, straight from the context data
+pub fn zone_for(data: &ShareMap, guild_id: Option<GuildId>) -> Zone {
+    zone_source_for(data, guild_id, None).0
+}
+
+/// `resolve_zone_source` for a user in a guild, straight from the context
+/// data
+pub fn zone_source_for(
+    data: &ShareMap,
+    guild_id: Option<GuildId>,
+    user_id: Option<UserId>,
+) -> (Zone, ZoneSource) {
+    let config = data
+        .get::<ConfigKey>()
+        .expect("Expected to find config in context");
+    let guild = data
+        .get::<StateKey>()
+        .expect("Expected to find bot state in context")
+        .guild(guild_id.map(|id| id.0));
+    let user = user_id.and_then(|id| {
+        data.get::<StateKey>()
+            .expect("Expected to find bot state in context")
+            .user(id.0)
+            .utc_offset_minutes
+    });
+    let hint = guild_id.and_then(|id| {
+        data.get::<ZoneHintKey>()
+            .and_then(|hints| hints.get(&id.0))
+            .copied()
+    });
+    resolve_zone_source(user, config, &guild, hint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minutes(offset: FixedOffset) -> i32 {
+        offset.local_minus_utc() / 60
+    }
+
+    #[test]
+    fn reads_the_usual_offset_spellings() {
+        assert_eq!(parse_utc_offset("UTC+2").map(minutes), Some(120));
+        assert_eq!(parse_utc_offset("+05:30").map(minutes), Some(330));
+        assert_eq!(parse_utc_offset("-0800").map(minutes), Some(-480));
+        assert_eq!(parse_utc_offset("GMT").map(minutes), Some(0));
+        assert_eq!(parse_utc_offset("utc-3:30").map(minutes), Some(-210));
+    }
+
+    #[test]
+    fn rejects_impossible_offsets() {
+        assert_eq!(parse_utc_offset("UTC+15"), None);
+        assert_eq!(parse_utc_offset("+02:60"), None);
+        assert_eq!(parse_utc_offset("tuesday"), None);
+    }
+
+    #[test]
+    fn guesses_from_the_region_before_the_locale() {
+        assert_eq!(infer_utc_offset("japan", "en-GB"), Some(9 * 60));
+        assert_eq!(infer_utc_offset("rotterdam", "en-GB"), Some(0));
+        assert_eq!(infer_utc_offset("", "hi"), Some(5 * 60 + 30));
+    }
+
+    #[test]
+    fn has_no_guess_for_widespread_locales() {
+        assert_eq!(infer_utc_offset("", "en-US"), None);
+        assert_eq!(infer_utc_offset("", "es-419"), None);
+    }
+
+    #[test]
+    fn only_falls_back_to_the_guess() {
+        let guild = GuildConfig {
+            utc_offset_minutes: Some(60),
+            ..GuildConfig::default()
+        };
+        let config = Config::default();
+        assert_eq!(
+            resolve_zone_source(None, &config, &guild, Some(540)),
+            (Zone::from_minutes(60).unwrap(), ZoneSource::Guild)
+        );
+        assert_eq!(
+            resolve_zone_source(None, &config, &GuildConfig::default(), Some(540)),
+            (Zone::from_minutes(540).unwrap(), ZoneSource::Guessed)
+        );
+        assert_eq!(
+            resolve_zone_source(None, &config, &GuildConfig::default(), None),
+            (Zone::Host, ZoneSource::Host)
+        );
+    }
+
+    #[test]
+    fn a_users_own_zone_comes_first() {
+        let guild = GuildConfig {
+            utc_offset_minutes: Some(60),
+            ..GuildConfig::default()
+        };
+        let (zone, source) = resolve_zone_source(Some(-300), &Config::default(), &guild, None);
+        assert_eq!(zone, Zone::from_minutes(-300).unwrap());
+        assert_eq!(source.describe(), "your own setting");
+    }
+
+    #[test]
+    fn formats_offsets_back_out() {
+        let offset = parse_utc_offset("-3:30").unwrap();
+        assert_eq!(format_utc_offset(offset), "UTC-03:30");
+        assert_eq!(Zone::Fixed(offset).name(), "UTC-03:30");
+    }
+}
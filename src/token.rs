@@ -0,0 +1,60 @@
+/// Tokens people leave in oauth2.tok from examples and templates, compared
+/// ignoring case, with `-` and spaces treated as `_`
+const PLACEHOLDERS: [&str; 8] = [
+    "your_token_here",
+    "your_bot_token",
+    "your_bot_token_here",
+    "insert_token_here",
+    "token_here",
+    "token",
+    "<token>",
+    "changeme",
+];
+
+/// Trims the token read from oauth2.tok, or explains why it can't be a real
+/// token. Connecting with one of these would only get an opaque
+/// authentication failure back from Discord.
+pub fn clean_token(raw: &str) -> Result<&str, String> {
+    let token = raw.trim();
+    if token.is_empty() {
+        return Err(
+            "oauth2.tok is empty. Paste your bot's token from the Discord \
+                    developer portal into it."
+                .to_string(),
+        );
+    }
+    let normalized = token.to_ascii_lowercase().replace(['-', ' '], "_");
+    let all_x = token.chars().all(|c| c == 'x' || c == 'X' || c == '.');
+    if PLACEHOLDERS.contains(&normalized.as_str()) || all_x {
+        return Err(format!(
+            "oauth2.tok still holds the placeholder {:?}. Replace it with your bot's \
+             token from the Discord developer portal.",
+            token
+        ));
+    }
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trims_real_tokens() {
+        assert_eq!(clean_token("  abc.def.ghi\n"), Ok("abc.def.ghi"));
+    }
+
+    #[test]
+    fn rejects_empty_tokens() {
+        assert!(clean_token("").is_err());
+        assert!(clean_token(" \n\t").is_err());
+    }
+
+    #[test]
+    fn rejects_placeholders() {
+        assert!(clean_token("YOUR-TOKEN-HERE").is_err());
+        assert!(clean_token("your bot token\n").is_err());
+        assert!(clean_token("<token>").is_err());
+        assert!(clean_token("XXXX.xxxx.XXXX").is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use chrono::NaiveDateTime;
+use serenity::{model::id::ChannelId, prelude::*};
+
+use crate::{
+    dates::next_tuesday,
+    state::GuildConfig,
+    timezone::{resolve_zone, ZoneHintKey},
+    ConfigKey, StateKey,
+};
+
+/// What we last set each countdown channel's topic to, and when
+#[derive(Default)]
+pub struct TopicTracker {
+    last_update: HashMap<u64, (Instant, String)>,
+}
+
+impl TopicTracker {
+    /// Whether `channel` should get its topic changed to `topic`. Discord
+    /// only allows a couple of topic edits per channel every ten minutes, so
+    /// we skip anything that isn't a change and wait out `interval` between
+    /// edits.
+    pub fn should_update(
+        &self,
+        channel: u64,
+        topic: &str,
+        interval: Duration,
+        now: Instant,
+    ) -> bool {
+        match self.last_update.get(&channel) {
+            Some((_, last_topic)) if last_topic == topic => false,
+            Some((last, _)) => now.duration_since(*last) >= interval,
+            None => true,
+        }
+    }
+
+    pub fn record(&mut self, channel: u64, topic: String, now: Instant) {
+        self.last_update.insert(channel, (now, topic));
+    }
+}
+
+pub struct TopicTrackerKey;
+impl TypeMapKey for TopicTrackerKey {
+    type Value = TopicTracker;
+}
+
+/// The countdown as a channel topic. This is only updated every few minutes,
+/// so it sticks to whole hours.
+pub fn topic_text(now: NaiveDateTime, guild: &GuildConfig) -> String {
+    let occasion = guild.occasion_name();
+    let until = next_tuesday(now).signed_duration_since(now);
+    if until.num_milliseconds() <= 0 {
+        return format!("It's {}! 🎉", occasion);
+    }
+    let days = until.num_days();
+    let hours = until.num_hours() % 24;
+    let plural = |n: i64, unit: &str| {
+        if n == 1 {
+            format!("1 {}", unit)
+        } else {
+            format!("{} {}s", n, unit)
+        }
+    };
+    let countdown = match (days, hours) {
+        (0, 0) => "Less than an hour".to_string(),
+        (0, hours) => plural(hours, "hour"),
+        (days, 0) => plural(days, "day"),
+        (days, hours) => format!("{} and {}", plural(days, "day"), plural(hours, "hour")),
+    };
+    format!("🗓️ {} until {}", countdown, occasion)
+}
+
+/// Brings every configured countdown channel's topic up to date.
+pub fn update_topics(ctx: &Context) {
+    let updates: Vec<(u64, String)> = {
+        let mut data = ctx.data.write();
+        let config = data
+            .get::<ConfigKey>()
+            .expect("Expected to find config in context");
+        let interval = Duration::from_secs(config.topic_update_secs);
+        let hints = data.get::<ZoneHintKey>();
+        let wanted: Vec<(u64, String)> = data
+            .get::<StateKey>()
+            .expect("Expected to find bot state in context")
+            .guilds()
+            .filter_map(|(id, guild)| {
+                let hint = hints.and_then(|hints| hints.get(&id)).copied();
+                let now = resolve_zone(config, guild, hint).now();
+                Some((guild.topic_channel?, topic_text(now, guild)))
+            })
+            .collect();
+        let tracker = data
+            .get_mut::<TopicTrackerKey>()
+            .expect("Expected to find topic tracker in context");
+        let instant = Instant::now();
+        let mut updates = Vec::new();
+        for (channel, topic) in wanted {
+            if tracker.should_update(channel, &topic, interval, instant) {
+                // Record the attempt even if it fails, so a channel we can't
+                // edit doesn't get retried every tick
+                tracker.record(channel, topic.clone(), instant);
+                updates.push((channel, topic));
+            }
+        }
+        updates
+    };
+
+    for (channel, topic) in updates {
+        if let Err(why) = ChannelId(channel).edit(&ctx.http, |c| c.topic(&topic)) {
+            println!("Error updating topic of channel {}: {:?}", channel, why);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(2024, 1, day).and_hms(hour, 30, 0)
+    }
+
+    #[test]
+    fn counts_down_in_days_and_hours() {
+        let guild = GuildConfig::default();
+        assert_eq!(
+            topic_text(at(3, 12), &guild),
+            "🗓️ 5 days and 11 hours until Tuesday"
+        );
+        assert_eq!(topic_text(at(1, 22), &guild), "🗓️ 1 hour until Tuesday");
+        assert_eq!(
+            topic_text(at(1, 23), &guild),
+            "🗓️ Less than an hour until Tuesday"
+        );
+    }
+
+    #[test]
+    fn celebrates_on_tuesday() {
+        assert_eq!(
+            topic_text(at(2, 12), &GuildConfig::default()),
+            "It's Tuesday! 🎉"
+        );
+    }
+
+    #[test]
+    fn only_updates_changed_topics_after_the_interval() {
+        let interval = Duration::from_secs(600);
+        let start = Instant::now();
+        let mut tracker = TopicTracker::default();
+        assert!(tracker.should_update(1, "a", interval, start));
+        tracker.record(1, "a".to_string(), start);
+        assert!(!tracker.should_update(1, "a", interval, start + interval));
+        assert!(!tracker.should_update(1, "b", interval, start + interval / 2));
+        assert!(tracker.should_update(1, "b", interval, start + interval));
+        assert!(tracker.should_update(2, "a", interval, start));
+    }
+}
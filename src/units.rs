@@ -0,0 +1,1155 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use rand::Rng;
+use regex::Regex;
+
+use crate::{
+    format::{format_thousands, format_value},
+    state::{GuildConfig, NumberStyle},
+    target::TimeOfDayRegex,
+};
+
+/// A built-in time unit: what matches it in a query, its names, and how
+/// long it is. Like `SiPrefix`, all in one place so nothing has to be kept
+/// in step by index.
+pub struct TimeUnit {
+    /// Regex alternatives, separated by `|`
+    pub pattern: &'static str,
+    pub singular: &'static str,
+    pub plural: &'static str,
+    /// The short form people write, like "hrs" without the s
+    pub abbreviation: &'static str,
+    pub seconds: i64,
+}
+
+impl TimeUnit {
+    /// Whether `word`, as the unit regex matched it, is this unit
+    fn matches(&self, word: &str) -> bool {
+        self.pattern.split('|').any(|alt| alt == word)
+    }
+}
+
+/// Every built-in time unit, shortest first
+pub const TIME_UNITS: [TimeUnit; 6] = [
+    TimeUnit {
+        pattern: r"sec",
+        singular: "second",
+        plural: "seconds",
+        abbreviation: "sec",
+        seconds: 1,
+    },
+    TimeUnit {
+        pattern: r"min",
+        singular: "minute",
+        plural: "minutes",
+        abbreviation: "min",
+        seconds: 60,
+    },
+    TimeUnit {
+        pattern: r"hour",
+        singular: "hour",
+        plural: "hours",
+        abbreviation: "hr",
+        seconds: 3600,
+    },
+    TimeUnit {
+        pattern: r"day",
+        singular: "day",
+        plural: "days",
+        abbreviation: "day",
+        seconds: 86400,
+    },
+    TimeUnit {
+        pattern: r"week",
+        singular: "week",
+        plural: "weeks",
+        abbreviation: "wk",
+        seconds: 604800,
+    },
+    TimeUnit {
+        pattern: r"year",
+        singular: "year",
+        plural: "years",
+        abbreviation: "yr",
+        seconds: 31557600,
+    },
+];
+
+/// The plural name of every built-in time unit, in `TIME_UNITS` order
+pub fn time_unit_names() -> Vec<&'static str> {
+    TIME_UNITS.iter().map(|unit| unit.plural).collect()
+}
+
+pub const DEFAULT_TIME_INDEX: usize = 2;
+
+/// "day" is hidden inside "tuesday", "today" and friends, so it only counts
+/// as a unit at the start of a word, right after a number like "10days", or
+/// right after an SI prefix
+const DAY_INDEX: usize = 3;
+
+/// Random units whose value lands outside this range are rerolled
+const READABLE_RANGE: (f64, f64) = (0.001, 1_000_000.0);
+const MAX_RANDOM_ATTEMPTS: usize = 10;
+
+/// An SI prefix: what matches it in a query, how it's written in a unit's
+/// name, and the power of ten it scales by. Kept together so there's no
+/// lining up separate lists by index.
+pub struct SiPrefix {
+    /// Regex alternatives, separated by `|` like in `TimeUnit`
+    pub pattern: &'static str,
+    pub name: &'static str,
+    pub power: i32,
+}
+
+impl SiPrefix {
+    /// Whether `word`, as the unit regex matched it, is this prefix
+    fn matches(&self, word: &str) -> bool {
+        self.pattern.split('|').any(|alt| alt == word)
+    }
+}
+
+/// Every SI prefix, smallest power first
+pub const SI_PREFIXES: [SiPrefix; 20] = [
+    SiPrefix {
+        pattern: r"yocto",
+        name: "yocto",
+        power: -24,
+    },
+    SiPrefix {
+        pattern: r"zepto",
+        name: "zepto",
+        power: -21,
+    },
+    SiPrefix {
+        pattern: r"atto",
+        name: "atto",
+        power: -18,
+    },
+    SiPrefix {
+        pattern: r"femto",
+        name: "femto",
+        power: -15,
+    },
+    SiPrefix {
+        pattern: r"pico",
+        name: "pico",
+        power: -12,
+    },
+    SiPrefix {
+        pattern: r"nano",
+        name: "nano",
+        power: -9,
+    },
+    SiPrefix {
+        pattern: r"micro",
+        name: "micro",
+        power: -6,
+    },
+    SiPrefix {
+        pattern: r"milli",
+        name: "milli",
+        power: -3,
+    },
+    SiPrefix {
+        pattern: r"centi",
+        name: "centi",
+        power: -2,
+    },
+    SiPrefix {
+        pattern: r"deci",
+        name: "deci",
+        power: -1,
+    },
+    SiPrefix {
+        pattern: r"deca",
+        name: "deca",
+        power: 1,
+    },
+    SiPrefix {
+        pattern: r"hecto",
+        name: "hecto",
+        power: 2,
+    },
+    SiPrefix {
+        pattern: r"kilo",
+        name: "kilo",
+        power: 3,
+    },
+    SiPrefix {
+        pattern: r"mega",
+        name: "mega",
+        power: 6,
+    },
+    SiPrefix {
+        pattern: r"giga",
+        name: "giga",
+        power: 9,
+    },
+    SiPrefix {
+        pattern: r"tera",
+        name: "tera",
+        power: 12,
+    },
+    SiPrefix {
+        pattern: r"peta",
+        name: "peta",
+        power: 15,
+    },
+    SiPrefix {
+        pattern: r"exa",
+        name: "exa",
+        power: 18,
+    },
+    SiPrefix {
+        pattern: r"zetta",
+        name: "zetta",
+        power: 21,
+    },
+    SiPrefix {
+        pattern: r"yotta",
+        name: "yotta",
+        power: 24,
+    },
+];
+
+/// The prefix with exactly `power`, if there is one
+fn prefix_with_power(power: i32) -> Option<usize> {
+    SI_PREFIXES.iter().position(|prefix| prefix.power == power)
+}
+
+/// A unit an operator made up, like a 900 second "coffee break"
+pub struct CustomUnit {
+    /// Lowercase, singular
+    pub name: String,
+    pub seconds: f64,
+}
+
+impl CustomUnit {
+    fn plural(&self) -> String {
+        if self.name.ends_with('s') {
+            self.name.clone()
+        } else {
+            format!("{}s", self.name)
+        }
+    }
+}
+
+/// Checks the configured custom units, leaving out (and logging) any that
+/// would be meaningless or shadow a built-in unit. Longer names come first,
+/// so "coffee break" wins over "coffee" when both are defined.
+pub fn load_custom_units(configured: &HashMap<String, f64>) -> Vec<CustomUnit> {
+    let mut units: Vec<CustomUnit> = Vec::new();
+    for (name, &seconds) in configured {
+        let name = name.trim().to_lowercase();
+        if !(seconds.is_finite() && seconds > 0.0) {
+            println!(
+                "Skipping custom unit {:?}: it needs a positive length",
+                name
+            );
+            continue;
+        }
+        let builtin = TIME_UNITS
+            .iter()
+            .map(|unit| &unit.plural)
+            .chain(SI_PREFIXES.iter().map(|prefix| &prefix.name))
+            .any(|builtin| {
+                builtin.contains(name.as_str()) || name.contains(builtin.trim_end_matches('s'))
+            });
+        if name.is_empty() || builtin {
+            println!(
+                "Skipping custom unit {:?}: it collides with a built-in unit",
+                name
+            );
+            continue;
+        }
+        if units.iter().any(|unit| unit.name == name) {
+            println!("Skipping custom unit {:?}: it's defined twice", name);
+            continue;
+        }
+        units.push(CustomUnit { name, seconds });
+    }
+    units.sort_by_key(|unit| Reverse(unit.name.len()));
+    units
+}
+
+/// One unit word or SI prefix found in a query, by its index into
+/// `TIME_UNITS` or `SI_PREFIXES` and its byte range
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Token {
+    pub index: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A number written in digits in a query, like the 2.5 in "2.5 hours"
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quantity {
+    pub value: f64,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Every time unit, SI prefix, and number in a query, in the order they
+/// appear
+#[derive(Debug, Default)]
+pub struct ParsedQuery {
+    pub times: Vec<Token>,
+    pub prefixes: Vec<Token>,
+    pub numbers: Vec<Quantity>,
+}
+
+impl ParsedQuery {
+    /// The SI prefixes directly in front of whatever starts at byte `start`
+    /// of `s`, allowing spaces between them, in reading order. So in
+    /// "kilo megaseconds" both prefixes belong to the seconds, but in
+    /// "kilo what seconds" neither does.
+    pub fn prefixes_before(&self, s: &str, start: usize) -> Vec<usize> {
+        self.attached_prefixes(s, start).0
+    }
+
+    /// The number directly in front of whatever starts at byte `start` of
+    /// `s`, and its prefixes, allowing spaces between. So in "3 kilo 5 days"
+    /// the 5 goes with the days, and the 3 with nothing.
+    pub fn number_before(&self, s: &str, start: usize) -> Option<f64> {
+        let (_, cursor) = self.attached_prefixes(s, start);
+        let number = self.numbers.iter().rev().find(|n| n.end <= cursor)?;
+        if s[number.end..cursor].trim().is_empty() {
+            Some(number.value)
+        } else {
+            None
+        }
+    }
+
+    /// `prefixes_before`, along with where the first of them starts
+    fn attached_prefixes(&self, s: &str, start: usize) -> (Vec<usize>, usize) {
+        let mut attached = Vec::new();
+        let mut cursor = start;
+        for prefix in self.prefixes.iter().rev() {
+            if prefix.end > cursor {
+                continue;
+            }
+            if !s[prefix.end..cursor].trim().is_empty() {
+                break;
+            }
+            attached.push(prefix.index);
+            cursor = prefix.start;
+        }
+        attached.reverse();
+        (attached, cursor)
+    }
+}
+
+pub struct CustomRegexCache {
+    /// Matches any SI prefix (group `si`), time unit (group `time`), or
+    /// number (group `number`), so a query is scanned once, left to right
+    unit_regex: Regex,
+    custom_units: Vec<CustomUnit>,
+    pub time_of_day: TimeOfDayRegex,
+}
+
+impl CustomRegexCache {
+    pub fn new(custom_units: Vec<CustomUnit>) -> CustomRegexCache {
+        let prefixes: Vec<&str> = SI_PREFIXES.iter().map(|prefix| prefix.pattern).collect();
+        let patterns: Vec<&str> = TIME_UNITS.iter().map(|unit| unit.pattern).collect();
+        let pattern = format!(
+            r"(?P<si>{})|(?P<time>{})|(?P<number>\d+(?:\.\d+)?)",
+            prefixes.join("|"),
+            patterns.join("|")
+        );
+        let unit_regex = Regex::new(&pattern).expect("Error building unit regex");
+
+        CustomRegexCache {
+            unit_regex,
+            custom_units,
+            time_of_day: TimeOfDayRegex::new(),
+        }
+    }
+}
+
+/// The unit a countdown gets reported in
+#[derive(Clone)]
+pub struct Unit {
+    /// Milliseconds in one of this unit
+    pub multiplier: f64,
+    pub name: String,
+    /// Only meaningful to the nearest whole unit, so shown rounded
+    pub approximate: bool,
+    /// How many SI prefixes in the query were left out of this unit
+    pub ignored_prefixes: usize,
+    /// Which of `TIME_UNITS` this is built on, for built-in units, so
+    /// a guild's display names can stand in for it
+    pub base: Option<usize>,
+}
+
+impl Unit {
+    /// Heartbeats at an average of `bpm` beats per minute
+    pub fn heartbeats(bpm: f64) -> Unit {
+        Unit {
+            multiplier: 60_000.0 / bpm,
+            name: "heartbeats".to_string(),
+            approximate: true,
+            ignored_prefixes: 0,
+            base: None,
+        }
+    }
+
+    pub fn value(&self, ms: i64) -> f64 {
+        ms as f64 / self.multiplier
+    }
+
+    pub fn render(&self, ms: i64, guild: &GuildConfig) -> String {
+        let name = self.display_name(guild);
+        let unprefixed = self
+            .base
+            .is_some_and(|base| self.name == TIME_UNITS[base].plural);
+        if guild.number_style == NumberStyle::Engineering && unprefixed && !self.approximate {
+            let (value, prefix) = engineering(self.value(ms));
+            let prefix = prefix.map_or("", |index| SI_PREFIXES[index].name);
+            // Three decimals is as precise as engineering notation gets
+            let value = (value * 1000.0).round() / 1000.0;
+            return format!("{} {}{}", value, prefix, name);
+        }
+        if self.approximate {
+            return format!("≈ {} {}", format_thousands(self.value(ms)), name);
+        }
+        format!("{} {}", format_value(self.value(ms), guild), name)
+    }
+
+    /// The name to show, with the guild's display name for the base unit
+    /// swapped in after any SI prefixes, so "millihours" can read "millihrs"
+    pub fn display_name(&self, guild: &GuildConfig) -> String {
+        let base = match self.base {
+            Some(base) if guild.unit_names.len() == TIME_UNITS.len() => base,
+            _ => return self.name.clone(),
+        };
+        match self.name.strip_suffix(TIME_UNITS[base].plural) {
+            Some(prefixes) => format!("{}{}", prefixes, guild.unit_names[base]),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// `value` in engineering notation: scaled by a power of 1000 to land between
+/// 1 and 1000, along with the index into `SI_PREFIXES` of the prefix for that
+/// power. No prefix means it needed no scaling, or was zero. Past yotta or
+/// yocto it's left as big or small as it has to be.
+pub fn engineering(value: f64) -> (f64, Option<usize>) {
+    if value == 0.0 || !value.is_finite() {
+        return (value, None);
+    }
+    let power = (value.abs().log10() / 3.0).floor() as i32 * 3;
+    let power = power
+        .max(SI_PREFIXES[0].power)
+        .min(SI_PREFIXES[SI_PREFIXES.len() - 1].power);
+    if power == 0 {
+        return (value, None);
+    }
+    (value / 10f64.powi(power), prefix_with_power(power))
+}
+
+/// Whether the guild lets countdowns be given in `unit`
+pub fn is_allowed(unit: &Unit, guild: &GuildConfig) -> bool {
+    let base = match unit.base {
+        Some(base) => base,
+        None => return true,
+    };
+    let unprefixed = unit.name == TIME_UNITS[base].plural;
+    (unprefixed || guild.si_prefixes_allowed)
+        && (guild.allowed_units.is_empty()
+            || guild
+                .allowed_units
+                .iter()
+                .any(|name| name == TIME_UNITS[base].plural))
+}
+
+/// The allowed plain unit closest in size to `unit`, for when `unit` itself
+/// isn't allowed. `None` if the guild allows nothing usable.
+pub fn nearest_allowed(unit: &Unit, guild: &GuildConfig) -> Option<Unit> {
+    let distance =
+        |candidate: &Unit| (candidate.multiplier.log10() - unit.multiplier.log10()).abs();
+    plain_units()
+        .into_iter()
+        .filter(|candidate| is_allowed(candidate, guild))
+        .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+}
+
+/// Finds every time unit, SI prefix, and number in `s`, with where they are
+pub fn parse_query(s: &str, regex_cache: &CustomRegexCache) -> ParsedQuery {
+    let mut parsed = ParsedQuery::default();
+    for caps in regex_cache.unit_regex.captures_iter(s) {
+        if let Some(number) = caps.name("number") {
+            if let Ok(value) = number.as_str().parse() {
+                parsed.numbers.push(Quantity {
+                    value,
+                    start: number.start(),
+                    end: number.end(),
+                });
+            }
+        } else if let Some(si) = caps.name("si") {
+            if let Some(index) = SI_PREFIXES
+                .iter()
+                .position(|prefix| prefix.matches(si.as_str()))
+            {
+                parsed.prefixes.push(Token {
+                    index,
+                    start: si.start(),
+                    end: si.end(),
+                });
+            }
+        } else if let Some(time) = caps.name("time") {
+            let index = TIME_UNITS
+                .iter()
+                .position(|unit| unit.matches(time.as_str()));
+            let prefixed = parsed
+                .prefixes
+                .last()
+                .is_some_and(|prefix| prefix.end == time.start());
+            let word_start = s[..time.start()]
+                .chars()
+                .next_back()
+                .is_none_or(|c| !c.is_alphabetic());
+            if index == Some(DAY_INDEX) && !prefixed && !word_start {
+                continue;
+            }
+            if let Some(index) = index {
+                parsed.times.push(Token {
+                    index,
+                    start: time.start(),
+                    end: time.end(),
+                });
+            }
+        }
+    }
+    parsed
+}
+
+/// Works out which unit `s` asks for. At most `max_prefixes` SI prefixes
+/// are applied, if given; the rest are counted in `Unit::ignored_prefixes`.
+/// With `simplify`, stacked prefixes are folded into one where they can be.
+pub fn find_multiplier_from(
+    s: &str,
+    regex_cache: &CustomRegexCache,
+    max_prefixes: Option<usize>,
+    simplify: bool,
+) -> Unit {
+    let parsed = parse_query(s, regex_cache);
+
+    // Custom units are named on purpose, so they beat any built-in unit
+    // that happens to appear too
+    let custom = regex_cache
+        .custom_units
+        .iter()
+        .find_map(|unit| Some((unit, s.find(unit.name.as_str())?)));
+    // Otherwise prioritize the longest time amounts, and the mention of it
+    // with the most prefixes attached
+    let time = parsed
+        .times
+        .iter()
+        .max_by_key(|time| (time.index, parsed.prefixes_before(s, time.start).len()));
+
+    let prefixes = match (custom, time) {
+        (Some((_, start)), _) => parsed.prefixes_before(s, start),
+        (None, Some(time)) => parsed.prefixes_before(s, time.start),
+        // No unit to attach them to, so they all modify the default
+        (None, None) => parsed.prefixes.iter().map(|prefix| prefix.index).collect(),
+    };
+    let applied = max_prefixes.map_or(prefixes.len(), |max| max.min(prefixes.len()));
+    let applied = within_f64_range(&prefixes[..applied]);
+    let stacked = if simplify {
+        simplify_prefixes(&prefixes[..applied])
+    } else {
+        prefixes[..applied].to_vec()
+    };
+    let mut unit = match (custom, time) {
+        (Some((custom, _)), _) => prefixed_unit(custom.seconds, &custom.plural(), &stacked),
+        (None, Some(time)) => build_unit(time.index, &stacked),
+        (None, None) => build_unit(DEFAULT_TIME_INDEX, &stacked),
+    };
+    unit.ignored_prefixes = prefixes.len() - applied;
+    unit
+}
+
+/// The single SI prefix worth the same as all of `si_indices` together, so
+/// "megakilo" becomes "giga", or none at all if they cancel out like
+/// "kilomilli" does. Stacks that add up to a power with no prefix of its own
+/// are left as they were.
+pub fn simplify_prefixes(si_indices: &[usize]) -> Vec<usize> {
+    let power: i32 = si_indices.iter().map(|&i| SI_PREFIXES[i].power).sum();
+    if power == 0 {
+        return Vec::new();
+    }
+    match prefix_with_power(power) {
+        Some(index) => vec![index],
+        None => si_indices.to_vec(),
+    }
+}
+
+/// Furthest total SI power a unit can get to. Past about 10^300 an f64
+/// multiplier overflows to infinity or underflows to zero, and countdowns
+/// come out as 0 or infinite, so prefixes beyond this are ignored.
+const MAX_TOTAL_SI_POWER: i32 = 240;
+
+/// How many of `si_indices`, from the front, can be stacked before the
+/// total power leaves what an f64 can hold
+fn within_f64_range(si_indices: &[usize]) -> usize {
+    let mut power = 0;
+    for (count, &i) in si_indices.iter().enumerate() {
+        power += SI_PREFIXES[i].power;
+        if power.abs() > MAX_TOTAL_SI_POWER {
+            return count;
+        }
+    }
+    si_indices.len()
+}
+
+/// A time unit with the given SI prefixes stacked on top.
+pub fn build_unit(time_index: usize, si_indices: &[usize]) -> Unit {
+    let mut unit = prefixed_unit(
+        TIME_UNITS[time_index].seconds as f64,
+        TIME_UNITS[time_index].plural,
+        si_indices,
+    );
+    unit.base = Some(time_index);
+    unit
+}
+
+/// A unit `seconds` long named `plural`, with the SI prefixes at
+/// `si_indices` applied
+fn prefixed_unit(seconds: f64, plural: &str, si_indices: &[usize]) -> Unit {
+    // For all powers mentioned, add it to the multiplier and also to a prefix string
+    let mut si_power = 3;
+    let mut unit_string = String::new();
+    for &i in si_indices {
+        si_power += SI_PREFIXES[i].power;
+        unit_string.push_str(SI_PREFIXES[i].name);
+    }
+    unit_string.push_str(plural);
+
+    let multiplier = seconds * 10f64.powf(si_power as f64);
+
+    Unit {
+        multiplier,
+        name: unit_string,
+        approximate: false,
+        ignored_prefixes: 0,
+        base: None,
+    }
+}
+
+/// The unit used when a query doesn't ask for one
+pub fn default_unit() -> Unit {
+    build_unit(DEFAULT_TIME_INDEX, &[])
+}
+
+/// Picks a random unit, maybe with a random SI prefix, for "surprise me"
+/// queries with `until` milliseconds left. Combinations that give an
+/// unreadably large or small answer are rerolled, falling back to the
+/// default unit if none are found.
+pub fn random_unit<R: Rng>(until: i64, rng: &mut R) -> Unit {
+    for _ in 0..MAX_RANDOM_ATTEMPTS {
+        let time_index = rng.gen_range(0, TIME_UNITS.len());
+        let prefixes: Vec<usize> = if rng.gen_bool(0.5) {
+            vec![rng.gen_range(0, SI_PREFIXES.len())]
+        } else {
+            Vec::new()
+        };
+        let unit = build_unit(time_index, &prefixes);
+        let value = unit.value(until).abs();
+        if value.is_finite() && value >= READABLE_RANGE.0 && value <= READABLE_RANGE.1 {
+            return unit;
+        }
+    }
+    default_unit()
+}
+
+/// Every time unit with no SI prefix
+fn plain_units() -> Vec<Unit> {
+    (0..TIME_UNITS.len()).map(|i| build_unit(i, &[])).collect()
+}
+
+/// How far from a comfortable number near 1 `unit` puts `ms`, in orders of
+/// magnitude
+fn awkwardness(unit: &Unit, ms: i64) -> f64 {
+    let value = unit.value(ms).abs();
+    if value == 0.0 {
+        0.0
+    } else {
+        value.log10().abs()
+    }
+}
+
+/// The plain unit that reads most naturally for a duration of `ms`
+pub fn auto_unit(ms: i64) -> Unit {
+    plain_units()
+        .into_iter()
+        .min_by(|a, b| awkwardness(a, ms).total_cmp(&awkwardness(b, ms)))
+        .unwrap_or_else(default_unit)
+}
+
+/// The opposite of `auto_unit`: whichever unit makes `ms` hardest to read,
+/// like seconds for next week or fortnights for the next few minutes.
+pub fn worst_unit(ms: i64) -> Unit {
+    let mut units = plain_units();
+    units.push(Unit {
+        multiplier: 14.0 * 86_400_000.0,
+        name: "fortnights".to_string(),
+        approximate: false,
+        ignored_prefixes: 0,
+        base: None,
+    });
+    units
+        .into_iter()
+        .max_by(|a, b| awkwardness(a, ms).total_cmp(&awkwardness(b, ms)))
+        .unwrap_or_else(default_unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    const HOUR_MS: i64 = 3_600_000;
+
+    fn prefix(name: &str) -> usize {
+        SI_PREFIXES
+            .iter()
+            .position(|prefix| prefix.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn finds_units_and_prefixes_with_their_positions() {
+        let parsed = parse_query(
+            "kilo megaseconds or hours",
+            &CustomRegexCache::new(Vec::new()),
+        );
+        assert_eq!(
+            parsed.prefixes,
+            vec![
+                Token {
+                    index: prefix("kilo"),
+                    start: 0,
+                    end: 4
+                },
+                Token {
+                    index: prefix("mega"),
+                    start: 5,
+                    end: 9
+                },
+            ]
+        );
+        let times: Vec<(usize, usize)> = parsed.times.iter().map(|t| (t.index, t.start)).collect();
+        assert_eq!(times, vec![(0, 9), (2, 20)]);
+    }
+
+    #[test]
+    fn attaches_only_adjacent_prefixes() {
+        let cache = CustomRegexCache::new(Vec::new());
+        let s = "kilo megaseconds";
+        assert_eq!(
+            parse_query(s, &cache).prefixes_before(s, 9),
+            vec![prefix("kilo"), prefix("mega")]
+        );
+        let s = "kilo what seconds";
+        assert!(parse_query(s, &cache).prefixes_before(s, 10).is_empty());
+    }
+
+    /// The number attached to each time unit in `s`, in order
+    fn numbers_for_units(s: &str) -> Vec<Option<f64>> {
+        let parsed = parse_query(s, &CustomRegexCache::new(Vec::new()));
+        parsed
+            .times
+            .iter()
+            .map(|time| parsed.number_before(s, time.start))
+            .collect()
+    }
+
+    #[test]
+    fn attaches_numbers_to_the_unit_after_them() {
+        assert_eq!(numbers_for_units("3 kilo 5 days"), vec![Some(5.0)]);
+        assert_eq!(
+            numbers_for_units("2.5 hours and 3 minutes"),
+            vec![Some(2.5), Some(3.0)]
+        );
+        assert_eq!(numbers_for_units("10days"), vec![Some(10.0)]);
+        // Through the unit's prefixes, but not past other words
+        assert_eq!(numbers_for_units("5 kilodays"), vec![Some(5.0)]);
+        assert_eq!(numbers_for_units("5 more days"), vec![None]);
+    }
+
+    #[test]
+    fn leaves_numbers_after_a_unit_alone() {
+        let s = "5 days kilo";
+        let parsed = parse_query(s, &CustomRegexCache::new(Vec::new()));
+        assert_eq!(numbers_for_units(s), vec![Some(5.0)]);
+        // The trailing prefix doesn't reach back to the days either
+        assert!(parsed.prefixes_before(s, parsed.times[0].start).is_empty());
+        assert_eq!(numbers_for_units("days 5"), vec![None]);
+    }
+
+    fn unit_for(s: &str, max_prefixes: Option<usize>) -> Unit {
+        find_multiplier_from(s, &CustomRegexCache::new(Vec::new()), max_prefixes, false)
+    }
+
+    #[test]
+    fn keeps_the_time_unit_table_consistent() {
+        for pair in TIME_UNITS.windows(2) {
+            assert!(
+                pair[0].seconds < pair[1].seconds,
+                "{} is out of order",
+                pair[1].plural
+            );
+        }
+        for unit in TIME_UNITS.iter() {
+            assert!(unit.plural.starts_with(unit.singular));
+            for alt in unit.pattern.split('|') {
+                assert!(unit.matches(alt));
+            }
+        }
+        assert_eq!(TIME_UNITS[DEFAULT_TIME_INDEX].plural, "hours");
+        assert_eq!(TIME_UNITS[DAY_INDEX].plural, "days");
+    }
+
+    #[test]
+    fn reads_every_time_unit_out_of_a_query() {
+        for unit in TIME_UNITS.iter() {
+            let s = format!("how many {} until tuesday", unit.plural);
+            assert_eq!(unit_for(&s, None).name, unit.plural);
+        }
+        assert_eq!(unit_for("until tuesday", None).name, "hours");
+    }
+
+    #[test]
+    fn keeps_the_si_prefix_table_consistent() {
+        for pair in SI_PREFIXES.windows(2) {
+            assert!(
+                pair[0].power < pair[1].power,
+                "{} is out of order",
+                pair[1].name
+            );
+        }
+        for (i, prefix) in SI_PREFIXES.iter().enumerate() {
+            assert!(
+                prefix.matches(prefix.name),
+                "{} doesn't match itself",
+                prefix.name
+            );
+            assert_eq!(prefix_with_power(prefix.power), Some(i));
+        }
+    }
+
+    #[test]
+    fn reads_every_si_prefix_out_of_a_query() {
+        for prefix in SI_PREFIXES.iter() {
+            let unit = unit_for(&format!("{}seconds until tuesday", prefix.name), None);
+            assert_eq!(unit.name, format!("{}seconds", prefix.name));
+        }
+    }
+
+    #[test]
+    fn stops_stacking_before_f64_overflows() {
+        let yotta = prefix("yotta");
+        assert_eq!(within_f64_range(&[yotta; 10]), 10);
+        assert_eq!(within_f64_range(&[yotta; 20]), 10);
+        let yocto = prefix("yocto");
+        assert_eq!(within_f64_range(&[yotta, yocto, yotta]), 3);
+        assert!(unit_for(&format!("{}seconds", "yotta".repeat(20)), None)
+            .multiplier
+            .is_finite());
+    }
+
+    #[test]
+    fn folds_stacked_prefixes_into_one() {
+        assert_eq!(
+            simplify_prefixes(&[prefix("mega"), prefix("kilo")]),
+            vec![prefix("giga")]
+        );
+        assert_eq!(
+            simplify_prefixes(&[prefix("kilo"), prefix("milli")]),
+            Vec::<usize>::new()
+        );
+        assert_eq!(simplify_prefixes(&[prefix("deci")]), vec![prefix("deci")]);
+    }
+
+    #[test]
+    fn leaves_stacks_without_a_single_prefix_alone() {
+        let stack = vec![prefix("kilo"), prefix("yotta")];
+        assert_eq!(simplify_prefixes(&stack), stack);
+    }
+
+    #[test]
+    fn only_simplifies_unit_names_when_asked() {
+        let cache = CustomRegexCache::new(Vec::new());
+        let s = "megakiloseconds until tuesday";
+        assert_eq!(
+            find_multiplier_from(s, &cache, None, true).name,
+            "gigaseconds"
+        );
+        assert_eq!(
+            find_multiplier_from(s, &cache, None, false).name,
+            "megakiloseconds"
+        );
+        let unit = find_multiplier_from("kilomilliseconds until tuesday", &cache, None, true);
+        assert_eq!(unit.name, "seconds");
+        assert_eq!(unit.multiplier, 1000.0);
+    }
+
+    #[test]
+    fn does_not_read_days_out_of_weekday_names() {
+        assert_eq!(unit_for("see you tuesday", None).name, "hours");
+        assert_eq!(unit_for("is it today or sunday", None).name, "hours");
+    }
+
+    #[test]
+    fn reads_days_as_their_own_word() {
+        assert_eq!(unit_for("days until tuesday", None).name, "days");
+        assert_eq!(unit_for("10days until tuesday", None).name, "days");
+    }
+
+    #[test]
+    fn reads_prefixed_days() {
+        assert_eq!(unit_for("kilodays until tuesday", None).name, "kilodays");
+        assert_eq!(
+            unit_for("how many centiday until tuesday", None).name,
+            "centidays"
+        );
+    }
+
+    #[test]
+    fn reads_prefixed_weeks_and_years() {
+        let unit = unit_for("how many milliweeks until tuesday", None);
+        assert_eq!(unit.name, "milliweeks");
+        assert_eq!(unit.multiplier, 604_800.0);
+        let unit = unit_for("kiloyears until tuesday", None);
+        assert_eq!(unit.name, "kiloyears");
+        assert_eq!(unit.multiplier, 31_557_600_000_000.0);
+        let unit = unit_for("how many microyears", None);
+        assert_eq!(unit.name, "microyears");
+        assert!((unit.multiplier - 31_557.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn applies_every_prefix_without_a_cap() {
+        let unit = unit_for("how many megakilohours", None);
+        assert_eq!(unit.name, "megakilohours");
+        assert_eq!(unit.ignored_prefixes, 0);
+    }
+
+    #[test]
+    fn caps_the_number_of_prefixes_applied() {
+        let unit = unit_for("how many megakilohours", Some(1));
+        assert_eq!(unit.name, "megahours");
+        assert_eq!(unit.multiplier, 3_600_000.0 * 1e6);
+        assert_eq!(unit.ignored_prefixes, 1);
+
+        let unit = unit_for("how many megakilohours", Some(0));
+        assert_eq!(unit.name, "hours");
+        assert_eq!(unit.ignored_prefixes, 2);
+    }
+
+    fn custom_units(units: &[(&str, f64)]) -> Vec<CustomUnit> {
+        let configured = units
+            .iter()
+            .map(|&(name, seconds)| (name.to_string(), seconds))
+            .collect();
+        load_custom_units(&configured)
+    }
+
+    #[test]
+    fn loads_custom_units_longest_first() {
+        let units = custom_units(&[("Coffee", 300.0), ("coffee break", 900.0)]);
+        let names: Vec<&str> = units.iter().map(|unit| unit.name.as_str()).collect();
+        assert_eq!(names, vec!["coffee break", "coffee"]);
+    }
+
+    #[test]
+    fn skips_bad_custom_units() {
+        let units = custom_units(&[
+            ("nap", 0.0),
+            ("forever", f64::INFINITY),
+            ("hour", 60.0),
+            ("kilonap", 60.0),
+            ("", 60.0),
+        ]);
+        assert!(units.is_empty());
+    }
+
+    #[test]
+    fn answers_in_custom_units() {
+        let cache = CustomRegexCache::new(custom_units(&[("coffee", 300.0)]));
+        let unit = find_multiplier_from("how many kilocoffee until tuesday", &cache, None, false);
+        assert_eq!(unit.name, "kilocoffees");
+        assert_eq!(unit.value(1_500_000), 5.0 / 1000.0);
+        // Named on purpose, so it beats a built-in unit in the same query
+        let unit = find_multiplier_from("hours, in coffee", &cache, None, false);
+        assert_eq!(unit.name, "coffees");
+    }
+
+    #[test]
+    fn picks_the_most_natural_unit() {
+        assert_eq!(auto_unit(132 * HOUR_MS).name, "weeks");
+        assert_eq!(auto_unit(90_000).name, "minutes");
+    }
+
+    #[test]
+    fn picks_the_least_natural_unit() {
+        assert_eq!(worst_unit(132 * HOUR_MS).name, "seconds");
+        assert_eq!(worst_unit(300_000).name, "years");
+    }
+
+    fn guild_with_unit_names() -> GuildConfig {
+        GuildConfig {
+            unit_names: ["secs", "mins", "hrs", "days", "wks", "yrs"]
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            ..GuildConfig::default()
+        }
+    }
+
+    #[test]
+    fn shows_the_guilds_unit_names_after_prefixes() {
+        let guild = guild_with_unit_names();
+        let milli = SI_PREFIXES
+            .iter()
+            .position(|prefix| prefix.name == "milli")
+            .unwrap();
+        assert_eq!(build_unit(2, &[]).display_name(&guild), "hrs");
+        assert_eq!(build_unit(2, &[milli]).display_name(&guild), "millihrs");
+        assert_eq!(build_unit(2, &[]).render(2 * HOUR_MS, &guild), "2 hrs");
+    }
+
+    #[test]
+    fn keeps_the_usual_names_otherwise() {
+        let partial = GuildConfig {
+            unit_names: vec!["secs".to_string()],
+            ..GuildConfig::default()
+        };
+        assert_eq!(build_unit(0, &[]).display_name(&partial), "seconds");
+        // Units with no base to rename keep their own name
+        let guild = guild_with_unit_names();
+        assert_eq!(Unit::heartbeats(70.0).display_name(&guild), "heartbeats");
+    }
+
+    #[test]
+    fn scales_values_by_powers_of_a_thousand() {
+        assert_eq!(engineering(475_200.0), (475.2, Some(prefix("kilo"))));
+        assert_eq!(engineering(0.0055), (5.5, Some(prefix("milli"))));
+        assert_eq!(engineering(132.0), (132.0, None));
+        assert_eq!(engineering(0.0), (0.0, None));
+    }
+
+    #[test]
+    fn stops_scaling_past_the_last_prefix() {
+        let (value, prefix_index) = engineering(1e30);
+        assert_eq!(prefix_index, Some(prefix("yotta")));
+        assert!((value - 1e6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn renders_plain_units_in_engineering_notation() {
+        let guild = GuildConfig {
+            number_style: NumberStyle::Engineering,
+            ..GuildConfig::default()
+        };
+        assert_eq!(
+            build_unit(0, &[]).render(132 * HOUR_MS, &guild),
+            "475.2 kiloseconds"
+        );
+        // Already prefixed units are left as asked for
+        let kilo = build_unit(0, &[prefix("kilo")]);
+        assert_eq!(kilo.render(132 * HOUR_MS, &guild), "475.2 kiloseconds");
+        let milli = build_unit(0, &[prefix("milli")]);
+        assert_eq!(milli.render(2000, &guild), "2000 milliseconds");
+    }
+
+    fn guild_allowing(units: &[&str], si_prefixes_allowed: bool) -> GuildConfig {
+        GuildConfig {
+            allowed_units: units.iter().map(|unit| unit.to_string()).collect(),
+            si_prefixes_allowed,
+            ..GuildConfig::default()
+        }
+    }
+
+    #[test]
+    fn allows_only_listed_units() {
+        let guild = guild_allowing(&["hours", "days"], true);
+        assert!(is_allowed(&build_unit(2, &[]), &guild));
+        assert!(is_allowed(&build_unit(2, &[prefix("kilo")]), &guild));
+        assert!(!is_allowed(&build_unit(0, &[]), &guild));
+        // Units that aren't built on a time unit are always fine
+        assert!(is_allowed(&Unit::heartbeats(70.0), &guild));
+    }
+
+    #[test]
+    fn can_disallow_prefixes() {
+        let guild = guild_allowing(&[], false);
+        assert!(is_allowed(&build_unit(0, &[]), &guild));
+        assert!(!is_allowed(&build_unit(0, &[prefix("kilo")]), &guild));
+    }
+
+    #[test]
+    fn swaps_in_the_nearest_allowed_unit() {
+        let guild = guild_allowing(&["hours", "weeks"], true);
+        assert_eq!(
+            nearest_allowed(&build_unit(0, &[]), &guild).unwrap().name,
+            "hours"
+        );
+        assert_eq!(
+            nearest_allowed(&build_unit(3, &[]), &guild).unwrap().name,
+            "weeks"
+        );
+        assert!(
+            nearest_allowed(&build_unit(0, &[]), &guild_allowing(&["fortnights"], true)).is_none()
+        );
+    }
+
+    #[test]
+    fn counts_heartbeats_at_the_given_rate() {
+        assert_eq!(Unit::heartbeats(60.0).value(60_000), 60.0);
+        assert_eq!(Unit::heartbeats(70.0).value(HOUR_MS), 4200.0);
+    }
+
+    #[test]
+    fn picks_the_same_random_unit_for_the_same_seed() {
+        let first = random_unit(132 * HOUR_MS, &mut StdRng::seed_from_u64(7));
+        let second = random_unit(132 * HOUR_MS, &mut StdRng::seed_from_u64(7));
+        assert_eq!(first.name, second.name);
+    }
+
+    #[test]
+    fn picks_random_units_with_readable_values() {
+        let until = 132 * HOUR_MS;
+        for seed in 0..50 {
+            let unit = random_unit(until, &mut StdRng::seed_from_u64(seed));
+            let value = unit.value(until);
+            assert!(
+                value >= READABLE_RANGE.0 && value <= READABLE_RANGE.1,
+                "seed {} gave {} {}",
+                seed,
+                value,
+                unit.name
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_random_units_readable_for_far_off_countdowns() {
+        // Ten weeks out, like the 10th Tuesday
+        let until = 10 * 7 * 24 * HOUR_MS;
+        for seed in 0..50 {
+            let value = random_unit(until, &mut StdRng::seed_from_u64(seed)).value(until);
+            assert!(value >= READABLE_RANGE.0 && value <= READABLE_RANGE.1);
+        }
+    }
+
+    #[test]
+    fn rounds_heartbeats_off() {
+        let guild = GuildConfig::default();
+        assert_eq!(
+            Unit::heartbeats(70.0).render(HOUR_MS + 500, &guild),
+            "≈ 4,201 heartbeats"
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use serenity::prelude::*;
+
+/// When each voice channel last had a countdown posted for it
+#[derive(Default)]
+pub struct VoiceDebounce {
+    last_posted: HashMap<u64, Instant>,
+}
+
+impl VoiceDebounce {
+    /// Decides whether a voice state change in a configured channel should
+    /// get a countdown, and records the post if so. Only the first person
+    /// joining an empty channel counts, and only once per `debounce`.
+    pub fn should_announce(
+        &mut self,
+        channel: u64,
+        joined: bool,
+        occupants: usize,
+        debounce: Duration,
+        now: Instant,
+    ) -> bool {
+        if !joined || occupants != 1 {
+            return false;
+        }
+        if let Some(last) = self.last_posted.get(&channel) {
+            if now.duration_since(*last) < debounce {
+                return false;
+            }
+        }
+        self.last_posted.insert(channel, now);
+        true
+    }
+}
+
+pub struct VoiceDebounceKey;
+impl TypeMapKey for VoiceDebounceKey {
+    type Value = VoiceDebounce;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEBOUNCE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn announces_the_first_person_joining() {
+        let mut debounce = VoiceDebounce::default();
+        assert!(debounce.should_announce(1, true, 1, DEBOUNCE, Instant::now()));
+    }
+
+    #[test]
+    fn ignores_leaving_and_joining_a_busy_channel() {
+        let mut debounce = VoiceDebounce::default();
+        let now = Instant::now();
+        assert!(!debounce.should_announce(1, false, 0, DEBOUNCE, now));
+        assert!(!debounce.should_announce(1, true, 2, DEBOUNCE, now));
+    }
+
+    #[test]
+    fn waits_out_the_debounce_per_channel() {
+        let mut debounce = VoiceDebounce::default();
+        let start = Instant::now();
+        assert!(debounce.should_announce(1, true, 1, DEBOUNCE, start));
+        let soon = start + Duration::from_secs(30);
+        assert!(!debounce.should_announce(1, true, 1, DEBOUNCE, soon));
+        assert!(debounce.should_announce(2, true, 1, DEBOUNCE, soon));
+        assert!(debounce.should_announce(1, true, 1, DEBOUNCE, start + DEBOUNCE));
+    }
+}
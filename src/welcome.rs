@@ -0,0 +1,75 @@
+use serenity::{model::guild::Guild, prelude::*};
+
+use crate::{announce::pick_announcement_channel, ConfigKey, StateKey};
+
+/// What a new guild hears from the bot, unless `Config::welcome_message`
+/// says otherwise
+pub const DEFAULT_WELCOME: &str = "Hi! I count down to Tuesday. Mention Tuesday in any \
+                                   message to see how long is left, like \"how many hours \
+                                   until tuesday\", or try tue!help for everything else.";
+
+/// Whether to greet a guild we just got a `guild_create` for. Guilds we
+/// were already in show up at startup and after reconnecting too, so only
+/// ones Discord says are new, and that we haven't greeted before, count.
+pub fn should_greet(enabled: bool, is_new: bool, already_greeted: bool) -> bool {
+    enabled && is_new && !already_greeted
+}
+
+/// Introduces the bot to `guild` if it just joined, remembering that it did
+/// so the guild is never greeted twice.
+pub fn greet(ctx: &Context, guild: &Guild, is_new: bool) {
+    let message = {
+        let mut data = ctx.data.write();
+        let config = data
+            .get::<ConfigKey>()
+            .expect("Expected to find config in context");
+        let enabled = config.welcome_on_join;
+        let message = config
+            .welcome_message
+            .clone()
+            .unwrap_or_else(|| DEFAULT_WELCOME.to_string());
+        let state = data
+            .get_mut::<StateKey>()
+            .expect("Expected to find bot state in context");
+        if !should_greet(enabled, is_new, state.greeted(guild.id.0)) {
+            return;
+        }
+        // Marked before sending, so a guild we can't post in isn't retried
+        // on every reconnect
+        state.mark_greeted(guild.id.0);
+        state.save();
+        message
+    };
+
+    // A guild we just joined can't have configured anywhere yet
+    let channel = match pick_announcement_channel(ctx, guild, None) {
+        Some(channel) => channel,
+        None => return,
+    };
+    if let Err(why) = channel.say(&ctx.http, message) {
+        println!("Error sending welcome message: {:?}", why);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::BotState;
+
+    #[test]
+    fn greets_only_new_guilds_once() {
+        assert!(should_greet(true, true, false));
+        assert!(!should_greet(true, false, false));
+        assert!(!should_greet(true, true, true));
+        assert!(!should_greet(false, true, false));
+    }
+
+    #[test]
+    fn remembers_greeted_guilds() {
+        let mut state = BotState::default();
+        assert!(!state.greeted(1));
+        state.mark_greeted(1);
+        assert!(state.greeted(1));
+        assert!(!state.greeted(2));
+    }
+}